@@ -8,7 +8,7 @@ pub struct Config {
 	#[arg(long, default_value = "[::]:0")]
 	pub bind: net::SocketAddr,
 
-	/// Connect to the given URL starting with https://
+	/// Connect to the given URL starting with https:// for WebTransport, or moqt:// for QUIC.
 	#[arg(value_parser = moq_url)]
 	pub url: Url,
 
@@ -25,6 +25,14 @@ pub struct Config {
 	#[arg(long)]
 	pub tls_disable_verify: bool,
 
+	/// Use the newer IETF draft wire format instead of the fork, to interoperate with other implementations.
+	#[arg(long)]
+	pub ietf: bool,
+
+	/// Log the session stats (RTT, congestion window, throughput, loss) every N seconds, or never if zero.
+	#[arg(long, default_value = "0")]
+	pub stats: u64,
+
 	/// Publish the current time to the relay, otherwise only subscribe.
 	#[arg(long)]
 	pub publish: bool,
@@ -32,14 +40,18 @@ pub struct Config {
 	/// The name of the clock track.
 	#[arg(long, default_value = "now")]
 	pub track: String,
+
+	/// Ask for the clock track to be delivered as datagrams instead of streams, when subscribing.
+	#[arg(long)]
+	pub datagrams: bool,
 }
 
 fn moq_url(s: &str) -> Result<Url, String> {
 	let url = Url::try_from(s).map_err(|e| e.to_string())?;
 
 	// Make sure the scheme is moq
-	if url.scheme() != "https" {
-		return Err("url scheme must be https:// for WebTransport".to_string());
+	if url.scheme() != "https" && url.scheme() != "moqt" {
+		return Err("url scheme must be https:// for WebTransport or moqt:// for QUIC".to_string());
 	}
 
 	Ok(url)
@@ -3,6 +3,7 @@ use std::time;
 use anyhow::Context;
 use moq_transport::{
 	cache::{fragment, segment, track},
+	session::Subscription,
 	VarInt,
 };
 
@@ -89,16 +90,16 @@ impl Publisher {
 	}
 }
 pub struct Subscriber {
-	track: track::Subscriber,
+	subscription: Subscription,
 }
 
 impl Subscriber {
-	pub fn new(track: track::Subscriber) -> Self {
-		Self { track }
+	pub fn new(subscription: Subscription) -> Self {
+		Self { subscription }
 	}
 
 	pub async fn run(mut self) -> anyhow::Result<()> {
-		while let Some(segment) = self.track.segment().await.context("failed to get segment")? {
+		while let Some(segment) = self.subscription.segment().await.context("failed to get segment")? {
 			log::debug!("got segment: {:?}", segment);
 			tokio::spawn(async move {
 				if let Err(err) = Self::recv_segment(segment).await {
@@ -111,24 +112,35 @@ impl Subscriber {
 	}
 
 	async fn recv_segment(mut segment: segment::Subscriber) -> anyhow::Result<()> {
-		let first = segment
-			.fragment()
-			.await
-			.context("failed to get first fragment")?
-			.context("no fragments in segment")?;
-
-		log::debug!("got first: {:?}", first);
+		let mut base = None;
+
+		// Fragments are returned in sequence order, so the base (zero) always comes first.
+		while let Some(next) = segment.next_fragment().await? {
+			let fragment = match next {
+				segment::Ordered::Fragment(fragment) => fragment,
+				segment::Ordered::Gap { missing, next } => {
+					// Wait for the base, but skip any missing deltas since they're stale anyway.
+					if base.is_some() {
+						log::debug!("skipping missing fragments: missing={} next={}", missing, next);
+						segment.skip();
+					}
+
+					continue;
+				}
+			};
 
-		if first.sequence.into_inner() != 0 {
-			anyhow::bail!("first object must be zero; I'm not going to implement a reassembly buffer");
-		}
+			log::debug!("next fragment: {:?}", fragment);
 
-		let base = Self::recv_fragment(first, Vec::new()).await?;
+			let Some(base) = &base else {
+				// The first fragment is the base, which the later deltas are appended to.
+				anyhow::ensure!(fragment.sequence.into_inner() == 0, "first object must be zero");
 
-		log::debug!("read base: {:?}", String::from_utf8_lossy(&base));
+				let value = Self::recv_fragment(fragment, Vec::new()).await?;
+				log::debug!("read base: {:?}", String::from_utf8_lossy(&value));
+				base = Some(value);
+				continue;
+			};
 
-		while let Some(fragment) = segment.fragment().await? {
-			log::debug!("next fragment: {:?}", fragment);
 			let value = Self::recv_fragment(fragment, base.clone()).await?;
 			let str = String::from_utf8(value).context("invalid UTF-8")?;
 
@@ -1,4 +1,10 @@
-use std::{fs, io, sync::Arc, time};
+use std::{
+	fs,
+	future::{self, Future},
+	io,
+	sync::Arc,
+	time,
+};
 
 use anyhow::Context;
 use clap::Parser;
@@ -6,7 +12,12 @@ use clap::Parser;
 mod cli;
 mod clock;
 
-use moq_transport::cache::broadcast;
+use moq_transport::{
+	cache::broadcast,
+	session::{self, SubscribeOptions},
+	transport,
+};
+use url::Url;
 
 // TODO: clap complete
 
@@ -57,53 +68,178 @@ async fn main() -> anyhow::Result<()> {
 		tls_config.dangerous().set_certificate_verifier(Arc::new(noop));
 	}
 
-	tls_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()]; // this one is important
-
-	let arc_tls_config = std::sync::Arc::new(tls_config);
-	let quinn_client_config = quinn::ClientConfig::new(arc_tls_config);
-
-	let mut endpoint = quinn::Endpoint::client(config.bind)?;
-	endpoint.set_default_client_config(quinn_client_config);
-
-	log::info!("connecting to relay: url={}", config.url);
-
-	let session = webtransport_quinn::connect(&endpoint, &config.url)
-		.await
-		.context("failed to create WebTransport session")?;
+	let endpoint = quinn::Endpoint::client(config.bind)?;
 
 	let (mut publisher, subscriber) = broadcast::new(""); // TODO config.namespace
 
-	if config.publish {
-		let session = moq_transport::session::Client::publisher(session, subscriber)
-			.await
-			.context("failed to create MoQ Transport session")?;
+	let mut url = config.url.clone();
+	let mut session = connect(&endpoint, &tls_config, &url).await?;
 
-		let publisher = publisher
+	// Move to a new session after a GOAWAY, until a session ends on its own.
+	if config.publish {
+		let track = publisher
 			.create_track(&config.track)
 			.context("failed to create clock track")?;
-		let clock = clock::Publisher::new(publisher);
+
+		// The clock is written to the local broadcast, so it keeps running while moving between sessions.
+		let clock = clock::Publisher::new(track);
+
+		let sessions = async {
+			while let Some(next) = publish(&config, &endpoint, &tls_config, &url, session, subscriber.clone()).await? {
+				(url, session) = next;
+			}
+
+			anyhow::Ok(())
+		};
 
 		tokio::select! {
-			res = session.run() => res.context("session error")?,
+			res = sessions => res?,
 			res = clock.run() => res.context("clock error")?,
 		}
 	} else {
-		let session = moq_transport::session::Client::subscriber(session, publisher)
+		while let Some(next) = subscribe(&config, &endpoint, &tls_config, &url, session, publisher.clone()).await? {
+			(url, session) = next;
+		}
+	}
+
+	Ok(())
+}
+
+// The next session to use after a GOAWAY, along with its URL.
+type Next = Option<(Url, Box<dyn transport::Session>)>;
+
+// Publish the broadcast over the session, returning the next session after a GOAWAY.
+async fn publish(
+	config: &cli::Config,
+	endpoint: &quinn::Endpoint,
+	tls: &rustls::ClientConfig,
+	url: &Url,
+	session: Box<dyn transport::Session>,
+	broadcast: broadcast::Subscriber,
+) -> anyhow::Result<Next> {
+	let session = client(config, url)
+		.publisher(session, broadcast)
+		.await
+		.context("failed to create MoQ Transport session")?;
+
+	let stats = session.clone();
+
+	tokio::select! {
+		res = session.clone().run() => res.context("session error")?,
+		next = reconnect(endpoint, tls, url, session.going_away()) => return Ok(Some(next)),
+		_ = session::log_stats(time::Duration::from_secs(config.stats), || stats.stats()) => {},
+	}
+
+	Ok(None)
+}
+
+// Subscribe to the clock over the session, returning the next session after a GOAWAY.
+async fn subscribe(
+	config: &cli::Config,
+	endpoint: &quinn::Endpoint,
+	tls: &rustls::ClientConfig,
+	url: &Url,
+	session: Box<dyn transport::Session>,
+	broadcast: broadcast::Publisher,
+) -> anyhow::Result<Next> {
+	let session = client(config, url)
+		.subscriber(session, broadcast)
+		.await
+		.context("failed to create MoQ Transport session")?;
+
+	let mut options = SubscribeOptions::new();
+	if config.datagrams {
+		options = options.with_datagram();
+	}
+
+	// Wait for SUBSCRIBE_OK, so we fail fast if the track doesn't exist.
+	let clock = async {
+		let subscription = session
+			.subscribe(&config.track, options)
 			.await
-			.context("failed to create MoQ Transport session")?;
+			.context("failed to subscribe to clock track")?;
 
-		let subscriber = subscriber
-			.get_track(&config.track)
-			.context("failed to get clock track")?;
-		let clock = clock::Subscriber::new(subscriber);
+		clock::Subscriber::new(subscription).run().await
+	};
 
-		tokio::select! {
-			res = session.run() => res.context("session error")?,
-			res = clock.run() => res.context("clock error")?,
+	let stats = session.clone();
+
+	tokio::select! {
+		res = session.clone().run() => res.context("session error")?,
+		res = clock => res.context("clock error")?,
+		next = reconnect(endpoint, tls, url, session.going_away()) => return Ok(Some(next)),
+		_ = session::log_stats(time::Duration::from_secs(config.stats), || stats.stats()) => {},
+	}
+
+	Ok(None)
+}
+
+async fn connect(
+	endpoint: &quinn::Endpoint,
+	tls: &rustls::ClientConfig,
+	url: &Url,
+) -> anyhow::Result<Box<dyn transport::Session>> {
+	// The ALPN depends on the scheme, which can change after a GOAWAY.
+	let mut tls = tls.clone();
+	tls.alpn_protocols = vec![transport::alpn(url).to_vec()];
+
+	log::info!("connecting to relay: url={}", url);
+
+	let session = transport::connect(endpoint, quinn::ClientConfig::new(Arc::new(tls)), url)
+		.await
+		.context("failed to connect")?;
+
+	Ok(session)
+}
+
+// After a GOAWAY, connect to its URL or the same URL if it's empty, while the current session keeps running.
+//
+// Never resolves if the connection fails, so the current session is kept until the relay closes it.
+async fn reconnect(
+	endpoint: &quinn::Endpoint,
+	tls: &rustls::ClientConfig,
+	url: &Url,
+	going_away: impl Future<Output = String>,
+) -> (Url, Box<dyn transport::Session>) {
+	let next = going_away.await;
+	let next = match next.as_str() {
+		"" => url.clone(),
+		next => match Url::parse(next) {
+			Ok(next) => next,
+			Err(err) => {
+				log::warn!(
+					"invalid GOAWAY url, reconnecting to the same URL: url={} err={}",
+					next,
+					err
+				);
+				url.clone()
+			}
+		},
+	};
+
+	log::info!("received GOAWAY, reconnecting: url={}", next);
+
+	match connect(endpoint, tls, &next).await {
+		Ok(session) => (next, session),
+		Err(err) => {
+			log::warn!(
+				"failed to reconnect, keeping the current session: url={} err={:#}",
+				next,
+				err
+			);
+			future::pending().await
 		}
 	}
+}
 
-	Ok(())
+fn client(config: &cli::Config, url: &Url) -> session::Client {
+	let client = match config.ietf {
+		true => session::Client::ietf(),
+		false => session::Client::default(),
+	};
+
+	// Without WebTransport, the path is sent during the MoQ handshake instead.
+	client.with_url(url)
 }
 
 pub struct NoCertificateVerification {}
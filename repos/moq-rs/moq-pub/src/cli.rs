@@ -23,7 +23,7 @@ pub struct Config {
 	#[arg(long, default_value = "1500000")]
 	pub bitrates: String,
 
-	/// Connect to the given URL starting with https://
+	/// Connect to the given URL starting with https:// for WebTransport, or moqt:// for QUIC.
 	#[arg(value_parser = moq_url)]
 	pub url: Url,
 
@@ -39,14 +39,34 @@ pub struct Config {
 	/// Fine for local development, but should be used in caution in production.
 	#[arg(long)]
 	pub tls_disable_verify: bool,
+
+	/// Use the newer IETF draft wire format instead of the fork, to interoperate with other implementations.
+	#[arg(long)]
+	pub ietf: bool,
+
+	/// Log the session stats (RTT, congestion window, throughput, loss) every N seconds, or never if zero.
+	#[arg(long, default_value = "0")]
+	pub stats: u64,
+
+	/// Coalesce small chunks into a single write of at least this many bytes, or write each chunk immediately if zero.
+	#[arg(long, default_value = "0")]
+	pub coalesce_size: usize,
+
+	/// The longest a chunk is delayed in milliseconds when using `--coalesce-size`.
+	#[arg(long, default_value = "10")]
+	pub coalesce_delay: u64,
+
+	/// Pace each stream at this bitrate in bits per second, or disable pacing if zero.
+	#[arg(long, default_value = "0")]
+	pub pacing: u64,
 }
 
 fn moq_url(s: &str) -> Result<Url, String> {
 	let url = Url::try_from(s).map_err(|e| e.to_string())?;
 
 	// Make sure the scheme is moq
-	if url.scheme() != "https" {
-		return Err("url scheme must be https:// for WebTransport".to_string());
+	if url.scheme() != "https" && url.scheme() != "moqt" {
+		return Err("url scheme must be https:// for WebTransport or moqt:// for QUIC".to_string());
 	}
 
 	Ok(url)
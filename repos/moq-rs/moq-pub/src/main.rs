@@ -9,7 +9,11 @@ use cli::*;
 mod media;
 use media::*;
 
-use moq_transport::cache::broadcast;
+use moq_transport::{
+	cache::broadcast,
+	session::{self, WriteStrategy},
+	transport,
+};
 
 // TODO: clap complete
 
@@ -63,28 +67,45 @@ async fn main() -> anyhow::Result<()> {
 		tls_config.dangerous().set_certificate_verifier(Arc::new(noop));
 	}
 
-	tls_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()]; // this one is important
+	// this one is important
+	tls_config.alpn_protocols = vec![transport::alpn(&config.url).to_vec()];
 
 	let arc_tls_config = std::sync::Arc::new(tls_config);
 	let quinn_client_config = quinn::ClientConfig::new(arc_tls_config);
 
-	let mut endpoint = quinn::Endpoint::client(config.bind)?;
-	endpoint.set_default_client_config(quinn_client_config);
+	let endpoint = quinn::Endpoint::client(config.bind)?;
 
 	log::info!("connecting to relay: url={}", config.url);
 
-	let session = webtransport_quinn::connect(&endpoint, &config.url)
+	let client = match config.ietf {
+		true => moq_transport::session::Client::ietf(),
+		false => moq_transport::session::Client::default(),
+	};
+
+	// Without WebTransport, the path is sent during the MoQ handshake instead.
+	let client = client.with_url(&config.url);
+
+	let session = transport::connect(&endpoint, quinn_client_config, &config.url)
 		.await
-		.context("failed to create WebTransport session")?;
+		.context("failed to connect")?;
 
-	let session = moq_transport::session::Client::publisher(session, subscriber)
+	let session = client
+		.publisher(session, subscriber)
 		.await
 		.context("failed to create MoQ Transport session")?;
 
+	let write = WriteStrategy::new()
+		.with_coalesce(config.coalesce_size, time::Duration::from_millis(config.coalesce_delay))
+		.with_pacing(config.pacing);
+	let session = session.with_write_strategy(write);
+
+	let stats = session.clone();
+
 	// TODO run a task that returns a 404 for all unknown subscriptions.
 	tokio::select! {
 		res = session.run() => res.context("session error")?,
 		res = media.run() => res.context("media error")?,
+		_ = session::log_stats(time::Duration::from_secs(config.stats), || stats.stats()) => {},
 	}
 
 	Ok(())
@@ -87,7 +87,7 @@ impl Media {
 		let mut catalog = broadcast.create_track(".catalog")?;
 
 		// Create the catalog track
-		Self::serve_catalog(&mut catalog, &init_track.name, &moov, &_config)?;
+		Self::serve_catalog(&mut catalog, &init_track.name, &moov, _config)?;
 
 		Ok(Media {
 			_broadcast: broadcast,
@@ -459,8 +459,8 @@ fn sample_keyframe(moof: &mp4::MoofBox) -> bool {
 				None => default_flags,
 			};
 
-			if i == 0 && trun.first_sample_flags.is_some() {
-				flags = trun.first_sample_flags.unwrap();
+			if let Some(first_sample_flags) = trun.first_sample_flags.filter(|_| i == 0) {
+				flags = first_sample_flags;
 			}
 
 			// https://chromium.googlesource.com/chromium/src/media/+/master/formats/mp4/track_run_iterator.cc#177
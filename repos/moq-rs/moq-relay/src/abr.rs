@@ -0,0 +1,595 @@
+use std::{
+	collections::HashMap,
+	fmt, fs,
+	io::Write,
+	path,
+	sync::{Arc, Mutex},
+	time,
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use moq_transport::{
+	cache::broadcast,
+	session::{AbrContext, AbrPolicy},
+	VarInt,
+};
+use serde::Deserialize;
+
+/// The query parameter a subscriber can use to choose another ABR algorithm, or `off`.
+pub const ABR_PARAM: &str = "abr";
+
+// The track containing the catalog, which lists the bitrate of each rendition.
+const CATALOG_TRACK: &str = ".catalog";
+
+// Stop waiting for a catalog that doesn't arrive in time, trying again on a later group.
+const CATALOG_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+// The weight of the newest sample in the bandwidth estimate.
+const BANDWIDTH_SMOOTHING: f64 = 0.3;
+
+/// The algorithm used to switch subscribers between renditions.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AbrAlgorithm {
+	/// Serve the track each subscriber asked for.
+	Off,
+
+	/// Pick the highest bitrate below a fraction of the estimated bandwidth.
+	Throughput,
+
+	/// Maximize the BOLA utility, using a virtual buffer since the relay can't see the player's buffer.
+	Bola,
+}
+
+impl AbrAlgorithm {
+	fn build(&self) -> Option<Box<dyn Algorithm>> {
+		match self {
+			Self::Off => None,
+			Self::Throughput => Some(Box::new(Throughput::default())),
+			Self::Bola => Some(Box::<Bola>::default()),
+		}
+	}
+}
+
+/// The ABR settings for new sessions.
+#[derive(Clone, Debug)]
+pub struct AbrConfig {
+	pub algorithm: AbrAlgorithm,
+
+	// Each decision is appended to a JSON lines file per session in this directory.
+	pub log: Option<path::PathBuf>,
+}
+
+/// A rendition in the bitrate ladder of a broadcast.
+#[derive(Clone, Debug)]
+pub struct Rendition {
+	pub track: String,
+
+	/// The bitrate in bits per second.
+	pub bitrate: u64,
+}
+
+#[derive(Deserialize)]
+struct Catalog {
+	tracks: Vec<CatalogTrack>,
+}
+
+#[derive(Deserialize)]
+struct CatalogTrack {
+	kind: Option<String>,
+	data_track: Option<String>,
+	bit_rate: Option<u64>,
+}
+
+/// Parse the video renditions from the catalog, sorted by bitrate.
+pub fn ladder(catalog: &[u8]) -> anyhow::Result<Vec<Rendition>> {
+	let catalog: Catalog = serde_json::from_slice(catalog)?;
+
+	let mut ladder: Vec<_> = catalog
+		.tracks
+		.into_iter()
+		.filter(|track| track.kind.as_deref() == Some("video"))
+		.filter_map(|track| {
+			Some(Rendition {
+				track: track.data_track?,
+				bitrate: track.bit_rate.filter(|bitrate| *bitrate > 0)?,
+			})
+		})
+		.collect();
+
+	ladder.sort_by_key(|rendition| rendition.bitrate);
+	Ok(ladder)
+}
+
+/// What an [Algorithm] knows when picking the rendition for a new group.
+#[derive(Debug)]
+pub struct Input<'a> {
+	/// The renditions sorted by bitrate, with at least two entries.
+	pub ladder: &'a [Rendition],
+
+	/// The index of the rendition currently served.
+	pub current: usize,
+
+	/// The estimated bandwidth of the connection in bits per second.
+	pub bandwidth: u64,
+
+	/// The time since the previous group, or zero for the first group.
+	pub duration: time::Duration,
+}
+
+/// Picks the rendition for each group of a single subscription.
+pub trait Algorithm: Send + fmt::Debug {
+	/// Return the index of the rendition to serve for the new group.
+	fn select(&mut self, input: &Input) -> usize;
+
+	/// The estimated buffer of the player, if the algorithm keeps one, for the decision log.
+	fn buffer(&self) -> Option<time::Duration> {
+		None
+	}
+}
+
+/// Pick the highest bitrate that fits in a fraction of the estimated bandwidth.
+#[derive(Debug)]
+pub struct Throughput {
+	safety: f64,
+}
+
+impl Default for Throughput {
+	fn default() -> Self {
+		Self { safety: 0.8 }
+	}
+}
+
+impl Algorithm for Throughput {
+	fn select(&mut self, input: &Input) -> usize {
+		let budget = input.bandwidth as f64 * self.safety;
+
+		input
+			.ladder
+			.iter()
+			.rposition(|rendition| rendition.bitrate as f64 <= budget)
+			.unwrap_or(0)
+	}
+}
+
+/// BOLA-BASIC without any feedback from the player.
+///
+/// The buffer is simulated instead: each group adds its duration, minus the time it takes to send at the estimated bandwidth.
+/// The parameters follow dash.js, aiming to reach the highest bitrate once the buffer reaches its target.
+#[derive(Debug)]
+pub struct Bola {
+	// The virtual buffer in seconds.
+	buffer: f64,
+
+	// The buffer in seconds where the lowest and highest bitrates are picked.
+	minimum: f64,
+	target: f64,
+}
+
+impl Default for Bola {
+	fn default() -> Self {
+		Self {
+			buffer: 0.0,
+			minimum: 1.0,
+			target: 4.0,
+		}
+	}
+}
+
+impl Algorithm for Bola {
+	fn select(&mut self, input: &Input) -> usize {
+		let bitrate = input.ladder[input.current].bitrate as f64;
+		let duration = input.duration.as_secs_f64();
+
+		// The previous group took this long to send, while the player consumed its duration.
+		let download = duration * bitrate / (input.bandwidth as f64).max(1.0);
+		self.buffer = (self.buffer + duration - download).clamp(0.0, self.target);
+
+		let lowest = input.ladder[0].bitrate as f64;
+		let utility = |rendition: &Rendition| (rendition.bitrate as f64 / lowest).ln() + 1.0;
+		let highest = utility(&input.ladder[input.ladder.len() - 1]);
+
+		let gp = (highest - 1.0) / (self.target / self.minimum - 1.0);
+		let v = self.minimum / gp;
+
+		let score = |rendition: &Rendition| (v * (utility(rendition) + gp) - self.buffer) / rendition.bitrate as f64;
+
+		(0..input.ladder.len())
+			.max_by(|a, b| score(&input.ladder[*a]).total_cmp(&score(&input.ladder[*b])))
+			.unwrap_or(0)
+	}
+
+	fn buffer(&self) -> Option<time::Duration> {
+		Some(time::Duration::from_secs_f64(self.buffer))
+	}
+}
+
+/// Switches the subscriptions of a session between renditions, see [AbrPolicy].
+///
+/// The ladder comes from the catalog of each broadcast, and the bandwidth is estimated from the congestion window and RTT.
+/// Unlike the send rate, this isn't limited by the bitrate currently served.
+pub struct Abr {
+	// The session ID, used in the decision log.
+	session: usize,
+	algorithm: AbrAlgorithm,
+	epoch: time::Instant,
+	state: Arc<Mutex<AbrState>>,
+	log: Option<Mutex<fs::File>>,
+}
+
+#[derive(Default)]
+struct AbrState {
+	// The smoothed bandwidth estimate in bits per second.
+	bandwidth: Option<f64>,
+
+	// The ladder of each broadcast by ID, which is None while the catalog is loading.
+	ladders: HashMap<String, Option<Arc<Vec<Rendition>>>>,
+
+	subscriptions: HashMap<VarInt, Subscription>,
+}
+
+struct Subscription {
+	algorithm: Box<dyn Algorithm>,
+
+	// When the previous group was decided.
+	last: time::Instant,
+}
+
+impl Abr {
+	/// Create the policy for a session, or None if the algorithm is off.
+	pub fn new(session: usize, config: &AbrConfig) -> Option<Self> {
+		config.algorithm.build()?;
+
+		let log = config.log.as_ref().and_then(|dir| {
+			let path = dir.join(format!("{}.jsonl", session));
+			let file = fs::OpenOptions::new().create(true).append(true).open(&path);
+
+			match file {
+				Ok(file) => Some(Mutex::new(file)),
+				Err(err) => {
+					log::warn!("failed to open ABR log: path={} err={}", path.display(), err);
+					None
+				}
+			}
+		});
+
+		Some(Self {
+			session,
+			algorithm: config.algorithm,
+			epoch: time::Instant::now(),
+			state: Default::default(),
+			log,
+		})
+	}
+
+	// Return the ladder of the broadcast, loading its catalog in the background the first time.
+	//
+	// A catalog that fails to parse disables ABR for the broadcast, but one that times out is loaded again for the next group.
+	fn ladder(&self, state: &mut AbrState, broadcast: &broadcast::Subscriber) -> Option<Arc<Vec<Rendition>>> {
+		if let Some(ladder) = state.ladders.get(&broadcast.id) {
+			return ladder.clone();
+		}
+
+		state.ladders.insert(broadcast.id.clone(), None);
+
+		let shared = self.state.clone();
+		let broadcast = broadcast.clone();
+
+		tokio::spawn(async move {
+			let ladder = match tokio::time::timeout(CATALOG_TIMEOUT, load(&broadcast)).await {
+				Ok(Ok(ladder)) => ladder,
+				Ok(Err(err)) => {
+					log::debug!("abr disabled, no catalog: broadcast={} err={:#}", broadcast.id, err);
+					Vec::new()
+				}
+				Err(_) => {
+					log::debug!("catalog timed out, retrying: broadcast={}", broadcast.id);
+					shared.lock().unwrap().ladders.remove(&broadcast.id);
+					return;
+				}
+			};
+
+			let ladder = Arc::new(ladder);
+			shared
+				.lock()
+				.unwrap()
+				.ladders
+				.insert(broadcast.id.clone(), Some(ladder));
+		});
+
+		None
+	}
+
+	// Update the bandwidth estimate with the current congestion window and RTT.
+	fn bandwidth(state: &mut AbrState, rtt: time::Duration, cwnd: u64) -> u64 {
+		let rtt = rtt.as_secs_f64();
+		if rtt > 0.0 {
+			let sample = cwnd as f64 * 8.0 / rtt;
+			let estimate = state.bandwidth.get_or_insert(sample);
+			*estimate += BANDWIDTH_SMOOTHING * (sample - *estimate);
+		}
+
+		state.bandwidth.unwrap_or_default() as u64
+	}
+
+	fn record(
+		&self,
+		ctx: &AbrContext,
+		from: &Rendition,
+		to: &Rendition,
+		bandwidth: u64,
+		buffer: Option<time::Duration>,
+	) {
+		if from.track != to.track {
+			log::info!(
+				"abr switch: session={} id={} group={:?} from={} to={} bandwidth={} rtt={:?} buffer={:?}",
+				self.session,
+				ctx.id,
+				ctx.group,
+				from.track,
+				to.track,
+				bandwidth,
+				ctx.stats.rtt,
+				buffer
+			);
+		}
+
+		let log = match &self.log {
+			Some(log) => log,
+			None => return,
+		};
+
+		let decision = serde_json::json!({
+			"time_ms": self.epoch.elapsed().as_millis() as u64,
+			"session": self.session,
+			"id": ctx.id.into_inner(),
+			"group": ctx.group.into_inner(),
+			"algorithm": format!("{:?}", self.algorithm).to_lowercase(),
+			"from": from.track,
+			"to": to.track,
+			"bitrate": to.bitrate,
+			"bandwidth": bandwidth,
+			"rtt_ms": ctx.stats.rtt.as_secs_f64() * 1000.0,
+			"cwnd": ctx.stats.cwnd,
+			"loss": ctx.stats.loss(),
+			"buffer_ms": buffer.map(|buffer| buffer.as_millis() as u64),
+		});
+
+		if let Err(err) = writeln!(log.lock().unwrap(), "{}", decision) {
+			log::warn!("failed to write ABR log: session={} err={}", self.session, err);
+		}
+	}
+}
+
+impl AbrPolicy for Abr {
+	fn select(&self, ctx: &AbrContext) -> Option<String> {
+		let mut state = self.state.lock().unwrap();
+
+		let ladder = self.ladder(&mut state, ctx.broadcast)?;
+		if ladder.len() < 2 {
+			return None;
+		}
+
+		// Only switch between video renditions, so other tracks like audio are served as is.
+		let current = ladder.iter().position(|rendition| rendition.track == ctx.current)?;
+		let bandwidth = Self::bandwidth(&mut state, ctx.stats.rtt, ctx.stats.cwnd);
+
+		let now = time::Instant::now();
+		let subscription = state.subscriptions.entry(ctx.id).or_insert_with(|| Subscription {
+			algorithm: self.algorithm.build().expect("abr is only created when enabled"),
+			last: now,
+		});
+
+		let input = Input {
+			ladder: &ladder,
+			current,
+			bandwidth,
+			duration: now - subscription.last,
+		};
+
+		let next = subscription.algorithm.select(&input).min(ladder.len() - 1);
+		let buffer = subscription.algorithm.buffer();
+		subscription.last = now;
+
+		drop(state);
+
+		self.record(ctx, &ladder[current], &ladder[next], bandwidth, buffer);
+
+		(next != current).then(|| ladder[next].track.clone())
+	}
+
+	fn finish(&self, id: VarInt) {
+		self.state.lock().unwrap().subscriptions.remove(&id);
+	}
+}
+
+impl fmt::Debug for Abr {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Abr")
+			.field("session", &self.session)
+			.field("algorithm", &self.algorithm)
+			.finish()
+	}
+}
+
+// Read the catalog track of the broadcast and parse its ladder.
+async fn load(broadcast: &broadcast::Subscriber) -> anyhow::Result<Vec<Rendition>> {
+	let mut track = broadcast.get_track(CATALOG_TRACK)?;
+	let mut segment = track.segment().await?.context("empty catalog")?;
+
+	let mut catalog = Vec::new();
+	while let Some(mut fragment) = segment.fragment().await? {
+		while let Some(chunk) = fragment.chunk().await? {
+			catalog.extend_from_slice(&chunk);
+		}
+	}
+
+	ladder(&catalog)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use moq_transport::cache::segment;
+
+	const CATALOG: &str = r#"{
+		"tracks": [
+			{ "kind": "video", "data_track": "720p", "bit_rate": 3000000 },
+			{ "kind": "audio", "data_track": "audio", "bit_rate": 128000 },
+			{ "kind": "video", "data_track": "360p", "bit_rate": 800000 },
+			{ "kind": "video", "data_track": "1080p" },
+			{ "kind": "video", "data_track": "zero", "bit_rate": 0 },
+			{ "kind": "video", "bit_rate": 100000 }
+		]
+	}"#;
+
+	fn renditions(bitrates: &[u64]) -> Vec<Rendition> {
+		bitrates
+			.iter()
+			.map(|bitrate| Rendition {
+				track: bitrate.to_string(),
+				bitrate: *bitrate,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn parse_ladder() {
+		let parsed = ladder(CATALOG.as_bytes()).unwrap();
+
+		// Only video renditions with a track and bitrate, sorted by bitrate.
+		let tracks: Vec<_> = parsed.iter().map(|rendition| rendition.track.as_str()).collect();
+		assert_eq!(tracks, ["360p", "720p"]);
+		assert_eq!(parsed[0].bitrate, 800000);
+
+		assert!(ladder(br#"{"tracks": []}"#).unwrap().is_empty());
+		assert!(ladder(b"not json").is_err());
+	}
+
+	#[test]
+	fn throughput() {
+		let ladder = renditions(&[500_000, 1_000_000, 2_000_000]);
+		let mut algorithm = Throughput::default();
+
+		let mut select = |bandwidth| {
+			algorithm.select(&Input {
+				ladder: &ladder,
+				current: 0,
+				bandwidth,
+				duration: time::Duration::ZERO,
+			})
+		};
+
+		// Only 80% of the bandwidth is used.
+		assert_eq!(select(2_500_000), 2);
+		assert_eq!(select(2_400_000), 1);
+		assert_eq!(select(1_250_000), 1);
+		assert_eq!(select(1_000_000), 0);
+
+		// The lowest rendition is used even if it doesn't fit.
+		assert_eq!(select(0), 0);
+	}
+
+	#[test]
+	fn bola() {
+		let ladder = renditions(&[500_000, 1_000_000, 2_000_000, 4_000_000]);
+		let mut algorithm = Bola::default();
+		let mut current = 0;
+
+		let select = |algorithm: &mut Bola, current: &mut usize, bandwidth| {
+			*current = algorithm.select(&Input {
+				ladder: &ladder,
+				current: *current,
+				bandwidth,
+				duration: time::Duration::from_secs(1),
+			});
+		};
+
+		// Starts at the lowest bitrate with an empty buffer.
+		select(&mut algorithm, &mut current, 10_000_000);
+		assert_eq!(current, 0);
+
+		// The buffer grows while the bandwidth is plenty, stepping up to the highest bitrate.
+		let mut previous = current;
+		for _ in 0..8 {
+			select(&mut algorithm, &mut current, 10_000_000);
+			assert!(current >= previous);
+			previous = current;
+		}
+		assert_eq!(current, ladder.len() - 1);
+		assert_eq!(
+			algorithm.buffer(),
+			Some(time::Duration::from_secs_f64(algorithm.target))
+		);
+
+		// The buffer drains once even the lowest bitrate takes longer to send than it plays, stepping down to it.
+		for _ in 0..8 {
+			let buffer = algorithm.buffer;
+			select(&mut algorithm, &mut current, 400_000);
+			assert!(algorithm.buffer <= buffer);
+			assert!(current <= previous);
+			previous = current;
+		}
+		assert_eq!(current, 0);
+	}
+
+	#[test]
+	fn bandwidth() {
+		let mut state = AbrState::default();
+
+		// No estimate until there's an RTT.
+		assert_eq!(Abr::bandwidth(&mut state, time::Duration::ZERO, 10_000), 0);
+
+		// The first sample is used as is: 100KB every 100ms is 8Mb/s.
+		let rtt = time::Duration::from_millis(100);
+		assert_eq!(Abr::bandwidth(&mut state, rtt, 100_000), 8_000_000);
+
+		// Later samples are smoothed.
+		assert_eq!(Abr::bandwidth(&mut state, rtt, 200_000), 10_400_000);
+		assert_eq!(Abr::bandwidth(&mut state, time::Duration::ZERO, 0), 10_400_000);
+
+		let mut estimate = 0;
+		for _ in 0..32 {
+			estimate = Abr::bandwidth(&mut state, rtt, 50_000);
+		}
+		assert!((4_000_000..4_010_000).contains(&estimate));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn catalog_timeout() {
+		let config = AbrConfig {
+			algorithm: AbrAlgorithm::Throughput,
+			log: None,
+		};
+		let abr = Abr::new(0, &config).unwrap();
+		let (mut publisher, subscriber) = broadcast::new("demo");
+
+		let ladder = |abr: &Abr| abr.ladder(&mut abr.state.lock().unwrap(), &subscriber);
+		assert!(ladder(&abr).is_none());
+
+		// The catalog is requested but never arrives, so it's loaded again after the timeout.
+		let mut catalog = publisher.next_track().await.unwrap();
+		tokio::time::sleep(CATALOG_TIMEOUT * 2).await;
+		assert!(!abr.state.lock().unwrap().ladders.contains_key("demo"));
+		assert!(ladder(&abr).is_none());
+
+		let mut segment = catalog
+			.create_segment(segment::Info {
+				sequence: VarInt::ZERO,
+				priority: 0,
+				expires: None,
+			})
+			.unwrap();
+		segment
+			.fragment(VarInt::ZERO, CATALOG.len())
+			.unwrap()
+			.chunk(CATALOG.as_bytes().to_vec().into())
+			.unwrap();
+		drop(segment);
+
+		tokio::time::sleep(time::Duration::from_secs(1)).await;
+		assert_eq!(ladder(&abr).unwrap().len(), 2);
+	}
+}
@@ -0,0 +1,177 @@
+use std::net;
+
+use axum::{
+	extract::{Path, State},
+	http::{header, StatusCode},
+	response::IntoResponse,
+	routing::{delete, get},
+	Json, Router,
+};
+use serde::Serialize;
+
+use crate::{role_label, Metrics, Origin, SessionInfo, Sessions};
+
+// Run a plain HTTP server for operators, separate from the media endpoint.
+pub struct Admin {
+	app: Router,
+	addr: net::SocketAddr,
+}
+
+#[derive(Clone)]
+struct AdminState {
+	origin: Origin,
+	sessions: Sessions,
+	metrics: Metrics,
+}
+
+impl Admin {
+	pub fn new(addr: net::SocketAddr, origin: Origin, sessions: Sessions, metrics: Metrics) -> Self {
+		let app = Router::new()
+			.route("/metrics", get(serve_metrics))
+			.route("/broadcasts", get(list_broadcasts))
+			.route("/broadcasts/*id", delete(close_broadcast))
+			.route("/sessions", get(list_sessions))
+			.route("/sessions/:id", delete(kick_session))
+			.with_state(AdminState {
+				origin,
+				sessions,
+				metrics,
+			});
+
+		Self { app, addr }
+	}
+
+	pub async fn serve(self) -> anyhow::Result<()> {
+		log::info!("serving admin on {}", self.addr);
+
+		axum::Server::bind(&self.addr)
+			.serve(self.app.into_make_service())
+			.await?;
+
+		Ok(())
+	}
+}
+
+async fn serve_metrics(State(state): State<AdminState>) -> impl IntoResponse {
+	let body = state.metrics.render(&state.origin, &state.sessions.list());
+	([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+#[derive(Serialize)]
+struct BroadcastResponse {
+	id: String,
+	closed: bool,
+	tracks: Vec<TrackResponse>,
+}
+
+#[derive(Serialize)]
+struct TrackResponse {
+	name: String,
+	segments: usize,
+	expired: usize,
+
+	// The range of cached segment sequence numbers.
+	first: Option<u64>,
+	last: Option<u64>,
+}
+
+async fn list_broadcasts(State(state): State<AdminState>) -> Json<Vec<BroadcastResponse>> {
+	let mut broadcasts: Vec<_> = state
+		.origin
+		.broadcasts()
+		.iter()
+		.map(|broadcast| {
+			let mut tracks: Vec<_> = broadcast
+				.tracks()
+				.iter()
+				.map(|track| {
+					let cached = track.cached();
+					TrackResponse {
+						name: track.name.clone(),
+						segments: cached.segments,
+						expired: cached.expired,
+						first: cached.first.map(|s| s.into_inner()),
+						last: cached.last.map(|s| s.into_inner()),
+					}
+				})
+				.collect();
+
+			tracks.sort_by(|a, b| a.name.cmp(&b.name));
+
+			BroadcastResponse {
+				id: broadcast.id.clone(),
+				closed: broadcast.is_closed().is_some(),
+				tracks,
+			}
+		})
+		.collect();
+
+	broadcasts.sort_by(|a, b| a.id.cmp(&b.id));
+	Json(broadcasts)
+}
+
+async fn close_broadcast(State(state): State<AdminState>, Path(id): Path<String>) -> StatusCode {
+	let id = id.trim_matches('/');
+
+	match state.origin.close(id) {
+		Ok(()) => {
+			log::info!("closed broadcast from admin: id={}", id);
+			StatusCode::NO_CONTENT
+		}
+		Err(_) => StatusCode::NOT_FOUND,
+	}
+}
+
+#[derive(Serialize)]
+struct SessionResponse {
+	id: usize,
+	role: &'static str,
+	path: String,
+	remote: net::SocketAddr,
+
+	rtt_ms: f64,
+	cwnd: u64,
+	bytes_sent: u64,
+	bytes_received: u64,
+	loss: f64,
+	streams_opened: u64,
+	streams_accepted: u64,
+	served: usize,
+	subscribed: usize,
+}
+
+impl From<SessionInfo> for SessionResponse {
+	fn from(session: SessionInfo) -> Self {
+		let stats = session.stats;
+
+		Self {
+			id: session.id,
+			role: role_label(session.role),
+			path: session.path,
+			remote: session.remote,
+			rtt_ms: stats.rtt.as_secs_f64() * 1000.0,
+			cwnd: stats.cwnd,
+			bytes_sent: stats.bytes_sent,
+			bytes_received: stats.bytes_received,
+			loss: stats.loss(),
+			streams_opened: stats.streams_opened,
+			streams_accepted: stats.streams_accepted,
+			served: stats.served.len(),
+			subscribed: stats.subscribed.len(),
+		}
+	}
+}
+
+async fn list_sessions(State(state): State<AdminState>) -> Json<Vec<SessionResponse>> {
+	Json(state.sessions.list().into_iter().map(Into::into).collect())
+}
+
+async fn kick_session(State(state): State<AdminState>, Path(id): Path<usize>) -> StatusCode {
+	match state.sessions.kick(id) {
+		true => {
+			log::info!("kicked session from admin: id={}", id);
+			StatusCode::NO_CONTENT
+		}
+		false => StatusCode::NOT_FOUND,
+	}
+}
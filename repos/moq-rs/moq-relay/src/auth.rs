@@ -0,0 +1,389 @@
+use std::{
+	fs, path,
+	sync::{Arc, RwLock},
+	time,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use moq_transport::setup::Role;
+use ring::hmac;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// The query parameter containing the token.
+pub const TOKEN_PARAM: &str = "jwt";
+
+#[derive(Error, Debug, Clone)]
+pub enum AuthError {
+	#[error("missing token")]
+	Missing,
+
+	#[error("invalid token: {0}")]
+	Invalid(&'static str),
+
+	#[error("expired token")]
+	Expired,
+
+	#[error("forbidden: path={0} role={1:?}")]
+	Forbidden(String, Role),
+}
+
+impl moq_transport::MoqError for AuthError {
+	fn code(&self) -> u32 {
+		match self {
+			Self::Missing | Self::Invalid(_) | Self::Expired => 401,
+			Self::Forbidden(..) => 403,
+		}
+	}
+
+	fn reason(&self) -> String {
+		self.to_string()
+	}
+}
+
+/// Decides what a session is allowed to do, based on the token in the connection URL.
+pub trait Authorizer: Send + Sync {
+	/// Verify the token, returning what it grants.
+	fn verify(&self, token: Option<&str>) -> Result<Grant, AuthError>;
+}
+
+/// Holds the current [Authorizer], which can be replaced when the keys are reloaded.
+#[derive(Clone)]
+pub struct Auth {
+	current: Arc<RwLock<Arc<dyn Authorizer>>>,
+}
+
+impl Auth {
+	pub fn new(auth: Arc<dyn Authorizer>) -> Self {
+		Self {
+			current: Arc::new(RwLock::new(auth)),
+		}
+	}
+
+	/// The authorizer for new sessions.
+	pub fn current(&self) -> Arc<dyn Authorizer> {
+		self.current.read().unwrap().clone()
+	}
+
+	/// Replace the authorizer, without affecting sessions that were already verified.
+	pub fn replace(&self, auth: Arc<dyn Authorizer>) {
+		*self.current.write().unwrap() = auth;
+	}
+}
+
+/// The paths and roles granted to a session.
+#[derive(Clone, Debug)]
+pub struct Grant {
+	// The allowed path prefixes, or None for any path.
+	paths: Option<Vec<String>>,
+
+	publish: bool,
+	subscribe: bool,
+}
+
+impl Grant {
+	/// Allow everything.
+	pub fn all() -> Self {
+		Self {
+			paths: None,
+			publish: true,
+			subscribe: true,
+		}
+	}
+
+	/// Check the session may act as `role` for the broadcast at `path`.
+	pub fn check(&self, path: &str, role: Role) -> Result<(), AuthError> {
+		let path = path.trim_matches('/');
+
+		let allowed = match &self.paths {
+			Some(paths) => paths.iter().any(|prefix| matches(prefix, path)),
+			None => true,
+		};
+
+		let allowed = allowed && (!role.is_publisher() || self.publish) && (!role.is_subscriber() || self.subscribe);
+
+		match allowed {
+			true => Ok(()),
+			false => Err(AuthError::Forbidden(path.to_string(), role)),
+		}
+	}
+}
+
+/// Allows any session, used when no keys are configured.
+pub struct AllowAll;
+
+impl Authorizer for AllowAll {
+	fn verify(&self, _token: Option<&str>) -> Result<Grant, AuthError> {
+		Ok(Grant::all())
+	}
+}
+
+/// Verifies a JWT signed with HMAC-SHA256 by any of the configured keys.
+///
+/// The claims list the allowed path prefixes and roles, for example:
+/// `{"paths": ["demo/"], "roles": ["publish", "subscribe"], "exp": 1700000000}`
+pub struct TokenAuth {
+	keys: Vec<hmac::Key>,
+}
+
+#[derive(Deserialize)]
+struct Header {
+	alg: String,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+	#[serde(default)]
+	paths: Vec<String>,
+
+	#[serde(default)]
+	roles: Vec<String>,
+
+	// The expiration as a unix timestamp in seconds.
+	exp: Option<u64>,
+}
+
+impl TokenAuth {
+	pub fn new<I, K>(secrets: I) -> Self
+	where
+		I: IntoIterator<Item = K>,
+		K: AsRef<[u8]>,
+	{
+		let keys = secrets
+			.into_iter()
+			.map(|secret| hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref()))
+			.collect();
+
+		Self { keys }
+	}
+
+	/// Load each key from a file, ignoring any surrounding whitespace.
+	pub fn load(paths: &[path::PathBuf]) -> anyhow::Result<Self> {
+		let mut secrets = Vec::new();
+		for path in paths {
+			let secret = fs::read_to_string(path)?;
+			secrets.push(secret.trim().to_string());
+		}
+
+		Ok(Self::new(secrets))
+	}
+}
+
+impl Authorizer for TokenAuth {
+	fn verify(&self, token: Option<&str>) -> Result<Grant, AuthError> {
+		let token = token.ok_or(AuthError::Missing)?;
+
+		let mut parts = token.split('.');
+		let (header, claims, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+			(Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+			_ => return Err(AuthError::Invalid("malformed")),
+		};
+
+		let header: Header = decode(header)?;
+		if header.alg != "HS256" {
+			return Err(AuthError::Invalid("unsupported algorithm"));
+		}
+
+		// The signature covers the encoded header and claims.
+		let signed = &token[..token.len() - signature.len() - 1];
+		let signature = URL_SAFE_NO_PAD
+			.decode(signature)
+			.map_err(|_| AuthError::Invalid("signature encoding"))?;
+
+		if !self
+			.keys
+			.iter()
+			.any(|key| hmac::verify(key, signed.as_bytes(), &signature).is_ok())
+		{
+			return Err(AuthError::Invalid("signature"));
+		}
+
+		let claims: Claims = decode(claims)?;
+
+		if let Some(exp) = claims.exp {
+			let now = time::SystemTime::now()
+				.duration_since(time::UNIX_EPOCH)
+				.unwrap_or_default()
+				.as_secs();
+
+			if now >= exp {
+				return Err(AuthError::Expired);
+			}
+		}
+
+		Ok(Grant {
+			paths: Some(claims.paths),
+			publish: claims.roles.iter().any(|role| role == "publish"),
+			subscribe: claims.roles.iter().any(|role| role == "subscribe"),
+		})
+	}
+}
+
+// Check if the path is the prefix or is nested under it, so "demo" doesn't match "demo2".
+fn matches(prefix: &str, path: &str) -> bool {
+	let prefix = prefix.trim_matches('/');
+
+	match path.strip_prefix(prefix) {
+		Some(rest) => prefix.is_empty() || rest.is_empty() || rest.starts_with('/'),
+		None => false,
+	}
+}
+
+fn decode<T: serde::de::DeserializeOwned>(part: &str) -> Result<T, AuthError> {
+	let json = URL_SAFE_NO_PAD
+		.decode(part)
+		.map_err(|_| AuthError::Invalid("encoding"))?;
+
+	serde_json::from_slice(&json).map_err(|_| AuthError::Invalid("json"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sign(secret: &str, header: &str, claims: &str) -> String {
+		let signed = format!("{}.{}", URL_SAFE_NO_PAD.encode(header), URL_SAFE_NO_PAD.encode(claims));
+		let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+		let signature = hmac::sign(&key, signed.as_bytes());
+		format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(signature))
+	}
+
+	fn token(secret: &str, claims: &str) -> String {
+		sign(secret, r#"{"alg":"HS256","typ":"JWT"}"#, claims)
+	}
+
+	const BOTH: &str = r#"{"paths":["demo"],"roles":["publish","subscribe"]}"#;
+
+	#[test]
+	fn signature() {
+		let auth = TokenAuth::new(["secret"]);
+
+		assert!(auth.verify(Some(&token("secret", BOTH))).is_ok());
+		assert!(matches!(auth.verify(None), Err(AuthError::Missing)));
+		assert!(matches!(
+			auth.verify(Some("not-a-token")),
+			Err(AuthError::Invalid("malformed"))
+		));
+
+		// Signed with another key.
+		assert!(matches!(
+			auth.verify(Some(&token("other", BOTH))),
+			Err(AuthError::Invalid("signature"))
+		));
+
+		// The claims were changed after signing.
+		let mut parts: Vec<String> = token("secret", BOTH).split('.').map(String::from).collect();
+		parts[1] = URL_SAFE_NO_PAD.encode(r#"{"paths":[""],"roles":["publish","subscribe"]}"#);
+		assert!(matches!(
+			auth.verify(Some(&parts.join("."))),
+			Err(AuthError::Invalid("signature"))
+		));
+	}
+
+	#[test]
+	fn algorithm() {
+		let auth = TokenAuth::new(["secret"]);
+
+		for alg in ["none", "HS512", "RS256"] {
+			let header = format!(r#"{{"alg":"{}"}}"#, alg);
+			assert!(matches!(
+				auth.verify(Some(&sign("secret", &header, BOTH))),
+				Err(AuthError::Invalid("unsupported algorithm"))
+			));
+		}
+
+		// An unsigned token is rejected before the signature is checked.
+		let unsigned = format!(
+			"{}.{}.",
+			URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+			URL_SAFE_NO_PAD.encode(BOTH)
+		);
+		assert!(matches!(
+			auth.verify(Some(&unsigned)),
+			Err(AuthError::Invalid("unsupported algorithm"))
+		));
+	}
+
+	#[test]
+	fn expiration() {
+		let auth = TokenAuth::new(["secret"]);
+
+		let expired = r#"{"paths":["demo"],"roles":["subscribe"],"exp":1}"#;
+		assert!(matches!(
+			auth.verify(Some(&token("secret", expired))),
+			Err(AuthError::Expired)
+		));
+
+		let now = time::SystemTime::now()
+			.duration_since(time::UNIX_EPOCH)
+			.unwrap()
+			.as_secs();
+		let valid = format!(r#"{{"paths":["demo"],"roles":["subscribe"],"exp":{}}}"#, now + 60);
+		assert!(auth.verify(Some(&token("secret", &valid))).is_ok());
+	}
+
+	#[test]
+	fn prefix() {
+		let auth = TokenAuth::new(["secret"]);
+		let grant = auth.verify(Some(&token("secret", BOTH))).unwrap();
+
+		assert!(grant.check("demo", Role::Both).is_ok());
+		assert!(grant.check("/demo/", Role::Both).is_ok());
+		assert!(grant.check("demo/live", Role::Both).is_ok());
+		assert!(grant.check("demo2", Role::Both).is_err());
+		assert!(grant.check("other/demo", Role::Both).is_err());
+		assert!(grant.check("", Role::Both).is_err());
+
+		// An empty prefix allows any path.
+		let any = r#"{"paths":[""],"roles":["subscribe"]}"#;
+		let grant = auth.verify(Some(&token("secret", any))).unwrap();
+		assert!(grant.check("demo2/live", Role::Subscriber).is_ok());
+
+		// No paths allows nothing.
+		let none = r#"{"roles":["subscribe"]}"#;
+		let grant = auth.verify(Some(&token("secret", none))).unwrap();
+		assert!(grant.check("demo", Role::Subscriber).is_err());
+	}
+
+	#[test]
+	fn roles() {
+		let auth = TokenAuth::new(["secret"]);
+
+		let publish = r#"{"paths":["demo"],"roles":["publish"]}"#;
+		let grant = auth.verify(Some(&token("secret", publish))).unwrap();
+		assert!(grant.check("demo", Role::Publisher).is_ok());
+		assert!(grant.check("demo", Role::Subscriber).is_err());
+		assert!(grant.check("demo", Role::Both).is_err());
+
+		let subscribe = r#"{"paths":["demo"],"roles":["subscribe"]}"#;
+		let grant = auth.verify(Some(&token("secret", subscribe))).unwrap();
+		assert!(grant.check("demo", Role::Publisher).is_err());
+		assert!(grant.check("demo", Role::Subscriber).is_ok());
+		assert!(grant.check("demo", Role::Both).is_err());
+
+		let err = grant.check("demo", Role::Publisher).unwrap_err();
+		assert_eq!(moq_transport::MoqError::code(&err), 403);
+	}
+
+	#[test]
+	fn rotation() {
+		let old = token("old", BOTH);
+		let new = token("new", BOTH);
+
+		// Both keys are accepted while rotating.
+		let auth = Auth::new(Arc::new(TokenAuth::new(["old", "new"])));
+		assert!(auth.current().verify(Some(&old)).is_ok());
+		assert!(auth.current().verify(Some(&new)).is_ok());
+
+		// Sessions verified before the reload keep their authorizer.
+		let before = auth.current();
+		auth.replace(Arc::new(TokenAuth::new(["new"])));
+
+		assert!(matches!(
+			auth.current().verify(Some(&old)),
+			Err(AuthError::Invalid("signature"))
+		));
+		assert!(auth.current().verify(Some(&new)).is_ok());
+		assert!(before.verify(Some(&old)).is_ok());
+	}
+}
@@ -1,11 +1,25 @@
-use std::{net, path};
+use std::{collections::BTreeMap, fs, net, path, sync::Arc, time};
 use url::Url;
 
-use clap::Parser;
+use anyhow::Context;
+use clap::{parser::ValueSource, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use moq_transport::session::{
+	EarliestDeadline, NewestFirst, PriorityPolicy, SegmentPriority, WeightedRoundRobin, WriteStrategy,
+};
+
+use serde::Deserialize;
+
+use crate::{AbrAlgorithm, AbrConfig, AllowAll, Authorizer, TokenAuth};
 
 /// Search for a pattern in a file and display the lines that contain it.
 #[derive(Parser, Clone)]
 pub struct Config {
+	/// Load any options not provided on the command line from this TOML file.
+	///
+	/// The TLS certificates and auth keys are reloaded from the file on SIGHUP.
+	#[arg(long)]
+	pub config: Option<path::PathBuf>,
+
 	/// Listen on this address
 	#[arg(long, default_value = "[::]:4443")]
 	pub listen: net::SocketAddr,
@@ -41,15 +55,658 @@ pub struct Config {
 	#[arg(long)]
 	pub api: Option<Url>,
 
+	/// Require a JWT signed with HMAC-SHA256 by the key in this file, passed as the `jwt` query parameter.
+	///
+	/// This value can be provided multiple times to accept multiple keys, such as during rotation.
+	/// If this is empty, any client can publish or subscribe to any path.
+	#[arg(long)]
+	pub auth_key: Vec<path::PathBuf>,
+
+	/// Serve the admin HTTP endpoints, such as /metrics, on this address.
+	///
+	/// The endpoints have no authentication, so the default only listens on localhost.
+	#[arg(long, default_value = "127.0.0.1:9090")]
+	pub admin: net::SocketAddr,
+
 	/// Our internal address which we advertise to other origins.
 	/// We use QUIC, so the certificate must be valid for this address.
-	/// This needs to be prefixed with https:// to use WebTransport.
+	/// This needs to be prefixed with https:// to use WebTransport, or moqt:// to use QUIC directly.
 	/// This is only used when --api is set and only for publishing broadcasts.
 	#[arg(long)]
 	pub api_node: Option<Url>,
 
+	/// Fetch any broadcast that isn't published to this relay from another relay, forming a tree.
+	///
+	/// This value can be provided multiple times for multiple relays, which are tried in order after --api.
+	/// The broadcast name is appended to the path, and any query such as a token is kept.
+	#[arg(long)]
+	pub upstream: Vec<Url>,
+
+	/// A unique ID for this relay, used to detect loops between relays, otherwise a random ID.
+	#[arg(long)]
+	pub relay_id: Option<String>,
+
+	/// On SIGTERM, wait up to this many milliseconds for sessions to leave after sending GOAWAY, before closing them.
+	#[arg(long, default_value = "25000")]
+	pub drain_timeout: u64,
+
 	/// Enable development mode.
 	/// Currently, this only listens on HTTPS and serves /fingerprint, for self-signed certificates
 	#[arg(long, action)]
 	pub dev: bool,
+
+	/// How to prioritize the segments sent to subscribers when congested.
+	#[arg(long, value_enum, default_value_t = Priority::Segment)]
+	pub priority: Priority,
+
+	/// The weight of a track when using `--priority weighted`, as NAME=WEIGHT.
+	///
+	/// This value can be provided multiple times for multiple tracks.
+	#[arg(long, value_parser = track_weight)]
+	pub track_weight: Vec<(String, u32)>,
+
+	/// Coalesce small chunks into a single write of at least this many bytes, or write each chunk immediately if zero.
+	#[arg(long, default_value = "0")]
+	pub coalesce_size: usize,
+
+	/// The longest a chunk is delayed in milliseconds when using `--coalesce-size`.
+	#[arg(long, default_value = "10")]
+	pub coalesce_delay: u64,
+
+	/// Pace each stream at this bitrate in bits per second, or disable pacing if zero.
+	#[arg(long, default_value = "0")]
+	pub pacing: u64,
+
+	/// Reset any stream from a publisher with an OBJECT larger than this many bytes, or allow any size if zero.
+	///
+	/// The default of 16 MiB is far larger than any media frame, but stops a publisher from filling the cache with one OBJECT.
+	#[arg(long, default_value = "16777216")]
+	pub max_fragment_size: usize,
+
+	/// Switch each subscriber between the video renditions in the catalog, based on the estimated bandwidth.
+	///
+	/// A subscriber can choose another algorithm, or `off`, with the `abr` query parameter.
+	#[arg(long, value_enum, default_value_t = AbrAlgorithm::Off)]
+	pub abr: AbrAlgorithm,
+
+	/// Append each ABR decision to a JSON lines file per session in this directory.
+	#[arg(long)]
+	pub abr_log: Option<path::PathBuf>,
+
+	#[command(flatten)]
+	pub quic: QuicConfig,
+}
+
+/// The QUIC transport settings, used for both incoming connections and connections to other origins.
+#[derive(Args, Clone, Debug)]
+#[command(next_help_heading = "QUIC")]
+pub struct QuicConfig {
+	/// The congestion controller.
+	#[arg(long, value_enum, default_value_t = Congestion::Bbr)]
+	pub congestion: Congestion,
+
+	/// The initial congestion window in bytes, otherwise the congestion controller's default.
+	#[arg(long)]
+	pub initial_window: Option<u64>,
+
+	/// The flow control window of each stream in bytes.
+	#[arg(long, default_value = "1250000")]
+	pub stream_window: u64,
+
+	/// The flow control window of the whole connection in bytes, otherwise unlimited.
+	#[arg(long)]
+	pub connection_window: Option<u64>,
+
+	/// The number of unidirectional streams the peer may have open at once.
+	#[arg(long, default_value = "100")]
+	pub max_uni_streams: u32,
+
+	/// Probe for a larger MTU, otherwise packets are limited to the initial MTU.
+	#[arg(long)]
+	pub mtu_discovery: bool,
+
+	/// The buffer for received datagrams in bytes, or reject datagrams if zero.
+	#[arg(long, default_value = "1250000")]
+	pub datagram_receive_buffer: usize,
+
+	/// The buffer for datagrams waiting to be sent in bytes.
+	#[arg(long, default_value = "1048576")]
+	pub datagram_send_buffer: usize,
+
+	/// Close the connection after this many milliseconds without any packets.
+	#[arg(long, default_value = "10000")]
+	pub idle_timeout: u64,
+
+	/// Send a keep-alive after this many milliseconds without any packets, or disable if zero.
+	#[arg(long, default_value = "4000")]
+	pub keep_alive: u64,
+}
+
+impl QuicConfig {
+	/// Create the transport config for QUIC connections.
+	pub fn transport(&self) -> anyhow::Result<quinn::TransportConfig> {
+		let mut transport = quinn::TransportConfig::default();
+
+		let idle_timeout = time::Duration::from_millis(self.idle_timeout);
+		transport.max_idle_timeout(Some(idle_timeout.try_into().context("invalid idle timeout")?));
+
+		let keep_alive = (self.keep_alive > 0).then(|| time::Duration::from_millis(self.keep_alive));
+		transport.keep_alive_interval(keep_alive);
+
+		match self.congestion {
+			Congestion::Bbr => {
+				let mut config = quinn::congestion::BbrConfig::default();
+				if let Some(window) = self.initial_window {
+					config.initial_window(window);
+				}
+				transport.congestion_controller_factory(Arc::new(config));
+			}
+			Congestion::Cubic => {
+				let mut config = quinn::congestion::CubicConfig::default();
+				if let Some(window) = self.initial_window {
+					config.initial_window(window);
+				}
+				transport.congestion_controller_factory(Arc::new(config));
+			}
+			Congestion::NewReno => {
+				let mut config = quinn::congestion::NewRenoConfig::default();
+				if let Some(window) = self.initial_window {
+					config.initial_window(window);
+				}
+				transport.congestion_controller_factory(Arc::new(config));
+			}
+		};
+
+		let stream_window = quinn::VarInt::from_u64(self.stream_window).context("invalid stream window")?;
+		transport.stream_receive_window(stream_window);
+
+		if let Some(window) = self.connection_window {
+			let window = quinn::VarInt::from_u64(window).context("invalid connection window")?;
+			transport.receive_window(window);
+		}
+
+		transport.max_concurrent_uni_streams(self.max_uni_streams.into());
+		transport.mtu_discovery_config(self.mtu_discovery.then(Default::default));
+
+		let datagram_receive_buffer = (self.datagram_receive_buffer > 0).then_some(self.datagram_receive_buffer);
+		transport.datagram_receive_buffer_size(datagram_receive_buffer);
+		transport.datagram_send_buffer_size(self.datagram_send_buffer);
+
+		Ok(transport)
+	}
+}
+
+/// The QUIC congestion controller.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Congestion {
+	/// Estimate the bottleneck bandwidth and RTT, which tolerates loss. (experimental in Quinn)
+	Bbr,
+
+	/// Grow the window with a cubic function, backing off on loss.
+	Cubic,
+
+	/// Grow the window linearly, backing off on loss.
+	NewReno,
+}
+
+impl Config {
+	/// Parse the command line, using the `--config` file for any options that weren't provided.
+	pub fn load() -> anyhow::Result<Self> {
+		let matches = Self::command().get_matches();
+		Self::from_matches(&matches)
+	}
+
+	/// Parse the command line and `--config` file again, such as on SIGHUP, returning any error instead of exiting.
+	pub fn reload() -> anyhow::Result<Self> {
+		Self::try_load_from(std::env::args_os())
+	}
+
+	fn try_load_from<I, T>(args: I) -> anyhow::Result<Self>
+	where
+		I: IntoIterator<Item = T>,
+		T: Into<std::ffi::OsString> + Clone,
+	{
+		let matches = Self::command().try_get_matches_from(args)?;
+		Self::from_matches(&matches)
+	}
+
+	fn from_matches(matches: &ArgMatches) -> anyhow::Result<Self> {
+		let mut config = Self::from_arg_matches(matches)?;
+
+		if let Some(path) = config.config.clone() {
+			let file = ConfigFile::load(&path).with_context(|| format!("failed to load config: {}", path.display()))?;
+
+			// Relative paths in the file are relative to the file itself.
+			let dir = path.parent().unwrap_or(path::Path::new(""));
+			config.merge(file, dir, matches);
+		}
+
+		Ok(config)
+	}
+
+	fn merge(&mut self, file: ConfigFile, dir: &path::Path, matches: &ArgMatches) {
+		let resolve = |paths: Vec<path::PathBuf>| paths.into_iter().map(|path| dir.join(path)).collect();
+
+		merge(matches, "listen", &mut self.listen, file.listen);
+		merge(matches, "admin", &mut self.admin, file.admin);
+		merge(matches, "api", &mut self.api, file.api.map(Some));
+		merge(matches, "api_node", &mut self.api_node, file.api_node.map(Some));
+		merge(matches, "upstream", &mut self.upstream, file.upstream);
+		merge(matches, "relay_id", &mut self.relay_id, file.relay_id.map(Some));
+		merge(matches, "drain_timeout", &mut self.drain_timeout, file.drain_timeout);
+		merge(matches, "dev", &mut self.dev, file.dev);
+
+		// The certificates and keys are paired, so the command line replaces both.
+		if !provided(matches, "tls_cert") && !provided(matches, "tls_key") {
+			if let Some(certs) = file.tls.cert {
+				self.tls_cert = certs.iter().map(|cert| dir.join(&cert.chain)).collect();
+				self.tls_key = certs.iter().map(|cert| dir.join(&cert.key)).collect();
+			}
+		}
+
+		merge(matches, "tls_root", &mut self.tls_root, file.tls.root.map(resolve));
+		merge(
+			matches,
+			"tls_disable_verify",
+			&mut self.tls_disable_verify,
+			file.tls.disable_verify,
+		);
+		merge(matches, "auth_key", &mut self.auth_key, file.auth.keys.map(resolve));
+		merge(
+			matches,
+			"max_fragment_size",
+			&mut self.max_fragment_size,
+			file.cache.max_fragment_size,
+		);
+
+		let transport = file.transport;
+		merge(matches, "priority", &mut self.priority, transport.priority);
+		merge(
+			matches,
+			"track_weight",
+			&mut self.track_weight,
+			transport.weights.map(|weights| weights.into_iter().collect()),
+		);
+		merge(
+			matches,
+			"coalesce_size",
+			&mut self.coalesce_size,
+			transport.coalesce_size,
+		);
+		merge(
+			matches,
+			"coalesce_delay",
+			&mut self.coalesce_delay,
+			transport.coalesce_delay,
+		);
+		merge(matches, "pacing", &mut self.pacing, transport.pacing);
+
+		merge(matches, "abr", &mut self.abr, file.abr.algorithm);
+		merge(
+			matches,
+			"abr_log",
+			&mut self.abr_log,
+			file.abr.log.map(|log| Some(dir.join(log))),
+		);
+
+		let quic = file.quic;
+		merge(matches, "congestion", &mut self.quic.congestion, quic.congestion);
+		merge(
+			matches,
+			"initial_window",
+			&mut self.quic.initial_window,
+			quic.initial_window.map(Some),
+		);
+		merge(
+			matches,
+			"stream_window",
+			&mut self.quic.stream_window,
+			quic.stream_window,
+		);
+		merge(
+			matches,
+			"connection_window",
+			&mut self.quic.connection_window,
+			quic.connection_window.map(Some),
+		);
+		merge(
+			matches,
+			"max_uni_streams",
+			&mut self.quic.max_uni_streams,
+			quic.max_uni_streams,
+		);
+		merge(
+			matches,
+			"mtu_discovery",
+			&mut self.quic.mtu_discovery,
+			quic.mtu_discovery,
+		);
+		merge(
+			matches,
+			"datagram_receive_buffer",
+			&mut self.quic.datagram_receive_buffer,
+			quic.datagram_receive_buffer,
+		);
+		merge(
+			matches,
+			"datagram_send_buffer",
+			&mut self.quic.datagram_send_buffer,
+			quic.datagram_send_buffer,
+		);
+		merge(matches, "idle_timeout", &mut self.quic.idle_timeout, quic.idle_timeout);
+		merge(matches, "keep_alive", &mut self.quic.keep_alive, quic.keep_alive);
+	}
+
+	/// Create the priority policy for a new session.
+	pub fn priority_policy(&self) -> Arc<dyn PriorityPolicy> {
+		match self.priority {
+			Priority::Segment => Arc::new(SegmentPriority),
+			Priority::Newest => Arc::new(NewestFirst),
+			Priority::Deadline => Arc::new(EarliestDeadline::default()),
+			Priority::Weighted => Arc::new(
+				self.track_weight
+					.iter()
+					.fold(WeightedRoundRobin::new(), |policy, (track, weight)| {
+						policy.with_weight(track, *weight)
+					}),
+			),
+		}
+	}
+
+	/// Create the write strategy for a new session.
+	pub fn write_strategy(&self) -> WriteStrategy {
+		WriteStrategy::new()
+			.with_coalesce(self.coalesce_size, time::Duration::from_millis(self.coalesce_delay))
+			.with_pacing(self.pacing)
+	}
+
+	/// The ABR settings for new sessions.
+	pub fn abr(&self) -> AbrConfig {
+		AbrConfig {
+			algorithm: self.abr,
+			log: self.abr_log.clone(),
+		}
+	}
+
+	/// Create the authorizer for new sessions, which allows everything unless keys are configured.
+	pub fn authorizer(&self) -> anyhow::Result<Arc<dyn Authorizer>> {
+		if self.auth_key.is_empty() {
+			log::warn!("authentication disabled; any client can publish or subscribe");
+			return Ok(Arc::new(AllowAll));
+		}
+
+		let auth = TokenAuth::load(&self.auth_key).context("failed to load auth keys")?;
+		Ok(Arc::new(auth))
+	}
+
+	/// The ID of this relay, generating a random one if not configured.
+	pub fn relay_id(&self) -> anyhow::Result<String> {
+		if let Some(id) = &self.relay_id {
+			anyhow::ensure!(!id.is_empty() && !id.contains(','), "invalid relay ID: {}", id);
+			return Ok(id.clone());
+		}
+
+		let mut id = [0u8; 8];
+		ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut id)
+			.map_err(|_| anyhow::anyhow!("failed to generate relay ID"))?;
+
+		Ok(hex::encode(id))
+	}
+
+	/// The maximum size of each OBJECT received from a publisher, if any.
+	pub fn max_fragment_size(&self) -> Option<usize> {
+		(self.max_fragment_size > 0).then_some(self.max_fragment_size)
+	}
+}
+
+/// The scheduling policy used when serving subscribers.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+	/// Use the priority chosen by the publisher, or the subscriber if provided.
+	Segment,
+
+	/// Always send the newest group first.
+	Newest,
+
+	/// Send the segment that expires first.
+	Deadline,
+
+	/// Share the bandwidth between tracks using the configured weights.
+	Weighted,
+}
+
+fn track_weight(s: &str) -> Result<(String, u32), String> {
+	let (name, weight) = s.rsplit_once('=').ok_or("expected NAME=WEIGHT")?;
+	let weight = weight.parse().map_err(|e| format!("invalid weight: {}", e))?;
+
+	Ok((name.to_string(), weight))
+}
+
+// Returns true if the option was passed on the command line, rather than using the default.
+fn provided(matches: &ArgMatches, id: &str) -> bool {
+	matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+// Use the value from the config file, unless the option was passed on the command line.
+fn merge<T>(matches: &ArgMatches, id: &str, value: &mut T, file: Option<T>) {
+	if let Some(file) = file {
+		if !provided(matches, id) {
+			*value = file;
+		}
+	}
+}
+
+// The options in the config file, named after the flags but grouped into sections.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+	listen: Option<net::SocketAddr>,
+	admin: Option<net::SocketAddr>,
+	api: Option<Url>,
+	api_node: Option<Url>,
+	upstream: Option<Vec<Url>>,
+	relay_id: Option<String>,
+	drain_timeout: Option<u64>,
+	dev: Option<bool>,
+
+	#[serde(default)]
+	tls: TlsFile,
+
+	#[serde(default)]
+	auth: AuthFile,
+
+	#[serde(default)]
+	cache: CacheFile,
+
+	#[serde(default)]
+	transport: TransportFile,
+
+	#[serde(default)]
+	abr: AbrFile,
+
+	#[serde(default)]
+	quic: QuicFile,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct TlsFile {
+	cert: Option<Vec<CertFile>>,
+	root: Option<Vec<path::PathBuf>>,
+	disable_verify: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CertFile {
+	chain: path::PathBuf,
+	key: path::PathBuf,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct AuthFile {
+	keys: Option<Vec<path::PathBuf>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CacheFile {
+	max_fragment_size: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct TransportFile {
+	priority: Option<Priority>,
+	weights: Option<BTreeMap<String, u32>>,
+	coalesce_size: Option<usize>,
+	coalesce_delay: Option<u64>,
+	pacing: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct AbrFile {
+	algorithm: Option<AbrAlgorithm>,
+	log: Option<path::PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct QuicFile {
+	congestion: Option<Congestion>,
+	initial_window: Option<u64>,
+	stream_window: Option<u64>,
+	connection_window: Option<u64>,
+	max_uni_streams: Option<u32>,
+	mtu_discovery: Option<bool>,
+	datagram_receive_buffer: Option<usize>,
+	datagram_send_buffer: Option<usize>,
+	idle_timeout: Option<u64>,
+	keep_alive: Option<u64>,
+}
+
+impl ConfigFile {
+	fn load(path: &path::Path) -> anyhow::Result<Self> {
+		let text = fs::read_to_string(path)?;
+		Ok(toml::from_str(&text)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Write the config file to a new directory, returning its path.
+	fn write(name: &str, text: &str) -> path::PathBuf {
+		let dir = std::env::temp_dir().join(format!("moq-relay-config-{}-{}", name, std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+
+		let path = dir.join("relay.toml");
+		fs::write(&path, text).unwrap();
+		path
+	}
+
+	fn load(path: &path::Path, args: &[&str]) -> anyhow::Result<Config> {
+		let config = path.to_str().unwrap();
+		let args = ["moq-relay", "--config", config]
+			.into_iter()
+			.chain(args.iter().copied());
+		Config::try_load_from(args)
+	}
+
+	#[test]
+	fn precedence() {
+		let path = write(
+			"precedence",
+			r#"
+			listen = "127.0.0.1:5000"
+			admin = "127.0.0.1:5001"
+			upstream = ["moqt://file.example"]
+
+			[transport]
+			pacing = 1000
+			weights = { audio = 3 }
+
+			[quic]
+			congestion = "cubic"
+			idle_timeout = 1000
+			"#,
+		);
+
+		let config = load(
+			&path,
+			&[
+				"--listen",
+				"127.0.0.1:6000",
+				"--upstream",
+				"moqt://cli.example",
+				"--idle-timeout",
+				"2000",
+			],
+		)
+		.unwrap();
+
+		// The command line wins, even when it matches the default.
+		assert_eq!(config.listen, "127.0.0.1:6000".parse().unwrap());
+		assert_eq!(config.upstream, vec![Url::parse("moqt://cli.example").unwrap()]);
+		assert_eq!(config.quic.idle_timeout, 2000);
+
+		// Otherwise the file replaces the default.
+		assert_eq!(config.admin, "127.0.0.1:5001".parse().unwrap());
+		assert_eq!(config.pacing, 1000);
+		assert_eq!(config.track_weight, vec![("audio".to_string(), 3)]);
+		assert!(matches!(config.quic.congestion, Congestion::Cubic));
+
+		// Neither sets it, so the default is used.
+		assert_eq!(config.quic.keep_alive, 4000);
+
+		let config = load(&path, &["--idle-timeout", "10000"]).unwrap();
+		assert_eq!(config.quic.idle_timeout, 10000);
+	}
+
+	#[test]
+	fn relative() {
+		let path = write(
+			"relative",
+			r#"
+			[[tls.cert]]
+			chain = "certs/relay.crt"
+			key = "/etc/relay.key"
+
+			[auth]
+			keys = ["auth.secret"]
+
+			[abr]
+			log = "abr"
+			"#,
+		);
+		let dir = path.parent().unwrap();
+
+		let config = load(&path, &[]).unwrap();
+		assert_eq!(config.tls_cert, vec![dir.join("certs/relay.crt")]);
+		assert_eq!(config.tls_key, vec![path::PathBuf::from("/etc/relay.key")]);
+		assert_eq!(config.auth_key, vec![dir.join("auth.secret")]);
+		assert_eq!(config.abr_log, Some(dir.join("abr")));
+
+		// Paths on the command line are left as they are, and replace both the certs and keys.
+		let config = load(&path, &["--tls-cert", "cli.crt"]).unwrap();
+		assert_eq!(config.tls_cert, vec![path::PathBuf::from("cli.crt")]);
+		assert!(config.tls_key.is_empty());
+	}
+
+	#[test]
+	fn invalid() {
+		let path = write("invalid", "listen = ");
+		assert!(load(&path, &[]).is_err());
+
+		let path = write("unknown", "[quic]\nwindow = 1\n");
+		assert!(load(&path, &[]).is_err());
+
+		let path = write("missing", "");
+		assert!(load(&path, &["--no-such-flag"]).is_err());
+	}
 }
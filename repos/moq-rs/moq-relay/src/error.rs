@@ -20,8 +20,17 @@ pub enum RelayError {
 	#[error("webtransport server error: {0}")]
 	WebTransportServer(#[from] webtransport_quinn::ServerError),
 
+	#[error("quic client error: {0}")]
+	QuicClient(#[from] moq_transport::transport::quic::ConnectError),
+
 	#[error("missing node")]
 	MissingNode,
+
+	#[error("loop detected: via={0}")]
+	Loop(String),
+
+	#[error("too many hops: via={0}")]
+	TooManyHops(String),
 }
 
 impl moq_transport::MoqError for RelayError {
@@ -34,6 +43,8 @@ impl moq_transport::MoqError for RelayError {
 			Self::MissingNode => 500,
 			Self::WebTransportClient(_) => 504,
 			Self::WebTransportServer(_) => 500,
+			Self::QuicClient(_) => 504,
+			Self::Loop(_) | Self::TooManyHops(_) => 508,
 		}
 	}
 
@@ -46,6 +57,9 @@ impl moq_transport::MoqError for RelayError {
 			Self::MissingNode => "missing node".to_owned(),
 			Self::WebTransportServer(err) => format!("upstream server error: {}", err),
 			Self::WebTransportClient(err) => format!("upstream client error: {}", err),
+			Self::QuicClient(err) => format!("upstream client error: {}", err),
+			Self::Loop(via) => format!("loop detected: via={}", via),
+			Self::TooManyHops(via) => format!("too many hops: via={}", via),
 		}
 	}
 }
@@ -1,20 +1,32 @@
 use anyhow::Context;
-use clap::Parser;
+use tokio::signal::unix::{signal, SignalKind};
 
+mod abr;
+mod admin;
+mod auth;
 mod config;
 mod error;
+mod metrics;
 mod origin;
 mod quic;
 mod session;
+mod sessions;
 mod tls;
+mod via;
 mod web;
 
+pub use abr::*;
+pub use admin::*;
+pub use auth::*;
 pub use config::*;
 pub use error::*;
+pub use metrics::*;
 pub use origin::*;
 pub use quic::*;
 pub use session::*;
+pub use sessions::*;
 pub use tls::*;
+pub use via::*;
 pub use web::*;
 
 #[tokio::main]
@@ -27,14 +39,27 @@ async fn main() -> anyhow::Result<()> {
 		.finish();
 	tracing::subscriber::set_global_default(tracer).unwrap();
 
-	let config = Config::parse();
+	let config = Config::load()?;
 	let tls = Tls::load(&config)?;
+	let metrics = Metrics::default();
 
 	// Create a QUIC server for media.
-	let quic = Quic::new(config.clone(), tls.clone())
+	let quic = Quic::new(config.clone(), tls.clone(), metrics.clone())
 		.await
 		.context("failed to create server")?;
 
+	// Create the admin server, which is always enabled.
+	let admin = Admin::new(config.admin, quic.origin(), quic.sessions(), metrics);
+
+	let reload = reload(tls.clone(), quic.auth());
+
+	// Drain the sessions on SIGTERM, such as when a container is stopped, then exit.
+	let mut terminate = signal(SignalKind::terminate())?;
+	let shutdown = async move {
+		terminate.recv().await;
+		log::info!("received SIGTERM, shutting down");
+	};
+
 	// Create the web server if the --dev flag was set.
 	// This is currently only useful in local development so it's not enabled by default.
 	if config.dev {
@@ -42,10 +67,37 @@ async fn main() -> anyhow::Result<()> {
 
 		// Unfortunately we can't use preconditions because Tokio still executes the branch; just ignore the result
 		tokio::select! {
-			res = quic.serve() => res.context("failed to run quic server"),
+			res = quic.serve(shutdown) => res.context("failed to run quic server"),
+			res = admin.serve() => res.context("failed to run admin server"),
+			res = reload => res.context("failed to reload config"),
 			res = web.serve() => res.context("failed to run web server"),
 		}
 	} else {
-		quic.serve().await.context("failed to run quic server")
+		tokio::select! {
+			res = quic.serve(shutdown) => res.context("failed to run quic server"),
+			res = admin.serve() => res.context("failed to run admin server"),
+			res = reload => res.context("failed to reload config"),
+		}
 	}
 }
+
+// Reload the TLS certificates and auth keys on SIGHUP, without dropping existing sessions.
+async fn reload(tls: Tls, auth: Auth) -> anyhow::Result<()> {
+	let mut hangup = signal(SignalKind::hangup())?;
+
+	while hangup.recv().await.is_some() {
+		log::info!("reloading TLS certificates and auth keys");
+
+		let res = Config::reload().and_then(|config| {
+			tls.reload(&config)?;
+			auth.replace(config.authorizer()?);
+			Ok(())
+		});
+
+		if let Err(err) = res {
+			log::warn!("failed to reload, keeping the previous config: {:#}", err);
+		}
+	}
+
+	Ok(())
+}
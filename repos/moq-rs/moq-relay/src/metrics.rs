@@ -0,0 +1,331 @@
+use std::{
+	collections::HashMap,
+	fmt::Write,
+	sync::{Arc, Mutex},
+	time,
+};
+
+use moq_transport::{session::Stats, setup::Role};
+
+use crate::{role_label, Origin, SessionInfo};
+
+// The upper bounds of the upstream fetch latency buckets, in seconds.
+const FETCH_BUCKETS: [f64; 10] = [0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Counters for the relay, rendered in the Prometheus text format.
+#[derive(Clone, Default)]
+pub struct Metrics {
+	state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+	// The totals of sessions that have finished.
+	finished: Totals,
+
+	// Upstream fetches from other origins.
+	fetches: u64,
+	fetch_failures: u64,
+	fetch_buckets: [u64; FETCH_BUCKETS.len()],
+	fetch_seconds: f64,
+
+	// Upstream sessions that ended while serving a broadcast, and the resulting outages.
+	upstream_lost: u64,
+	outages: u64,
+	outage_seconds: f64,
+
+	// The segments evicted from the cache, and the evictions counted so far for each cached track.
+	// NOTE: Evictions since the last scrape are missed if the track is removed in the meantime.
+	evicted: u64,
+	evicted_tracks: HashMap<(String, String), usize>,
+}
+
+#[derive(Default)]
+struct Totals {
+	bytes_sent: u64,
+	bytes_received: u64,
+	objects_sent: u64,
+	objects_received: u64,
+}
+
+impl Totals {
+	fn add(&mut self, stats: &Stats) {
+		self.bytes_sent += stats.bytes_sent;
+		self.bytes_received += stats.bytes_received;
+		self.objects_sent += stats.objects_sent;
+		self.objects_received += stats.objects_received;
+	}
+}
+
+impl Metrics {
+	/// Add the final stats of a session to the totals.
+	pub fn finish(&self, stats: &Stats) {
+		self.state.lock().unwrap().finished.add(stats);
+	}
+
+	/// Record an upstream fetch that connected after `elapsed`.
+	pub fn fetch(&self, elapsed: time::Duration) {
+		let mut state = self.state.lock().unwrap();
+		let seconds = elapsed.as_secs_f64();
+
+		state.fetches += 1;
+		state.fetch_seconds += seconds;
+
+		for (bucket, le) in state.fetch_buckets.iter_mut().zip(FETCH_BUCKETS) {
+			if seconds <= le {
+				*bucket += 1;
+			}
+		}
+	}
+
+	/// Record an upstream fetch that failed.
+	pub fn fetch_failed(&self) {
+		self.state.lock().unwrap().fetch_failures += 1;
+	}
+
+	/// Record an upstream session that ended while serving a broadcast.
+	pub fn upstream_lost(&self) {
+		self.state.lock().unwrap().upstream_lost += 1;
+	}
+
+	/// Record an outage of an upstream broadcast until the returned guard is dropped.
+	pub fn outage(&self) -> Outage {
+		self.state.lock().unwrap().outages += 1;
+
+		Outage {
+			metrics: self.clone(),
+			start: time::Instant::now(),
+		}
+	}
+
+	// Add the evictions since the last scrape to the total, given the evictions so far for each cached track.
+	fn evicted(&self, tracks: HashMap<(String, String), usize>) -> u64 {
+		let mut state = self.state.lock().unwrap();
+
+		for (key, expired) in &tracks {
+			// A smaller count means the track was replaced by a new one with the same name.
+			let seen = state.evicted_tracks.get(key).copied().filter(|seen| seen <= expired);
+			state.evicted += (expired - seen.unwrap_or(0)) as u64;
+		}
+
+		state.evicted_tracks = tracks;
+		state.evicted
+	}
+
+	/// Render the metrics in the Prometheus text exposition format.
+	pub fn render(&self, origin: &Origin, sessions: &[SessionInfo]) -> String {
+		let mut out = String::new();
+
+		{
+			let state = self.state.lock().unwrap();
+
+			let mut roles = HashMap::new();
+			let mut tracks = HashMap::<String, u64>::new();
+			let mut totals = Totals::default();
+
+			for session in sessions {
+				totals.add(&session.stats);
+
+				*roles.entry(role_label(session.role)).or_insert(0u64) += 1;
+
+				for sub in session.stats.served.values() {
+					*tracks.entry(sub.name.clone()).or_default() += 1;
+				}
+			}
+
+			header(
+				&mut out,
+				"moq_relay_sessions",
+				"gauge",
+				"The number of active sessions by role.",
+			);
+			for role in [Role::Publisher, Role::Subscriber, Role::Both] {
+				let role = role_label(role);
+				let count = roles.get(role).copied().unwrap_or(0);
+				writeln!(out, "moq_relay_sessions{{role=\"{}\"}} {}", role, count).unwrap();
+			}
+
+			header(
+				&mut out,
+				"moq_relay_subscriptions",
+				"gauge",
+				"The number of subscriptions being served by track.",
+			);
+			for (track, count) in tracks {
+				writeln!(out, "moq_relay_subscriptions{{track=\"{}\"}} {}", escape(&track), count).unwrap();
+			}
+
+			let finished = &state.finished;
+			counter(
+				&mut out,
+				"moq_relay_bytes_sent_total",
+				"The number of UDP bytes sent.",
+				finished.bytes_sent + totals.bytes_sent,
+			);
+			counter(
+				&mut out,
+				"moq_relay_bytes_received_total",
+				"The number of UDP bytes received.",
+				finished.bytes_received + totals.bytes_received,
+			);
+			counter(
+				&mut out,
+				"moq_relay_objects_sent_total",
+				"The number of OBJECTs sent.",
+				finished.objects_sent + totals.objects_sent,
+			);
+			counter(
+				&mut out,
+				"moq_relay_objects_received_total",
+				"The number of OBJECTs received.",
+				finished.objects_received + totals.objects_received,
+			);
+
+			counter(
+				&mut out,
+				"moq_relay_upstream_failures_total",
+				"The number of failed fetches from upstream origins.",
+				state.fetch_failures,
+			);
+
+			counter(
+				&mut out,
+				"moq_relay_upstream_lost_total",
+				"The number of upstream sessions that ended while serving a broadcast.",
+				state.upstream_lost,
+			);
+
+			gauge(
+				&mut out,
+				"moq_relay_upstream_outages",
+				"The number of upstream broadcasts currently reconnecting.",
+				state.outages as usize,
+			);
+
+			header(
+				&mut out,
+				"moq_relay_upstream_outage_seconds_total",
+				"counter",
+				"The total time upstream broadcasts spent reconnecting.",
+			);
+			writeln!(out, "moq_relay_upstream_outage_seconds_total {}", state.outage_seconds).unwrap();
+
+			header(
+				&mut out,
+				"moq_relay_upstream_fetch_seconds",
+				"histogram",
+				"The time to connect to an upstream origin.",
+			);
+			for (bucket, le) in state.fetch_buckets.iter().zip(FETCH_BUCKETS) {
+				writeln!(
+					out,
+					"moq_relay_upstream_fetch_seconds_bucket{{le=\"{}\"}} {}",
+					le, bucket
+				)
+				.unwrap();
+			}
+			writeln!(
+				out,
+				"moq_relay_upstream_fetch_seconds_bucket{{le=\"+Inf\"}} {}",
+				state.fetches
+			)
+			.unwrap();
+			writeln!(out, "moq_relay_upstream_fetch_seconds_sum {}", state.fetch_seconds).unwrap();
+			writeln!(out, "moq_relay_upstream_fetch_seconds_count {}", state.fetches).unwrap();
+		}
+
+		// Walk the cache without holding our own lock.
+		let broadcasts = origin.broadcasts();
+
+		let mut cache_tracks = 0;
+		let mut cache_segments = 0;
+		let mut cache_bytes = 0;
+		let mut cache_expired = HashMap::new();
+
+		for broadcast in &broadcasts {
+			for track in broadcast.tracks() {
+				let cached = track.cached();
+				cache_tracks += 1;
+				cache_segments += cached.segments;
+				cache_bytes += cached.bytes;
+				cache_expired.insert((broadcast.id.clone(), track.name.clone()), cached.expired);
+			}
+		}
+
+		let evicted = self.evicted(cache_expired);
+
+		gauge(
+			&mut out,
+			"moq_relay_broadcasts",
+			"The number of broadcasts in the cache.",
+			broadcasts.len(),
+		);
+		gauge(
+			&mut out,
+			"moq_relay_cache_tracks",
+			"The number of tracks in the cache.",
+			cache_tracks,
+		);
+		gauge(
+			&mut out,
+			"moq_relay_cache_segments",
+			"The number of segments in the cache.",
+			cache_segments,
+		);
+		gauge(
+			&mut out,
+			"moq_relay_cache_bytes",
+			"The number of payload bytes in the cache.",
+			cache_bytes,
+		);
+		counter(
+			&mut out,
+			"moq_relay_cache_evicted_segments_total",
+			"The number of segments evicted from the cache.",
+			evicted,
+		);
+
+		out
+	}
+}
+
+/// An outage of an upstream broadcast, which is added to the metrics when dropped.
+pub struct Outage {
+	metrics: Metrics,
+	start: time::Instant,
+}
+
+impl Outage {
+	/// How long the outage has lasted so far.
+	pub fn elapsed(&self) -> time::Duration {
+		self.start.elapsed()
+	}
+}
+
+impl Drop for Outage {
+	fn drop(&mut self) {
+		let mut state = self.metrics.state.lock().unwrap();
+		state.outages -= 1;
+		state.outage_seconds += self.start.elapsed().as_secs_f64();
+	}
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+	writeln!(out, "# HELP {} {}", name, help).unwrap();
+	writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+	header(out, name, "counter", help);
+	writeln!(out, "{} {}", name, value).unwrap();
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: usize) {
+	header(out, name, "gauge", help);
+	writeln!(out, "{} {}", name, value).unwrap();
+}
+
+// Escape a label value.
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
@@ -1,16 +1,40 @@
 use std::ops::{Deref, DerefMut};
 use std::{
 	collections::HashMap,
+	fmt,
+	future::Future,
 	sync::{Arc, Mutex, Weak},
 };
 
 use moq_api::ApiError;
-use moq_transport::cache::{broadcast, CacheError};
+use moq_transport::{
+	cache::{broadcast, track, CacheError},
+	setup::Role,
+	transport::quic,
+	MoqError,
+};
 use url::Url;
 
-use tokio::time;
+use tokio::{
+	sync::{watch, Mutex as AsyncMutex, Notify, OwnedMutexGuard},
+	time,
+};
+
+use crate::{Grant, Metrics, RelayError, Via, VIA_PARAM};
+
+// Reconnect to the origin with exponential backoff, giving up after an outage this long.
+const RETRY_MIN: time::Duration = time::Duration::from_millis(100);
+const RETRY_MAX: time::Duration = time::Duration::from_secs(5);
+const RETRY_TIMEOUT: time::Duration = time::Duration::from_secs(30);
 
-use crate::RelayError;
+// Keep subscribers of a broadcast that isn't published anywhere for this long, waiting for a publisher.
+const PENDING_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+// Wait this long for the current source of a broadcast to hand it over to a new publisher.
+const TAKEOVER_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+// The most broadcasts a single session can subscribe to through a router at once.
+const MAX_ROUTES: usize = 32;
 
 #[derive(Clone)]
 pub struct Origin {
@@ -24,54 +48,140 @@ pub struct Origin {
 	// TODO: Stub this out instead.
 	node: Option<Url>,
 
+	// Relays to fetch unknown broadcasts from, tried in order after the API.
+	upstream: Vec<Url>,
+
+	// Our ID, added to the via parameter of upstream fetches to detect loops.
+	id: String,
+
 	// A map of active broadcasts by ID.
 	cache: Arc<Mutex<HashMap<String, Weak<Subscriber>>>>,
 
 	// A QUIC endpoint we'll use to fetch from other origins.
 	quic: quinn::Endpoint,
+
+	// The client config used for moqt:// origins, which negotiates MoQ directly over QUIC.
+	quic_config: quinn::ClientConfig,
+
+	// Records the latency and failures of upstream fetches.
+	metrics: Metrics,
 }
 
 impl Origin {
-	pub fn new(api: Option<moq_api::Client>, node: Option<Url>, quic: quinn::Endpoint) -> Self {
+	pub fn new(
+		api: Option<moq_api::Client>,
+		node: Option<Url>,
+		upstream: Vec<Url>,
+		id: String,
+		quic: quinn::Endpoint,
+		quic_config: quinn::ClientConfig,
+		metrics: Metrics,
+	) -> Self {
 		Self {
 			api,
 			node,
+			upstream,
+			id,
 			cache: Default::default(),
 			quic,
+			quic_config,
+			metrics,
 		}
 	}
 
-	/// Create a new broadcast with the given ID.
-	///
-	/// Publisher::run needs to be called to periodically refresh the origin cache.
-	pub async fn publish(&mut self, id: &str) -> Result<Publisher, RelayError> {
-		let (publisher, subscriber) = broadcast::new(id);
+	/// The ID of this relay, used to detect loops.
+	pub fn id(&self) -> &str {
+		&self.id
+	}
 
+	/// Return the broadcasts currently in the cache.
+	pub fn broadcasts(&self) -> Vec<Arc<Subscriber>> {
+		let cache = self.cache.lock().unwrap();
+		cache.values().filter_map(Weak::upgrade).collect()
+	}
+
+	/// Close a broadcast, disconnecting its publisher or upstream origin and removing it from the cache.
+	///
+	/// Existing subscribers receive an error, while new subscribers will fetch the broadcast again.
+	pub fn close(&self, id: &str) -> Result<(), CacheError> {
 		let subscriber = {
 			let mut cache = self.cache.lock().unwrap();
+			let subscriber = cache.get(id).and_then(Weak::upgrade).ok_or(CacheError::NotFound)?;
+			cache.remove(id);
+			subscriber
+		};
+
+		// Stops an upstream fetch, since its session can't fulfill a closed broadcast.
+		subscriber.publisher.clone().close(CacheError::Closed).ok();
+
+		// Wakes the task serving a local publisher, even if it's not waiting yet.
+		subscriber.close.notify_one();
 
-			// Check if the broadcast already exists.
-			// TODO This is racey, because a new publisher could be created while existing subscribers are still active.
-			if cache.contains_key(id) {
-				return Err(CacheError::Duplicate.into());
+		Ok(())
+	}
+
+	// Return the cached broadcast, or insert a new one if it's missing or closed.
+	//
+	// A new broadcast comes with its lease, so the caller becomes its source before anybody else can.
+	fn entry(&self, id: &str) -> (Arc<Subscriber>, Option<Lease>) {
+		let mut cache = self.cache.lock().unwrap();
+
+		// A closed broadcast may be held by its old subscribers until they notice, but new sessions shouldn't join it.
+		// NOTE: It's dropped after unlocking the cache, since dropping the last reference removes it from the cache.
+		let existing = cache.get(id).and_then(Weak::upgrade);
+		if let Some(subscriber) = &existing {
+			if subscriber.is_closed().is_none() {
+				return (subscriber.clone(), None);
 			}
+		}
 
-			// Create subscriber that will remove from the cache when dropped.
-			let subscriber = Arc::new(Subscriber {
-				broadcast: subscriber,
-				origin: self.clone(),
-			});
+		let (publisher, broadcast) = broadcast::new(id);
+		let subscriber = Arc::new(Subscriber {
+			broadcast,
+			publisher,
+			origin: self.clone(),
+			close: Default::default(),
+			lease: Default::default(),
+			waiting: Arc::new(watch::channel(0).0),
+		});
 
-			cache.insert(id.to_string(), Arc::downgrade(&subscriber));
+		// The lease was just created, so it can't be held yet.
+		let lease = subscriber.lease().expect("new lease is free");
 
-			subscriber
+		cache.insert(id.to_string(), Arc::downgrade(&subscriber));
+		drop(cache);
+		drop(existing);
+
+		(subscriber, Some(lease))
+	}
+
+	/// Publish the broadcast with the given ID, creating it or taking it over from the current source.
+	///
+	/// An existing broadcast may be waiting for a publisher, fetched from another origin, or published by another session.
+	/// Its subscribers are kept and their tracks are resumed by the new publisher, which allows a publisher to reconnect.
+	///
+	/// Publisher::run needs to be called to periodically refresh the origin cache.
+	pub async fn publish(&mut self, id: &str) -> Result<Publisher, RelayError> {
+		let (subscriber, lease) = loop {
+			let (subscriber, lease) = self.entry(id);
+
+			let lease = match lease {
+				Some(lease) => lease,
+				None => subscriber.take_over().await?,
+			};
+
+			// The previous source may have closed the broadcast before releasing the lease, so start a new one.
+			if subscriber.is_closed().is_none() {
+				break (subscriber, lease);
+			}
 		};
 
 		// Create a publisher that constantly updates itself as the origin in moq-api.
 		// It holds a reference to the subscriber to prevent dropping early.
 		let mut publisher = Publisher {
-			broadcast: publisher,
+			broadcast: lease.broadcast.clone(),
 			subscriber,
+			lease: Some(lease),
 			api: None,
 		};
 
@@ -89,25 +199,26 @@ impl Origin {
 		Ok(publisher)
 	}
 
-	pub fn subscribe(&self, id: &str) -> Arc<Subscriber> {
-		let mut cache = self.cache.lock().unwrap();
+	/// Return the broadcast with the given ID, fetching it from another origin if it's not in the cache.
+	///
+	/// The `via` parameter lists the relays the subscriber's request already passed through.
+	///
+	/// A broadcast that isn't published anywhere yet is kept for a while, so a publisher can connect after its subscribers.
+	pub fn subscribe(&self, id: &str, via: &Via) -> Arc<Subscriber> {
+		let (subscriber, lease) = self.entry(id);
 
-		if let Some(broadcast) = cache.get(id) {
-			if let Some(broadcast) = broadcast.upgrade() {
-				return broadcast;
-			}
+		if let Some(lease) = lease {
+			self.fetch(via, lease);
 		}
 
-		let (publisher, subscriber) = broadcast::new(id);
-		let subscriber = Arc::new(Subscriber {
-			broadcast: subscriber,
-			origin: self.clone(),
-		});
-
-		cache.insert(id.to_string(), Arc::downgrade(&subscriber));
+		subscriber
+	}
 
+	// Fulfill the broadcast from another origin until it's closed or taken over by a local publisher.
+	fn fetch(&self, via: &Via, mut lease: Lease) {
 		let mut this = self.clone();
-		let id = id.to_string();
+		let id = lease.broadcast.id.clone();
+		let via = via.with(&self.id);
 
 		// Rather than fetching from the API and connecting via QUIC inline, we'll spawn a task to do it.
 		// This way we could stop polling this session and it won't impact other session.
@@ -115,47 +226,248 @@ impl Origin {
 		// However, the downside is that we don't return an error immediately.
 		// If that's important, it can be done but it gets a bit racey.
 		tokio::spawn(async move {
-			if let Err(err) = this.serve(&id, publisher).await {
+			if let Err(err) = this.serve(&id, &via, &mut lease).await {
 				log::warn!("failed to serve remote broadcast: id={} err={}", id, err);
+
+				let err = match err {
+					RelayError::Cache(err) => err,
+					err => CacheError::Reset(err.code()),
+				};
+
+				lease.broadcast.clone().close(err).ok();
 			}
 		});
+	}
 
-		subscriber
+	// Fetch the broadcast from its origin, reconnecting until the outage is too long or there are no subscribers left.
+	//
+	// The tracks of a failed session are resumed by the next one, so downstream subscribers stay attached.
+	// Returns early if a local publisher takes over, handing it the tracks instead.
+	async fn serve(&mut self, id: &str, via: &Via, lease: &mut Lease) -> Result<(), RelayError> {
+		let mut delay = RETRY_MIN;
+		let mut outage = None;
+		let mut served = false;
+		let pending = time::Instant::now();
+
+		loop {
+			let start = time::Instant::now();
+
+			let res = tokio::select! {
+				res = self.connect(id, via, lease.broadcast.clone()) => res,
+				_ = lease.interrupted() => return Ok(()),
+			};
+
+			let err = match res {
+				Ok(session) => {
+					self.metrics.fetch(start.elapsed());
+
+					if outage.take().is_some() {
+						log::info!("resumed remote broadcast: id={}", id);
+					}
+
+					delay = RETRY_MIN;
+					served = true;
+
+					let res = tokio::select! {
+						res = session.clone().run() => res,
+						_ = lease.takeover() => {
+							log::info!("remote broadcast taken over by local publisher: id={}", id);
+							return lease.requeue(session.detach());
+						}
+					};
+
+					if lease.broadcast.is_closed().is_some() {
+						// There are no subscribers left.
+						return Ok(());
+					}
+
+					// Keep the tracks open, so they're resumed by the next session.
+					lease.requeue(session.detach())?;
+
+					self.metrics.upstream_lost();
+
+					match res {
+						Ok(()) => RelayError::Cache(CacheError::Closed),
+						Err(err) => err.into(),
+					}
+				}
+				// The broadcast isn't published anywhere yet, unless it was served before and the origin is moving.
+				Err(RelayError::Cache(CacheError::NotFound)) if !served => {
+					if pending.elapsed() >= PENDING_TIMEOUT {
+						return Err(CacheError::NotFound.into());
+					}
+
+					log::debug!("waiting for broadcast to be published: id={} delay={:?}", id, delay);
+
+					if !lease.backoff(delay).await {
+						return Ok(());
+					}
+
+					delay = (delay * 2).min(RETRY_MAX);
+					continue;
+				}
+				Err(err) => {
+					self.metrics.fetch_failed();
+					err
+				}
+			};
+
+			let outage = outage.get_or_insert_with(|| self.metrics.outage());
+			if outage.elapsed() >= RETRY_TIMEOUT {
+				return Err(err);
+			}
+
+			log::warn!(
+				"lost remote broadcast, retrying: id={} delay={:?} err={}",
+				id,
+				delay,
+				err
+			);
+
+			if !lease.backoff(delay).await {
+				return Ok(());
+			}
+
+			delay = (delay * 2).min(RETRY_MAX);
+		}
 	}
 
-	async fn serve(&mut self, id: &str, publisher: broadcast::Publisher) -> Result<(), RelayError> {
+	// Find the origin for the broadcast and connect to it, trying each upstream relay in order.
+	async fn connect(
+		&mut self,
+		id: &str,
+		via: &Via,
+		publisher: broadcast::Publisher,
+	) -> Result<moq_transport::session::Subscriber, RelayError> {
 		log::debug!("finding origin: id={}", id);
 
+		let mut urls = Vec::new();
+
 		// Fetch the origin from the API.
-		let origin = self
-			.api
-			.as_mut()
-			.ok_or(CacheError::NotFound)?
-			.get_origin(id)
-			.await?
-			.ok_or(CacheError::NotFound)?;
+		// NOTE: No token is sent to these origins, so they can't require one.
+		// If the API is unavailable, fall back to the upstream relays instead of failing the fetch.
+		if let Some(api) = self.api.as_mut() {
+			match api.get_origin(id).await {
+				Ok(Some(origin)) => urls.push(origin.url),
+				Ok(None) => {}
+				Err(err) => log::warn!("failed to get origin: id={} err={}", id, err),
+			}
+		}
 
-		log::debug!("fetching from origin: id={} url={}", id, origin.url);
+		// Otherwise ask our upstream relays, keeping any query such as a token.
+		for upstream in &self.upstream {
+			let mut url = upstream.clone();
+			url.set_path(&format!("{}/{}", upstream.path().trim_end_matches('/'), id));
+			urls.push(url);
+		}
 
-		// Establish the webtransport session.
-		let session = webtransport_quinn::connect(&self.quic, &origin.url).await?;
-		let session = moq_transport::session::Client::subscriber(session, publisher).await?;
+		let mut res = Err(CacheError::NotFound.into());
 
-		session.run().await?;
+		for mut url in urls {
+			// Tell the origin which relays the request passed through, so it can detect loops.
+			url.query_pairs_mut().append_pair(VIA_PARAM, &via.to_string());
 
-		Ok(())
+			log::debug!("fetching from origin: id={} url={}", id, url);
+
+			res = self.connect_url(&url, publisher.clone()).await;
+			match &res {
+				Ok(_) => break,
+				Err(err) => log::warn!("failed to connect to origin: id={} url={} err={}", id, url, err),
+			}
+		}
+
+		res
+	}
+
+	async fn connect_url(
+		&self,
+		url: &Url,
+		publisher: broadcast::Publisher,
+	) -> Result<moq_transport::session::Subscriber, RelayError> {
+		// The path and query are sent in the SETUP message, since the WebTransport client drops the query.
+		let client = moq_transport::session::Client::default()
+			.with_path(&url[url::Position::BeforePath..url::Position::AfterQuery]);
+
+		let session = match url.scheme() {
+			quic::SCHEME => {
+				// Connect directly over QUIC.
+				let conn = quic::connect(&self.quic, self.quic_config.clone(), url).await?;
+				client.subscriber(conn, publisher).await?
+			}
+			_ => {
+				// Establish the webtransport session.
+				let session = webtransport_quinn::connect(&self.quic, url).await?;
+				client.subscriber(session, publisher).await?
+			}
+		};
+
+		Ok(session)
 	}
 }
 
 pub struct Subscriber {
 	pub broadcast: broadcast::Subscriber,
 
+	// The other half of the broadcast, fulfilled by whoever holds the lease.
+	// NOTE: This keeps the broadcast open while it's waiting for a new source.
+	publisher: broadcast::Publisher,
+
 	origin: Origin,
+
+	// Notified by Origin::close to stop serving the broadcast.
+	close: Arc<Notify>,
+
+	// Held by the source of the broadcast: a local publisher or the task fetching it from another origin.
+	lease: Arc<AsyncMutex<()>>,
+
+	// The number of new publishers waiting for the lease.
+	waiting: Arc<watch::Sender<usize>>,
+}
+
+impl Subscriber {
+	// Acquire the lease if nobody holds it.
+	fn lease(&self) -> Option<Lease> {
+		let guard = self.lease.clone().try_lock_owned().ok()?;
+		Some(self.with_guard(guard))
+	}
+
+	// Ask the current source to hand over the broadcast, waiting for it to release the lease.
+	async fn take_over(&self) -> Result<Lease, CacheError> {
+		if let Some(lease) = self.lease() {
+			return Ok(lease);
+		}
+
+		log::info!("taking over broadcast: id={}", self.id);
+
+		// The source hands over while anybody is waiting, even if it checks after we started.
+		let _waiting = Waiting::new(self.waiting.clone());
+
+		let guard = time::timeout(TAKEOVER_TIMEOUT, self.lease.clone().lock_owned())
+			.await
+			.map_err(|_| CacheError::Duplicate)?;
+
+		Ok(self.with_guard(guard))
+	}
+
+	fn with_guard(&self, guard: OwnedMutexGuard<()>) -> Lease {
+		Lease {
+			broadcast: self.publisher.clone(),
+			waiting: self.waiting.clone(),
+			_guard: guard,
+		}
+	}
 }
 
 impl Drop for Subscriber {
 	fn drop(&mut self) {
-		self.origin.cache.lock().unwrap().remove(&self.broadcast.id);
+		let mut cache = self.origin.cache.lock().unwrap();
+
+		// Only remove our own entry, since it may have been closed and replaced.
+		if let Some(entry) = cache.get(&self.broadcast.id) {
+			if std::ptr::eq(entry.as_ptr(), self) {
+				cache.remove(&self.broadcast.id);
+			}
+		}
 	}
 }
 
@@ -167,13 +479,79 @@ impl Deref for Subscriber {
 	}
 }
 
+// Counts a publisher waiting in take_over, until it gets the lease, times out, or is cancelled.
+//
+// A count rather than a stored permit, so a publisher that gave up doesn't make the next source hand over.
+struct Waiting(Arc<watch::Sender<usize>>);
+
+impl Waiting {
+	fn new(waiting: Arc<watch::Sender<usize>>) -> Self {
+		waiting.send_modify(|count| *count += 1);
+		Self(waiting)
+	}
+}
+
+impl Drop for Waiting {
+	fn drop(&mut self) {
+		self.0.send_modify(|count| *count -= 1);
+	}
+}
+
+// Resolves once a new publisher is waiting for the lease.
+async fn takeover(waiting: &watch::Sender<usize>) {
+	// The sender is borrowed, so it can't be dropped while we wait.
+	waiting.subscribe().wait_for(|count| *count > 0).await.ok();
+}
+
+// The right to fulfill a broadcast, held by one source at a time.
+struct Lease {
+	broadcast: broadcast::Publisher,
+	waiting: Arc<watch::Sender<usize>>,
+	_guard: OwnedMutexGuard<()>,
+}
+
+impl Lease {
+	// Queue the tracks of the previous source, so the next one resumes them.
+	fn requeue(&mut self, tracks: Vec<track::Publisher>) -> Result<(), RelayError> {
+		for track in tracks {
+			self.broadcast.requeue(track)?;
+		}
+
+		Ok(())
+	}
+
+	// Resolves once a new publisher wants to take over the broadcast.
+	async fn takeover(&self) {
+		takeover(&self.waiting).await
+	}
+
+	// Resolves once the broadcast is closed or a new publisher wants to take it over.
+	async fn interrupted(&self) {
+		tokio::select! {
+			_ = self.broadcast.closed() => {},
+			_ = self.takeover() => {},
+		}
+	}
+
+	// Sleep before retrying, returning false if the source should stop instead.
+	async fn backoff(&self, delay: time::Duration) -> bool {
+		tokio::select! {
+			_ = time::sleep(delay) => true,
+			_ = self.interrupted() => false,
+		}
+	}
+}
+
 pub struct Publisher {
 	pub broadcast: broadcast::Publisher,
 
 	api: Option<(moq_api::Client, moq_api::Origin)>,
 
-	#[allow(dead_code)]
+	// Holds the cache entry until the publisher is dropped.
 	subscriber: Arc<Subscriber>,
+
+	// Released when the publisher hands over the broadcast, otherwise it's closed on drop.
+	lease: Option<Lease>,
 }
 
 impl Publisher {
@@ -192,6 +570,42 @@ impl Publisher {
 		}
 	}
 
+	/// Resolves once the broadcast is closed by [Origin::close].
+	// NOTE: The future doesn't borrow the publisher, so it can be polled alongside Publisher::run.
+	pub fn closed(&self) -> impl Future<Output = ()> {
+		let close = self.subscriber.close.clone();
+		async move { close.notified().await }
+	}
+
+	/// Resolves once another publisher wants to take over the broadcast, see [Publisher::handover].
+	pub fn takeover(&self) -> impl Future<Output = ()> {
+		let waiting = self.subscriber.waiting.clone();
+		async move { takeover(&waiting).await }
+	}
+
+	/// Hand the tracks of the session to the publisher taking over the broadcast.
+	pub fn handover(&mut self, tracks: Vec<track::Publisher>) -> Result<(), RelayError> {
+		if let Some(mut lease) = self.lease.take() {
+			lease.requeue(tracks)?;
+		}
+
+		Ok(())
+	}
+
+	/// Stop publishing after the session ended, keeping the subscribers and tracks for a reconnect.
+	///
+	/// The broadcast is fetched from another origin until a new publisher takes it over, or it's closed once it can't be found.
+	pub async fn unpublish(&mut self, tracks: Vec<track::Publisher>) -> Result<(), RelayError> {
+		self.close().await?;
+
+		if let Some(mut lease) = self.lease.take() {
+			lease.requeue(tracks)?;
+			self.subscriber.origin.fetch(&Via::default(), lease);
+		}
+
+		Ok(())
+	}
+
 	pub async fn close(&mut self) -> Result<(), ApiError> {
 		if let Some((api, _)) = self.api.as_mut() {
 			api.delete_origin(&self.broadcast.id).await?;
@@ -201,6 +615,15 @@ impl Publisher {
 	}
 }
 
+impl Drop for Publisher {
+	fn drop(&mut self) {
+		// Close the broadcast unless it was handed over, so subscribers don't wait forever.
+		if let Some(lease) = self.lease.take() {
+			lease.broadcast.close(CacheError::Closed).ok();
+		}
+	}
+}
+
 impl Deref for Publisher {
 	type Target = broadcast::Publisher;
 
@@ -214,3 +637,207 @@ impl DerefMut for Publisher {
 		&mut self.broadcast
 	}
 }
+
+/// Routes SUBSCRIBE namespaces to broadcasts in the origin, used by sessions that serve more than one broadcast.
+///
+/// Each routed broadcast is held until it's closed or the router is dropped, so it's only fetched once per session.
+/// Only the namespaces the session is allowed to subscribe to are routed, up to a limit.
+pub struct Router {
+	origin: Origin,
+	grant: Grant,
+	via: Via,
+	subscribers: Mutex<HashMap<String, Arc<Subscriber>>>,
+}
+
+impl Router {
+	pub fn new(origin: Origin, grant: Grant, via: Via) -> Self {
+		Self {
+			origin,
+			grant,
+			via,
+			subscribers: Default::default(),
+		}
+	}
+}
+
+impl moq_transport::session::Router for Router {
+	fn route(&self, namespace: &str) -> Result<broadcast::Subscriber, CacheError> {
+		self.grant
+			.check(namespace, Role::Subscriber)
+			.map_err(|_| CacheError::Unauthorized)?;
+
+		let mut subscribers = self.subscribers.lock().unwrap();
+
+		// Forget the closed broadcasts, so they're fetched again and don't count towards the limit.
+		subscribers.retain(|_, subscriber| subscriber.is_closed().is_none());
+
+		if !subscribers.contains_key(namespace) && subscribers.len() >= MAX_ROUTES {
+			return Err(CacheError::TooLarge);
+		}
+
+		let subscriber = subscribers
+			.entry(namespace.to_string())
+			.or_insert_with(|| self.origin.subscribe(namespace, &self.via));
+
+		Ok(subscriber.broadcast.clone())
+	}
+}
+
+impl fmt::Debug for Router {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Router")
+			.field("namespaces", &self.subscribers.lock().unwrap().keys())
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// An origin without an API or upstream relays, so broadcasts are only published locally.
+	fn origin() -> Origin {
+		let quic = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+		let tls = rustls::ClientConfig::builder()
+			.with_safe_defaults()
+			.with_root_certificates(rustls::RootCertStore::empty())
+			.with_no_client_auth();
+
+		Origin::new(
+			None,
+			None,
+			Vec::new(),
+			"test".to_string(),
+			quic,
+			quinn::ClientConfig::new(Arc::new(tls)),
+			Metrics::default(),
+		)
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn subscribe_first() {
+		let mut origin = origin();
+
+		let subscriber = origin.subscribe("demo", &Via::default());
+		let mut track = subscriber.get_track("video").unwrap();
+
+		// The publisher takes over from the task waiting for it, and receives the pending request.
+		time::sleep(time::Duration::from_secs(1)).await;
+		let mut publisher = origin.publish("demo").await.unwrap();
+		assert!(Arc::ptr_eq(&subscriber, &publisher.subscriber));
+
+		let requested = publisher.next_track().await.unwrap();
+		assert_eq!(requested.name, "video");
+
+		requested.close(CacheError::Closed).unwrap();
+		assert!(track.segment().await.unwrap().is_none());
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn pending_timeout() {
+		let origin = origin();
+
+		let subscriber = origin.subscribe("demo", &Via::default());
+		assert!(matches!(subscriber.closed().await, CacheError::NotFound));
+
+		// A new subscriber starts over.
+		let next = origin.subscribe("demo", &Via::default());
+		assert!(!Arc::ptr_eq(&subscriber, &next));
+		assert!(next.is_closed().is_none());
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn takeover() {
+		let mut origin = origin();
+
+		let mut first = origin.publish("demo").await.unwrap();
+		let subscriber = origin.subscribe("demo", &Via::default());
+		let _track = subscriber.get_track("video").unwrap();
+		let requested = first.next_track().await.unwrap();
+
+		let handover = tokio::spawn(async move {
+			first.takeover().await;
+			first.handover(vec![requested]).unwrap();
+		});
+
+		let mut second = origin.publish("demo").await.unwrap();
+		handover.await.unwrap();
+
+		// The subscribers and tracks are kept, even though the first publisher was dropped.
+		assert!(Arc::ptr_eq(&subscriber, &second.subscriber));
+		assert!(subscriber.is_closed().is_none());
+		assert_eq!(second.next_track().await.unwrap().name, "video");
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn reconnect() {
+		let mut origin = origin();
+
+		let mut first = origin.publish("demo").await.unwrap();
+		let subscriber = origin.subscribe("demo", &Via::default());
+		let _track = subscriber.get_track("video").unwrap();
+		let requested = first.next_track().await.unwrap();
+
+		first.unpublish(vec![requested]).await.unwrap();
+		drop(first);
+
+		// Reconnect before the broadcast is given up on.
+		time::sleep(PENDING_TIMEOUT / 2).await;
+		assert!(subscriber.is_closed().is_none());
+
+		let mut second = origin.publish("demo").await.unwrap();
+		assert!(Arc::ptr_eq(&subscriber, &second.subscriber));
+		assert_eq!(second.next_track().await.unwrap().name, "video");
+
+		// The task waiting for a publisher stopped, so the broadcast isn't closed after the timeout.
+		time::sleep(PENDING_TIMEOUT).await;
+		assert!(subscriber.is_closed().is_none());
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn takeover_timeout() {
+		let mut origin = origin();
+
+		// The first publisher never hands over.
+		let first = origin.publish("demo").await.unwrap();
+
+		let start = time::Instant::now();
+		let err = origin.publish("demo").await.err().unwrap();
+		assert!(matches!(err, RelayError::Cache(CacheError::Duplicate)));
+		assert_eq!(start.elapsed(), TAKEOVER_TIMEOUT);
+
+		// Nobody is waiting anymore, so the first publisher isn't asked to hand over.
+		assert!(time::timeout(TAKEOVER_TIMEOUT, first.takeover()).await.is_err());
+
+		// Nor is the next one once it takes over.
+		drop(first);
+		let second = origin.publish("demo").await.unwrap();
+		assert!(time::timeout(TAKEOVER_TIMEOUT, second.takeover()).await.is_err());
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn takeover_closed() {
+		let mut origin = origin();
+
+		let first = origin.publish("demo").await.unwrap();
+		let subscriber = origin.subscribe("demo", &Via::default());
+
+		let mut next = origin.clone();
+		let second = tokio::spawn(async move { next.publish("demo").await });
+
+		// The first publisher closes the broadcast instead of handing it over.
+		first.takeover().await;
+		drop(first);
+
+		let second = second.await.unwrap().unwrap();
+		assert!(subscriber.is_closed().is_some());
+
+		// The new publisher gets a new broadcast, rather than the closed one.
+		assert!(second.subscriber.is_closed().is_none());
+		assert!(!Arc::ptr_eq(&subscriber, &second.subscriber));
+		assert!(Arc::ptr_eq(
+			&origin.subscribe("demo", &Via::default()),
+			&second.subscriber
+		));
+	}
+}
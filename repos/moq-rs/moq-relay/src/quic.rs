@@ -1,10 +1,11 @@
-use std::{sync::Arc, time};
+use std::{future::Future, sync::Arc, time};
 
 use anyhow::Context;
 
+use moq_transport::transport::quic;
 use tokio::task::JoinSet;
 
-use crate::{Config, Origin, Session, Tls};
+use crate::{Auth, Config, Metrics, Origin, Session, Sessions, Tls};
 
 pub struct Quic {
 	quic: quinn::Endpoint,
@@ -14,28 +15,39 @@ pub struct Quic {
 
 	// The map of active broadcasts by path.
 	origin: Origin,
+
+	// Used to create the settings for each session.
+	config: Config,
+
+	// The active sessions, shared with the admin server.
+	sessions: Sessions,
+
+	// Verifies the token of each session, replaced on reload.
+	auth: Auth,
 }
 
 impl Quic {
 	// Create a QUIC endpoint that can be used for both clients and servers.
-	pub async fn new(config: Config, tls: Tls) -> anyhow::Result<Self> {
+	pub async fn new(config: Config, tls: Tls, metrics: Metrics) -> anyhow::Result<Self> {
 		let mut client_config = tls.client.clone();
 		let mut server_config = tls.server.clone();
 		client_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()];
-		server_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec()];
-
-		// Enable BBR congestion control
-		// TODO validate the implementation
-		let mut transport_config = quinn::TransportConfig::default();
-		transport_config.max_idle_timeout(Some(time::Duration::from_secs(10).try_into().unwrap()));
-		transport_config.keep_alive_interval(Some(time::Duration::from_secs(4))); // TODO make this smarter
-		transport_config.congestion_controller_factory(Arc::new(quinn::congestion::BbrConfig::default()));
-		transport_config.mtu_discovery_config(None); // Disable MTU discovery
+		server_config.alpn_protocols = vec![webtransport_quinn::ALPN.to_vec(), quic::ALPN.to_vec()];
+
+		// A separate client config is used to connect to moqt:// origins, otherwise the ALPN would be ambiguous.
+		let mut moq_client_config = tls.client.clone();
+		moq_client_config.alpn_protocols = vec![quic::ALPN.to_vec()];
+
+		// The congestion control, flow control and timeouts are configurable to compare transports.
+		let transport_config = config.quic.transport().context("invalid QUIC config")?;
+		log::info!("using QUIC config: {:?}", config.quic);
 		let transport_config = Arc::new(transport_config);
 
 		let mut client_config = quinn::ClientConfig::new(Arc::new(client_config));
+		let mut moq_client_config = quinn::ClientConfig::new(Arc::new(moq_client_config));
 		let mut server_config = quinn::ServerConfig::with_crypto(Arc::new(server_config));
 		server_config.transport_config(transport_config.clone());
+		moq_client_config.transport_config(transport_config.clone());
 		client_config.transport_config(transport_config);
 
 		// There's a bit more boilerplate to make a generic endpoint.
@@ -48,7 +60,7 @@ impl Quic {
 			.context("failed to create QUIC endpoint")?;
 		quic.set_default_client_config(client_config);
 
-		let api = config.api.map(|url| {
+		let api = config.api.clone().map(|url| {
 			log::info!("using moq-api: url={}", url);
 			moq_api::Client::new(url)
 		});
@@ -57,20 +69,71 @@ impl Quic {
 			log::info!("advertising origin: url={}", node);
 		}
 
-		let origin = Origin::new(api, config.api_node, quic.clone());
+		let auth = Auth::new(config.authorizer()?);
+
+		for upstream in &config.upstream {
+			log::info!("using upstream relay: url={}", upstream);
+		}
+
+		let id = config.relay_id()?;
+		log::info!("relay ID: {}", id);
+
+		let origin = Origin::new(
+			api,
+			config.api_node.clone(),
+			config.upstream.clone(),
+			id,
+			quic.clone(),
+			moq_client_config,
+			metrics.clone(),
+		);
 		let conns = JoinSet::new();
 
-		Ok(Self { quic, origin, conns })
+		Ok(Self {
+			quic,
+			origin,
+			conns,
+			config,
+			sessions: Sessions::new(metrics),
+			auth,
+		})
+	}
+
+	/// The map of active broadcasts, shared with the admin server.
+	pub fn origin(&self) -> Origin {
+		self.origin.clone()
+	}
+
+	/// The active sessions, shared with the admin server.
+	pub fn sessions(&self) -> Sessions {
+		self.sessions.clone()
+	}
+
+	/// The authorizer for new sessions, which can be replaced on reload.
+	pub fn auth(&self) -> Auth {
+		self.auth.clone()
 	}
 
-	pub async fn serve(mut self) -> anyhow::Result<()> {
+	/// Accept sessions until `shutdown` resolves, then drain them before returning.
+	pub async fn serve(mut self, shutdown: impl Future<Output = ()>) -> anyhow::Result<()> {
 		log::info!("listening on {}", self.quic.local_addr()?);
 
+		tokio::pin!(shutdown);
+
 		loop {
 			tokio::select! {
 				res = self.quic.accept() => {
 					let conn = res.context("failed to accept QUIC connection")?;
-					let mut session = Session::new(self.origin.clone());
+					let mut session = Session::new(
+						self.origin.clone(),
+						self.config.priority_policy(),
+						self.config.write_strategy(),
+						self.config.max_fragment_size(),
+						self.sessions.clone(),
+						self.auth.current(),
+						self.config.quic.clone(),
+					)
+					.with_abr(self.config.abr());
 					self.conns.spawn(async move { session.run(conn).await });
 				},
 				res = self.conns.join_next(), if !self.conns.is_empty() => {
@@ -79,7 +142,57 @@ impl Quic {
 						log::warn!("connection terminated: {:?}", err);
 					}
 				},
+				_ = &mut shutdown => break,
 			}
 		}
+
+		self.drain().await
+	}
+
+	// Stop accepting sessions and ask the existing ones to leave, closing any that remain after the timeout.
+	async fn drain(mut self) -> anyhow::Result<()> {
+		let start = time::Instant::now();
+		let timeout = time::Duration::from_millis(self.config.drain_timeout);
+
+		// Refuse any new connections, so a load balancer sends them elsewhere.
+		self.quic.set_server_config(None);
+
+		let total = self.conns.len();
+		log::info!("draining sessions: count={} timeout={:?}", total, timeout);
+
+		// Each session sends a GOAWAY, and publishers remove their origin from the API.
+		self.sessions.drain();
+
+		let deadline = tokio::time::sleep(timeout);
+		tokio::pin!(deadline);
+
+		while !self.conns.is_empty() {
+			tokio::select! {
+				res = self.conns.join_next() => {
+					let res = res.expect("no tasks").expect("task aborted");
+					if let Err(err) = res {
+						log::warn!("connection terminated: {:?}", err);
+					}
+				},
+				_ = &mut deadline => break,
+			}
+		}
+
+		let remaining = self.conns.len();
+
+		// Close whatever is left and wait for the CONNECTION_CLOSE frames to be sent.
+		self.quic.close(quinn::VarInt::from_u32(503), b"shutting down");
+		self.conns.shutdown().await;
+		self.quic.wait_idle().await;
+
+		log::info!(
+			"drained sessions: count={} finished={} forced={} elapsed={:?}",
+			total,
+			total - remaining,
+			remaining,
+			start.elapsed()
+		);
+
+		Ok(())
 	}
 }
@@ -1,17 +1,76 @@
+use std::{future::Future, sync::Arc};
+
 use anyhow::Context;
+use clap::ValueEnum;
 
-use moq_transport::{session::Request, setup::Role, MoqError};
+use moq_transport::{
+	cache::track,
+	session::{PriorityPolicy, Request, SessionError, WriteStrategy},
+	setup::Role,
+	transport::quic,
+	MoqError,
+};
 
-use crate::Origin;
+use crate::{
+	Abr, AbrAlgorithm, AbrConfig, AuthError, Authorizer, Grant, Origin, Publisher, QuicConfig, Router, Sessions, Via,
+	ABR_PARAM, TOKEN_PARAM, VIA_PARAM,
+};
 
 #[derive(Clone)]
 pub struct Session {
 	origin: Origin,
+
+	// Decides the stream priority of the segments we serve.
+	priority: Arc<dyn PriorityPolicy>,
+
+	// Decides how the segments we serve are written to each stream.
+	write: WriteStrategy,
+
+	// The maximum size of each OBJECT received from a publisher.
+	max_fragment_size: Option<usize>,
+
+	// The active sessions, used by the admin server.
+	sessions: Sessions,
+
+	// Verifies the token in the connection URL.
+	auth: Arc<dyn Authorizer>,
+
+	// The QUIC transport settings, logged for each session.
+	quic: QuicConfig,
+
+	// Switches subscribers between renditions, unless the algorithm is off.
+	abr: AbrConfig,
 }
 
 impl Session {
-	pub fn new(origin: Origin) -> Self {
-		Self { origin }
+	pub fn new(
+		origin: Origin,
+		priority: Arc<dyn PriorityPolicy>,
+		write: WriteStrategy,
+		max_fragment_size: Option<usize>,
+		sessions: Sessions,
+		auth: Arc<dyn Authorizer>,
+		quic: QuicConfig,
+	) -> Self {
+		Self {
+			origin,
+			priority,
+			write,
+			max_fragment_size,
+			sessions,
+			auth,
+			quic,
+			abr: AbrConfig {
+				algorithm: AbrAlgorithm::Off,
+				log: None,
+			},
+		}
+	}
+
+	/// Switch subscribers between renditions using the provided settings.
+	pub fn with_abr(mut self, abr: AbrConfig) -> Self {
+		self.abr = abr;
+		self
 	}
 
 	pub async fn run(&mut self, conn: quinn::Connecting) -> anyhow::Result<()> {
@@ -27,45 +86,112 @@ impl Session {
 		);
 		let id = conn.stable_id();
 
-		// Wait for the CONNECT request.
-		let request = webtransport_quinn::accept(conn)
-			.await
-			.context("failed to receive WebTransport request")?;
+		log::info!(
+			"QUIC transport: id={} max_datagram_size={:?} config={:?}",
+			id,
+			conn.max_datagram_size(),
+			self.quic
+		);
 
-		// Strip any leading and trailing slashes to get the broadcast name.
-		let path = request.url().path().trim_matches('/').to_string();
+		// Keep a handle so the admin server can inspect or close the connection.
+		let handle = conn.clone();
 
-		log::debug!("received WebTransport CONNECT: id={} path={}", id, path);
+		let alpn = conn
+			.handshake_data()
+			.and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+			.and_then(|data| data.protocol);
 
-		// Accept the CONNECT request.
-		let session = request
-			.ok()
-			.await
-			.context("failed to respond to WebTransport request")?;
+		let (request, path, query) = match alpn.as_deref() {
+			Some(quic::ALPN) => {
+				// Perform the MoQ handshake directly over QUIC.
+				let request = moq_transport::session::Server::accept(conn)
+					.await
+					.context("failed to accept handshake")?;
 
-		// Perform the MoQ handshake.
-		let request = moq_transport::session::Server::accept(session)
-			.await
-			.context("failed to accept handshake")?;
+				// The path is sent in the SETUP message instead of the CONNECT request, including any query.
+				let (path, query) = split_path(request.path().unwrap_or_default());
+				let path = path.trim_matches('/').to_string();
+
+				(request, path, query)
+			}
+			_ => {
+				// Wait for the CONNECT request.
+				let request = webtransport_quinn::accept(conn)
+					.await
+					.context("failed to receive WebTransport request")?;
+
+				// Strip any leading and trailing slashes to get the broadcast name.
+				let path = request.url().path().trim_matches('/').to_string();
+				let query = request.url().query().map(str::to_string);
+
+				log::debug!("received WebTransport CONNECT: id={} path={}", id, path);
+
+				// Accept the CONNECT request.
+				let session = request
+					.ok()
+					.await
+					.context("failed to respond to WebTransport request")?;
+
+				// Perform the MoQ handshake.
+				let request = moq_transport::session::Server::accept(session)
+					.await
+					.context("failed to accept handshake")?;
+
+				// Other relays also send the query in the SETUP message, since their WebTransport client drops it.
+				let query = query.or_else(|| split_path(request.path().unwrap_or_default()).1);
+
+				(request, path, query)
+			}
+		};
 
 		log::debug!("received MoQ SETUP: id={} role={:?}", id, request.role());
 
 		let role = request.role();
 
+		// Reject requests forwarded in a loop by other relays, before fetching the broadcast again.
+		let via = param(query.as_deref(), VIA_PARAM)
+			.map(|via| Via::parse(&via))
+			.unwrap_or_default();
+
+		if let Err(err) = via.check(self.origin.id()) {
+			request.reject(err.code());
+			return Err(err.into());
+		}
+
+		let token = param(query.as_deref(), TOKEN_PARAM);
+
+		// Check the token before touching the origin, so a client can't publish or subscribe without permission.
+		let grant = match self.authorize(token.as_deref(), &path, role) {
+			Ok(grant) => grant,
+			Err(err) => {
+				request.reject(err.code());
+				return Err(err.into());
+			}
+		};
+
+		// A subscriber can choose another ABR algorithm, such as to compare them.
+		if let Some(algorithm) = param(query.as_deref(), ABR_PARAM) {
+			match AbrAlgorithm::from_str(&algorithm, true) {
+				Ok(algorithm) => self.abr.algorithm = algorithm,
+				Err(_) => log::warn!("ignoring unknown ABR algorithm: id={} abr={}", id, algorithm),
+			}
+		}
+
 		match role {
 			Role::Publisher => {
-				if let Err(err) = self.serve_publisher(id, request, &path).await {
+				if let Err(err) = self.serve_publisher(id, request, &path, handle).await {
 					log::warn!("error serving publisher: id={} path={} err={:#?}", id, path, err);
 				}
 			}
 			Role::Subscriber => {
-				if let Err(err) = self.serve_subscriber(id, request, &path).await {
+				if let Err(err) = self.serve_subscriber(id, request, &path, handle, grant, via).await {
 					log::warn!("error serving subscriber: id={} path={} err={:#?}", id, path, err);
 				}
 			}
 			Role::Both => {
-				log::warn!("role both not supported: id={}", id);
-				request.reject(300);
+				if let Err(err) = self.serve_both(id, request, &path, handle, grant, via).await {
+					log::warn!("error serving pubsub: id={} path={} err={:#?}", id, path, err);
+				}
 			}
 		};
 
@@ -74,7 +200,19 @@ impl Session {
 		Ok(())
 	}
 
-	async fn serve_publisher(&mut self, id: usize, request: Request, path: &str) -> anyhow::Result<()> {
+	fn authorize(&self, token: Option<&str>, path: &str, role: Role) -> Result<Grant, AuthError> {
+		let grant = self.auth.verify(token)?;
+		grant.check(path, role)?;
+		Ok(grant)
+	}
+
+	async fn serve_publisher(
+		&mut self,
+		id: usize,
+		request: Request,
+		path: &str,
+		conn: quinn::Connection,
+	) -> anyhow::Result<()> {
 		log::info!("serving publisher: id={}, path={}", id, path);
 
 		let mut origin = match self.origin.publish(path).await {
@@ -85,27 +223,233 @@ impl Session {
 			}
 		};
 
-		let session = request.subscriber(origin.broadcast.clone()).await?;
+		let mut session = request.subscriber(origin.broadcast.clone()).await?;
+		if let Some(size) = self.max_fragment_size {
+			session = session.with_max_fragment_size(size);
+		}
 
-		tokio::select! {
-			_ = session.run() => origin.close().await?,
-			_ = origin.run() => (), // TODO send error to session
-		};
+		let stats = session.clone();
+		let tracks = session.clone();
+
+		let handle = session.clone();
+		let _registered = self
+			.sessions
+			.register(id, Role::Publisher, path, conn.clone(), move || handle.stats());
+
+		let goaway = session.clone();
+		let run = session.run();
+		self.serve_origin(
+			id,
+			path,
+			"publisher",
+			&mut origin,
+			conn,
+			run,
+			|| tracks.detach(),
+			|| goaway.go_away(""),
+		)
+		.await?;
+
+		log::info!("session stats: id={} {}", id, stats.stats());
 
 		Ok(())
 	}
 
-	async fn serve_subscriber(&mut self, id: usize, request: Request, path: &str) -> anyhow::Result<()> {
+	async fn serve_subscriber(
+		&mut self,
+		id: usize,
+		request: Request,
+		path: &str,
+		conn: quinn::Connection,
+		grant: Grant,
+		via: Via,
+	) -> anyhow::Result<()> {
 		log::info!("serving subscriber: id={} path={}", id, path);
 
-		let subscriber = self.origin.subscribe(path);
+		// The empty namespace refers to the path, while other namespaces are routed through the origin.
+		// Newer drafts always include a namespace, so this lets them subscribe to any broadcast.
+		let subscriber = self.origin.subscribe(path, &via);
+		let router = Arc::new(Router::new(self.origin.clone(), grant, via));
+
+		let mut session = request
+			.publisher(subscriber.broadcast.clone())
+			.await?
+			.with_router(router)
+			.with_priority(self.priority.clone())
+			.with_write_strategy(self.write.clone());
+		if let Some(abr) = Abr::new(id, &self.abr) {
+			session = session.with_abr(Arc::new(abr));
+		}
+
+		let stats = session.clone();
+
+		let handle = session.clone();
+		let _registered = self
+			.sessions
+			.register(id, Role::Subscriber, path, conn, move || handle.stats());
 
-		let session = request.publisher(subscriber.broadcast.clone()).await?;
-		session.run().await?;
+		let goaway = session.clone();
+		let run = session.run();
+		tokio::pin!(run);
+
+		// Keep serving after the GOAWAY until the client leaves, so it can switch to another relay without a gap.
+		let res = tokio::select! {
+			res = &mut run => res,
+			_ = self.sessions.draining() => {
+				log::info!("draining subscriber: id={} path={}", id, path);
+				goaway.go_away("").await.ok();
+				run.await
+			}
+		};
+		log::info!("session stats: id={} {}", id, stats.stats());
+		res?;
+
+		// Make sure this doesn't get dropped too early
+		drop(subscriber);
+
+		Ok(())
+	}
+
+	async fn serve_both(
+		&mut self,
+		id: usize,
+		request: Request,
+		path: &str,
+		conn: quinn::Connection,
+		grant: Grant,
+		via: Via,
+	) -> anyhow::Result<()> {
+		log::info!("serving pubsub: id={} path={}", id, path);
+
+		// Publish the client's broadcast under the path, just like a publisher.
+		let mut origin = match self.origin.publish(path).await {
+			Ok(origin) => origin,
+			Err(err) => {
+				request.reject(err.code());
+				return Err(err.into());
+			}
+		};
+
+		// The empty namespace refers to the path, while other namespaces are routed through the origin.
+		let subscriber = self.origin.subscribe(path, &via);
+		let router = Arc::new(Router::new(self.origin.clone(), grant, via));
+
+		let mut session = request
+			.pubsub(subscriber.broadcast.clone(), origin.broadcast.clone())
+			.await?
+			.with_router(router)
+			.with_priority(self.priority.clone())
+			.with_write_strategy(self.write.clone());
+		if let Some(size) = self.max_fragment_size {
+			session = session.with_max_fragment_size(size);
+		}
+		if let Some(abr) = Abr::new(id, &self.abr) {
+			session = session.with_abr(Arc::new(abr));
+		}
+
+		let stats = session.clone();
+		let tracks = session.clone();
+
+		let handle = session.clone();
+		let _registered = self
+			.sessions
+			.register(id, Role::Both, path, conn.clone(), move || handle.stats());
+
+		let goaway = session.clone();
+		let run = session.run();
+		self.serve_origin(
+			id,
+			path,
+			"pubsub",
+			&mut origin,
+			conn,
+			run,
+			|| tracks.detach(),
+			|| goaway.go_away(""),
+		)
+		.await?;
+
+		log::info!("session stats: id={} {}", id, stats.stats());
 
 		// Make sure this doesn't get dropped too early
 		drop(subscriber);
 
 		Ok(())
 	}
+
+	// Serve a session that publishes a broadcast until it ends, is replaced by a new session, or the relay drains.
+	#[allow(clippy::too_many_arguments)]
+	async fn serve_origin<R, G>(
+		&self,
+		id: usize,
+		path: &str,
+		label: &str,
+		origin: &mut Publisher,
+		conn: quinn::Connection,
+		run: R,
+		detach: impl Fn() -> Vec<track::Publisher>,
+		go_away: impl Fn() -> G,
+	) -> anyhow::Result<()>
+	where
+		R: Future,
+		G: Future<Output = Result<(), SessionError>>,
+	{
+		let closed = origin.closed();
+		let takeover = origin.takeover();
+		let draining = self.sessions.draining();
+		tokio::pin!(run, closed, takeover, draining);
+
+		let mut drained = false;
+
+		loop {
+			tokio::select! {
+				// Keep the subscribers attached, so the publisher can reconnect, unless the relay is shutting down.
+				_ = &mut run => {
+					if !drained {
+						origin.unpublish(detach()).await?;
+					}
+					break;
+				}
+				_ = origin.run(), if !drained => break, // TODO send error to session
+				_ = &mut closed => {
+					origin.close().await?;
+					break;
+				}
+				_ = &mut takeover => {
+					log::info!("{} replaced by a new session: id={} path={}", label, id, path);
+					origin.handover(detach())?;
+					conn.close(quinn::VarInt::from_u32(409), b"replaced");
+					break;
+				}
+				// Stop advertising the broadcast right away, but keep serving it until the client leaves.
+				_ = &mut draining, if !drained => {
+					log::info!("draining {}: id={} path={}", label, id, path);
+					drained = true;
+
+					if let Err(err) = origin.close().await {
+						log::warn!("failed to remove origin: id={} path={} err={:?}", id, path, err);
+					}
+
+					go_away().await.ok();
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+// Split the path sent in the SETUP message into the path and query.
+fn split_path(path: &str) -> (&str, Option<String>) {
+	match path.split_once('?') {
+		Some((path, query)) => (path, Some(query.to_string())),
+		None => (path, None),
+	}
+}
+
+// Find the parameter in the URL query, if any.
+fn param(query: Option<&str>, name: &str) -> Option<String> {
+	url::form_urlencoded::parse(query?.as_bytes())
+		.find(|(key, _)| key == name)
+		.map(|(_, value)| value.into_owned())
 }
@@ -0,0 +1,139 @@
+use std::{
+	collections::HashMap,
+	future::Future,
+	net,
+	sync::{Arc, Mutex},
+};
+
+use moq_transport::{session::Stats, setup::Role};
+use tokio::sync::watch;
+
+use crate::Metrics;
+
+/// The active sessions, used by the admin server to list and kick them.
+#[derive(Clone)]
+pub struct Sessions {
+	state: Arc<Mutex<HashMap<usize, Entry>>>,
+
+	// The final stats of each session are added to the totals.
+	metrics: Metrics,
+
+	// Set once the relay is shutting down, so each session asks its client to leave.
+	draining: Arc<watch::Sender<bool>>,
+}
+
+struct Entry {
+	role: Role,
+	path: String,
+	conn: quinn::Connection,
+	stats: Box<dyn Fn() -> Stats + Send>,
+}
+
+/// A snapshot of an active session.
+pub struct SessionInfo {
+	pub id: usize,
+	pub role: Role,
+	pub path: String,
+	pub remote: net::SocketAddr,
+	pub stats: Stats,
+}
+
+impl Sessions {
+	pub fn new(metrics: Metrics) -> Self {
+		Self {
+			state: Default::default(),
+			metrics,
+			draining: Arc::new(watch::Sender::new(false)),
+		}
+	}
+
+	/// Register a session until the returned guard is dropped, using the closure to snapshot its stats.
+	pub fn register<F>(&self, id: usize, role: Role, path: &str, conn: quinn::Connection, stats: F) -> SessionGuard
+	where
+		F: Fn() -> Stats + Send + 'static,
+	{
+		let entry = Entry {
+			role,
+			path: path.to_string(),
+			conn,
+			stats: Box::new(stats),
+		};
+
+		self.state.lock().unwrap().insert(id, entry);
+
+		SessionGuard {
+			sessions: self.clone(),
+			id,
+		}
+	}
+
+	/// Snapshot the active sessions, sorted by ID.
+	pub fn list(&self) -> Vec<SessionInfo> {
+		let state = self.state.lock().unwrap();
+
+		let mut sessions: Vec<_> = state
+			.iter()
+			.map(|(id, entry)| SessionInfo {
+				id: *id,
+				role: entry.role,
+				path: entry.path.clone(),
+				remote: entry.conn.remote_address(),
+				stats: (entry.stats)(),
+			})
+			.collect();
+
+		sessions.sort_by_key(|session| session.id);
+		sessions
+	}
+
+	/// Close the connection for the session, returning false if it doesn't exist.
+	pub fn kick(&self, id: usize) -> bool {
+		let state = self.state.lock().unwrap();
+
+		match state.get(&id) {
+			Some(entry) => {
+				entry.conn.close(quinn::VarInt::from_u32(403), b"kicked");
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Ask every session to leave, including those that register afterwards.
+	pub fn drain(&self) {
+		self.draining.send_replace(true);
+	}
+
+	/// Resolves once [Sessions::drain] is called.
+	// NOTE: The future doesn't borrow the sessions, so it can be polled alongside a session.
+	pub fn draining(&self) -> impl Future<Output = ()> {
+		let mut draining = self.draining.subscribe();
+		async move {
+			draining.wait_for(|draining| *draining).await.ok();
+		}
+	}
+}
+
+/// Removes a session from [Sessions] when dropped, adding its final stats to the [Metrics].
+pub struct SessionGuard {
+	sessions: Sessions,
+	id: usize,
+}
+
+impl Drop for SessionGuard {
+	fn drop(&mut self) {
+		let entry = self.sessions.state.lock().unwrap().remove(&self.id);
+		if let Some(entry) = entry {
+			self.sessions.metrics.finish(&(entry.stats)());
+		}
+	}
+}
+
+/// The role of a session's client, as used in metric labels and the admin API.
+pub fn role_label(role: Role) -> &'static str {
+	match role {
+		Role::Publisher => "publisher",
+		Role::Subscriber => "subscriber",
+		Role::Both => "both",
+	}
+}
@@ -5,7 +5,7 @@ use rustls::sign::CertifiedKey;
 use rustls::{Certificate, PrivateKey, RootCertStore};
 use std::io::{self, Cursor, Read};
 use std::path;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::{fs, time};
 use webpki::{DnsNameRef, EndEntityCert};
 
@@ -15,21 +15,14 @@ use crate::Config;
 pub struct Tls {
 	pub server: rustls::ServerConfig,
 	pub client: rustls::ClientConfig,
-	pub fingerprints: Vec<String>,
+
+	// The certificates used by the server, which can be replaced on reload.
+	certs: Arc<ReloadCerts>,
 }
 
 impl Tls {
 	pub fn load(config: &Config) -> anyhow::Result<Self> {
-		let mut serve = ServeCerts::default();
-
-		// Load the certificate and key files based on their index.
-		anyhow::ensure!(
-			config.tls_cert.len() == config.tls_key.len(),
-			"--tls-cert and --tls-key counts differ"
-		);
-		for (chain, key) in config.tls_cert.iter().zip(config.tls_key.iter()) {
-			serve.load(chain, key)?;
-		}
+		let serve = ServeCerts::new(config)?;
 
 		// Create a list of acceptable root certificates.
 		let mut roots = RootCertStore::empty();
@@ -64,21 +57,43 @@ impl Tls {
 			client.dangerous().set_certificate_verifier(Arc::new(noop));
 		}
 
-		let fingerprints = serve.fingerprints();
+		let certs = Arc::new(ReloadCerts {
+			current: RwLock::new(serve),
+		});
 
 		// Create the TLS configuration we'll use as a server (relay <- browser)
 		let server = rustls::ServerConfig::builder()
 			.with_safe_defaults()
 			.with_no_client_auth()
-			.with_cert_resolver(Arc::new(serve));
+			.with_cert_resolver(certs.clone());
 
-		let certs = Self {
-			server,
-			client,
-			fingerprints,
-		};
+		let tls = Self { server, client, certs };
+
+		Ok(tls)
+	}
+
+	/// Load the certificates again, used for new connections while existing connections are unaffected.
+	pub fn reload(&self, config: &Config) -> anyhow::Result<()> {
+		let serve = ServeCerts::new(config)?;
+		*self.certs.current.write().unwrap() = serve;
+
+		Ok(())
+	}
+
+	// Return the SHA256 fingerprint of our certificates.
+	pub fn fingerprints(&self) -> Vec<String> {
+		self.certs.current.read().unwrap().fingerprints()
+	}
+}
 
-		Ok(certs)
+// Resolves the certificate using the latest certificates loaded.
+struct ReloadCerts {
+	current: RwLock<ServeCerts>,
+}
+
+impl ResolvesServerCert for ReloadCerts {
+	fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+		self.current.read().unwrap().resolve(client_hello)
 	}
 }
 
@@ -88,6 +103,27 @@ struct ServeCerts {
 }
 
 impl ServeCerts {
+	// Load the certificate and key files based on their index.
+	pub fn new(config: &Config) -> anyhow::Result<Self> {
+		let mut serve = Self::default();
+
+		anyhow::ensure!(
+			config.tls_cert.len() == config.tls_key.len(),
+			"--tls-cert and --tls-key counts differ"
+		);
+		for (chain, key) in config.tls_cert.iter().zip(config.tls_key.iter()) {
+			serve.load(chain, key)?;
+		}
+
+		// Checked here, including on reload, so /fingerprint can assume there's a certificate.
+		anyhow::ensure!(
+			!serve.list.is_empty(),
+			"missing certificate: use --tls-cert and --tls-key"
+		);
+
+		Ok(serve)
+	}
+
 	// Load a certificate and cooresponding key from a file
 	pub fn load(&mut self, chain: &path::PathBuf, key: &path::PathBuf) -> anyhow::Result<()> {
 		// Read the PEM certificate chain
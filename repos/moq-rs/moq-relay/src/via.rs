@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::RelayError;
+
+/// The query parameter listing the relays a request was forwarded through, used to detect loops.
+pub const VIA_PARAM: &str = "via";
+
+/// The most relays a request can be forwarded through, even without a loop.
+pub const MAX_HOPS: usize = 8;
+
+/// The IDs of the relays a request was forwarded through, oldest first.
+#[derive(Clone, Debug, Default)]
+pub struct Via {
+	relays: Vec<String>,
+}
+
+impl Via {
+	/// Parse the comma separated IDs from the query parameter.
+	pub fn parse(value: &str) -> Self {
+		let relays = value
+			.split(',')
+			.filter(|relay| !relay.is_empty())
+			.map(str::to_string)
+			.collect();
+
+		Self { relays }
+	}
+
+	/// Return an error if the request already passed through this relay, or through too many relays.
+	pub fn check(&self, relay: &str) -> Result<(), RelayError> {
+		if self.relays.iter().any(|id| id == relay) {
+			return Err(RelayError::Loop(self.to_string()));
+		}
+
+		if self.relays.len() >= MAX_HOPS {
+			return Err(RelayError::TooManyHops(self.to_string()));
+		}
+
+		Ok(())
+	}
+
+	/// Add this relay before forwarding the request upstream.
+	pub fn with(&self, relay: &str) -> Self {
+		let mut relays = self.relays.clone();
+		relays.push(relay.to_string());
+
+		Self { relays }
+	}
+}
+
+impl fmt::Display for Via {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.relays.join(","))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse() {
+		assert_eq!(Via::parse("").to_string(), "");
+		assert_eq!(Via::parse("a").to_string(), "a");
+		assert_eq!(Via::parse("a,b").to_string(), "a,b");
+
+		// Empty IDs are skipped.
+		assert_eq!(Via::parse(",a,,b,").to_string(), "a,b");
+	}
+
+	#[test]
+	fn check() {
+		let via = Via::parse("a,b");
+		assert!(via.check("c").is_ok());
+		assert!(matches!(via.check("a"), Err(RelayError::Loop(path)) if path == "a,b"));
+		assert!(matches!(via.check("b"), Err(RelayError::Loop(_))));
+
+		// IDs must match exactly.
+		assert!(via.check("ab").is_ok());
+
+		let ids: Vec<String> = (0..MAX_HOPS).map(|i| i.to_string()).collect();
+		let via = Via::parse(&ids[..MAX_HOPS - 1].join(","));
+		assert!(via.check("x").is_ok());
+
+		let via = Via::parse(&ids.join(","));
+		assert!(matches!(via.check("x"), Err(RelayError::TooManyHops(_))));
+	}
+
+	#[test]
+	fn with() {
+		let via = Via::default();
+		assert!(via.check("a").is_ok());
+
+		let via = via.with("a");
+		assert_eq!(via.to_string(), "a");
+		assert!(via.check("a").is_err());
+
+		// The original is unchanged.
+		let next = via.with("b");
+		assert_eq!(next.to_string(), "a,b");
+		assert_eq!(via.to_string(), "a");
+
+		// The value round trips through the query parameter.
+		assert_eq!(Via::parse(&next.to_string()).to_string(), "a,b");
+	}
+}
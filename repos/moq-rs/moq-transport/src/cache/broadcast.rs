@@ -8,6 +8,7 @@
 //! If the track already exists, it will be returned.
 //! If the track doesn't exist, it will be sent to [Unknown] to be handled.
 //! A [Subscriber] can be cloned to create multiple subscriptions.
+//! A [Subscriber] can also request a track as a replacement for another, which is passed along with the request.
 //!
 //! The broadcast is automatically closed with [CacheError::Closed] when [Publisher] is dropped, or all [Subscriber]s are dropped.
 use std::{
@@ -40,7 +41,7 @@ pub struct Info {
 #[derive(Debug)]
 struct State {
 	tracks: HashMap<String, track::Subscriber>,
-	requested: VecDeque<track::Publisher>,
+	requested: VecDeque<Request>,
 	closed: Result<(), CacheError>,
 }
 
@@ -61,7 +62,7 @@ impl State {
 		Ok(())
 	}
 
-	pub fn request(&mut self, name: &str) -> Result<track::Subscriber, CacheError> {
+	pub fn request(&mut self, name: &str, switch: Option<&str>) -> Result<track::Subscriber, CacheError> {
 		self.closed.clone()?;
 
 		// Create a new track.
@@ -71,7 +72,10 @@ impl State {
 		self.tracks.insert(name.to_string(), subscriber.clone());
 
 		// Send the track to the Publisher to handle.
-		self.requested.push_back(publisher);
+		self.requested.push_back(Request {
+			track: publisher,
+			switch: switch.map(str::to_string),
+		});
 
 		Ok(subscriber)
 	}
@@ -86,13 +90,19 @@ impl State {
 		Ok(false)
 	}
 
-	pub fn next(&mut self) -> track::Publisher {
+	pub fn next(&mut self) -> Request {
 		// We panic instead of erroring to avoid a nasty wakeup loop if you don't call has_next first.
 		self.requested.pop_front().expect("no entry in queue")
 	}
 
 	pub fn close(&mut self, err: CacheError) -> Result<(), CacheError> {
 		self.closed.clone()?;
+
+		// Close any tracks that were requested but never fulfilled, so their subscribers don't wait forever.
+		for request in self.requested.drain(..) {
+			request.track.close(err.clone()).ok();
+		}
+
 		self.closed = Err(err);
 		Ok(())
 	}
@@ -108,6 +118,16 @@ impl Default for State {
 	}
 }
 
+/// A track requested by a [Subscriber] that doesn't exist yet.
+#[derive(Debug)]
+pub struct Request {
+	/// The track to fulfill.
+	pub track: track::Publisher,
+
+	/// The name of the track this one replaces, if it was requested with [Subscriber::switch_track].
+	pub switch: Option<String>,
+}
+
 /// Publish new tracks for a broadcast by name.
 // TODO remove Clone
 #[derive(Clone)]
@@ -137,6 +157,11 @@ impl Publisher {
 
 	/// Block until the next track requested by a subscriber.
 	pub async fn next_track(&mut self) -> Result<track::Publisher, CacheError> {
+		Ok(self.next_request().await?.track)
+	}
+
+	/// Block until the next track requested by a subscriber, including the track it replaces.
+	pub async fn next_request(&mut self) -> Result<Request, CacheError> {
 		loop {
 			let notify = {
 				let state = self.state.lock();
@@ -151,6 +176,38 @@ impl Publisher {
 		}
 	}
 
+	/// Queue a track to be fulfilled again, such as when the session fulfilling it failed.
+	///
+	/// It's returned by [Publisher::next_request] like any other request, keeping the existing subscribers of the track.
+	pub fn requeue(&mut self, track: track::Publisher) -> Result<(), CacheError> {
+		let mut state = self.state.lock_mut();
+		state.closed.clone()?;
+		state.requested.push_back(Request { track, switch: None });
+
+		Ok(())
+	}
+
+	/// Check if the broadcast is closed, either because every [Subscriber] was dropped or [Publisher::close] was called.
+	pub fn is_closed(&self) -> Option<CacheError> {
+		self.state.lock().closed.as_ref().err().cloned()
+	}
+
+	/// Wait until the broadcast is closed, either because every [Subscriber] was dropped or [Publisher::close] was called.
+	pub async fn closed(&self) -> CacheError {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+				if let Some(err) = state.closed.as_ref().err() {
+					return err.clone();
+				}
+
+				state.changed()
+			};
+
+			notify.await;
+		}
+	}
+
 	/// Close the broadcast with an error.
 	pub fn close(self, err: CacheError) -> Result<(), CacheError> {
 		self.state.lock_mut().close(err)
@@ -200,7 +257,24 @@ impl Subscriber {
 		}
 
 		// Request a new track if it does not exist.
-		state.into_mut().request(name)
+		state.into_mut().request(name, None)
+	}
+
+	/// Get a track from the broadcast by name, as a replacement for the track named `from`.
+	///
+	/// This is the same as [Subscriber::get_track], except the publisher is told about the switch if the track is requested.
+	pub fn switch_track(&self, from: &str, name: &str) -> Result<track::Subscriber, CacheError> {
+		let state = self.state.lock();
+		if let Some(track) = state.get(name)? {
+			return Ok(track);
+		}
+
+		state.into_mut().request(name, Some(from))
+	}
+
+	/// Return the tracks currently in the broadcast, including any that were requested but not fulfilled yet.
+	pub fn tracks(&self) -> Vec<track::Subscriber> {
+		self.state.lock().tracks.values().cloned().collect()
 	}
 
 	/// Check if the broadcast is closed, either because the publisher was dropped or called [Publisher::close].
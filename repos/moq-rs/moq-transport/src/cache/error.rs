@@ -24,6 +24,22 @@ pub enum CacheError {
 	/// A resource already exists with that ID.
 	#[error("duplicate")]
 	Duplicate,
+
+	/// The deadline passed before the resource was delivered.
+	#[error("expired")]
+	Expired,
+
+	/// The fragment didn't match the declared size.
+	#[error("wrong size")]
+	WrongSize,
+
+	/// The fragment, or another resource, exceeded the memory limit.
+	#[error("too large")]
+	TooLarge,
+
+	/// The subscriber isn't allowed to access the resource.
+	#[error("unauthorized")]
+	Unauthorized,
 }
 
 impl MoqError for CacheError {
@@ -35,6 +51,10 @@ impl MoqError for CacheError {
 			Self::Stop => 206,
 			Self::NotFound => 404,
 			Self::Duplicate => 409,
+			Self::Expired => 410,
+			Self::WrongSize => 400,
+			Self::TooLarge => 413,
+			Self::Unauthorized => 403,
 		}
 	}
 
@@ -46,6 +66,10 @@ impl MoqError for CacheError {
 			Self::Stop => "stop".to_owned(),
 			Self::NotFound => "not found".to_owned(),
 			Self::Duplicate => "duplicate".to_owned(),
+			Self::Expired => "expired".to_owned(),
+			Self::WrongSize => "wrong size".to_owned(),
+			Self::TooLarge => "too large".to_owned(),
+			Self::Unauthorized => "unauthorized".to_owned(),
 		}
 	}
 }
@@ -8,6 +8,7 @@
 //! You can clone the [Subscriber] and each will read a copy of of all future chunks. (fanout)
 //!
 //! The fragment is closed with [CacheError::Closed] when all publishers or subscribers are dropped.
+//! If the fragment has a declared size, it's closed with [CacheError::WrongSize] when the chunks don't add up to it.
 use core::fmt;
 use std::{ops::Deref, sync::Arc};
 
@@ -35,7 +36,6 @@ pub struct Info {
 	pub sequence: VarInt,
 
 	// The size of the fragment, optionally None if this is the last fragment in a segment.
-	// TODO enforce this size.
 	pub size: Option<usize>,
 }
 
@@ -43,6 +43,12 @@ struct State {
 	// The data that has been received thus far.
 	chunks: Vec<Bytes>,
 
+	// The total size of the chunks.
+	size: usize,
+
+	// The maximum size of the chunks, used to bound fragments without a declared size.
+	limit: Option<usize>,
+
 	// Set when the publisher is dropped.
 	closed: Result<(), CacheError>,
 }
@@ -59,6 +65,8 @@ impl Default for State {
 	fn default() -> Self {
 		Self {
 			chunks: Vec::new(),
+			size: 0,
+			limit: None,
 			closed: Ok(()),
 		}
 	}
@@ -67,7 +75,10 @@ impl Default for State {
 impl fmt::Debug for State {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		// We don't want to print out the contents, so summarize.
-		f.debug_struct("State").field("closed", &self.closed).finish()
+		f.debug_struct("State")
+			.field("size", &self.size)
+			.field("closed", &self.closed)
+			.finish()
 	}
 }
 
@@ -85,14 +96,37 @@ pub struct Publisher {
 
 impl Publisher {
 	fn new(state: Watch<State>, info: Arc<Info>) -> Self {
-		let _dropped = Arc::new(Dropped::new(state.clone()));
+		let _dropped = Arc::new(Dropped::new(state.clone(), info.size));
 		Self { state, info, _dropped }
 	}
 
+	/// Limit the total size of the chunks, closing the fragment with [CacheError::TooLarge] if exceeded.
+	pub fn with_limit(self, limit: usize) -> Self {
+		self.state.lock_mut().limit = Some(limit);
+		self
+	}
+
 	/// Write a new chunk of bytes.
+	///
+	/// The fragment is closed with an error if the chunk would exceed the declared size or the limit.
 	pub fn chunk(&mut self, chunk: Bytes) -> Result<(), CacheError> {
 		let mut state = self.state.lock_mut();
 		state.closed.clone()?;
+
+		let size = state.size + chunk.len();
+
+		let err = match (self.info.size, state.limit) {
+			(Some(declared), _) if size > declared => Some(CacheError::WrongSize),
+			(_, Some(limit)) if size > limit => Some(CacheError::TooLarge),
+			_ => None,
+		};
+
+		if let Some(err) = err {
+			state.close(err.clone())?;
+			return Err(err);
+		}
+
+		state.size = size;
 		state.chunks.push(chunk);
 		Ok(())
 	}
@@ -139,7 +173,7 @@ pub struct Subscriber {
 
 impl Subscriber {
 	fn new(state: Watch<State>, info: Arc<Info>) -> Self {
-		let _dropped = Arc::new(Dropped::new(state.clone()));
+		let _dropped = Arc::new(Dropped::new(state.clone(), None));
 
 		Self {
 			state,
@@ -149,6 +183,11 @@ impl Subscriber {
 		}
 	}
 
+	/// The number of bytes received so far.
+	pub fn size(&self) -> usize {
+		self.state.lock().size
+	}
+
 	/// Block until the next chunk of bytes is available.
 	pub async fn chunk(&mut self) -> Result<Option<Bytes>, CacheError> {
 		loop {
@@ -193,16 +232,70 @@ impl fmt::Debug for Subscriber {
 struct Dropped {
 	// Modify the segment state.
 	state: Watch<State>,
+
+	// The declared size, checked when the publishers are dropped.
+	size: Option<usize>,
 }
 
 impl Dropped {
-	fn new(state: Watch<State>) -> Self {
-		Self { state }
+	fn new(state: Watch<State>, size: Option<usize>) -> Self {
+		Self { state, size }
 	}
 }
 
 impl Drop for Dropped {
 	fn drop(&mut self) {
-		self.state.lock_mut().close(CacheError::Closed).ok();
+		let mut state = self.state.lock_mut();
+
+		// A short fragment is an error, otherwise subscribers would receive truncated data.
+		let err = match self.size {
+			Some(size) if state.size < size => CacheError::WrongSize,
+			_ => CacheError::Closed,
+		};
+
+		state.close(err).ok();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{cache::segment, MoqError};
+
+	#[tokio::test]
+	async fn fragment_size() {
+		let (mut publisher, mut subscriber) = segment::new(segment::Info {
+			sequence: VarInt::ZERO,
+			priority: 0,
+			expires: None,
+		});
+
+		// A chunk can't overflow the declared size.
+		let mut fragment = publisher.fragment(VarInt::ZERO, 4).unwrap();
+		fragment.chunk(Bytes::from_static(b"abc")).unwrap();
+		assert!(matches!(
+			fragment.chunk(Bytes::from_static(b"de")),
+			Err(CacheError::WrongSize)
+		));
+
+		// A fragment can't end before the declared size.
+		let mut fragment = publisher.fragment(VarInt::from_u32(1), 4).unwrap();
+		fragment.chunk(Bytes::from_static(b"abc")).unwrap();
+		drop(fragment);
+
+		// An unbounded fragment can't exceed the limit.
+		let mut fragment = publisher.final_fragment(VarInt::from_u32(2)).unwrap().with_limit(4);
+		fragment.chunk(Bytes::from_static(b"abcd")).unwrap();
+		assert!(matches!(
+			fragment.chunk(Bytes::from_static(b"e")),
+			Err(CacheError::TooLarge)
+		));
+
+		for expected in [CacheError::WrongSize, CacheError::WrongSize, CacheError::TooLarge] {
+			// The valid chunk is still cached, followed by the error.
+			let mut fragment = subscriber.fragment().await.unwrap().unwrap();
+			assert!(fragment.chunk().await.unwrap().is_some());
+			assert_eq!(fragment.chunk().await.unwrap_err().code(), expected.code());
+		}
 	}
 }
@@ -13,9 +13,12 @@ pub mod broadcast;
 mod error;
 pub mod fragment;
 pub mod segment;
+mod sequence;
 pub mod track;
 
 pub(crate) mod watch;
 pub(crate) use watch::*;
 
 pub use error::*;
+pub use sequence::Arrival;
+pub(crate) use sequence::Sequencer;
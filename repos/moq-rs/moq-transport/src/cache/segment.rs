@@ -3,7 +3,7 @@
 //! A [Publisher] writes an ordered stream of fragments.
 //! Each fragment can have a sequence number, allowing the subscriber to detect gaps fragments.
 //!
-//! A [Subscriber] reads an ordered stream of fragments.
+//! A [Subscriber] reads fragments in the order they arrived, or in sequence order with [Subscriber::next_fragment].
 //! The subscriber can be cloned, in which case each subscriber receives a copy of each fragment. (fanout)
 //!
 //! The segment is closed with [CacheError::Closed] when all publishers or subscribers are dropped.
@@ -12,15 +12,18 @@ use std::{ops::Deref, sync::Arc, time};
 
 use crate::VarInt;
 
-use super::{fragment, CacheError, Watch};
+use super::{fragment, Arrival, CacheError, Sequencer, Watch};
 
 /// Create a new segment with the given info.
 pub fn new(info: Info) -> (Publisher, Subscriber) {
 	let state = Watch::new(State::default());
 	let info = Arc::new(info);
 
+	// Use tokio's clock, which can be paused in tests.
+	let created = tokio::time::Instant::now().into_std();
+
 	let publisher = Publisher::new(state.clone(), info.clone());
-	let subscriber = Subscriber::new(state, info);
+	let subscriber = Subscriber::new(state, info, created);
 
 	(publisher, subscriber)
 }
@@ -43,6 +46,9 @@ struct State {
 	// The data that has been received thus far.
 	fragments: Vec<fragment::Subscriber>,
 
+	// Used to detect gaps in the fragment sequence numbers.
+	sequencer: Sequencer,
+
 	// Set when the publisher is dropped.
 	closed: Result<(), CacheError>,
 }
@@ -59,6 +65,7 @@ impl Default for State {
 	fn default() -> Self {
 		Self {
 			fragments: Vec::new(),
+			sequencer: Sequencer::starting_at(0),
 			closed: Ok(()),
 		}
 	}
@@ -101,10 +108,16 @@ impl Publisher {
 
 		let mut state = self.state.lock_mut();
 		state.closed.clone()?;
+		state.sequencer.insert(sequence);
 		state.fragments.push(subscriber);
 		Ok(publisher)
 	}
 
+	/// Check how a fragment with this sequence number would arrive, relative to the fragments so far.
+	pub fn arrival(&self, sequence: VarInt) -> Arrival {
+		self.state.lock().sequencer.peek(sequence)
+	}
+
 	/// Write a fragment
 	pub fn fragment(&mut self, sequence: VarInt, size: usize) -> Result<fragment::Publisher, CacheError> {
 		self.push_fragment(sequence, Some(size))
@@ -147,26 +160,53 @@ pub struct Subscriber {
 	// Immutable segment state.
 	info: Arc<Info>,
 
+	// When the segment was created, which is when it was received for a relay.
+	created: time::Instant,
+
 	// The number of chunks that we've read.
 	// NOTE: Cloned subscribers inherit this index, but then run in parallel.
 	pub index: usize,
 
+	// The next fragment sequence returned by next_fragment.
+	expected: u64,
+
+	// Set when a gap before the expected fragment was returned, so we wait for it instead.
+	gap: bool,
+
 	// Dropped when all Subscribers are dropped.
 	_dropped: Arc<Dropped>,
 }
 
 impl Subscriber {
-	fn new(state: Watch<State>, info: Arc<Info>) -> Self {
+	fn new(state: Watch<State>, info: Arc<Info>, created: time::Instant) -> Self {
 		let _dropped = Arc::new(Dropped::new(state.clone()));
 
 		Self {
 			state,
 			info,
+			created,
 			index: 0,
+			expected: 0,
+			gap: false,
 			_dropped,
 		}
 	}
 
+	/// The number of payload bytes received so far, across every fragment.
+	pub fn size(&self) -> usize {
+		self.state.lock().fragments.iter().map(|fragment| fragment.size()).sum()
+	}
+
+	/// When the segment was created.
+	pub fn created(&self) -> time::Instant {
+		self.created
+	}
+
+	/// When the segment expires, or None if it's cached forever.
+	pub fn deadline(&self) -> Option<time::Instant> {
+		self.expires.map(|expires| self.created + expires)
+	}
+
 	/// Block until the next chunk of bytes is available.
 	pub async fn fragment(&mut self) -> Result<Option<fragment::Subscriber>, CacheError> {
 		loop {
@@ -190,6 +230,75 @@ impl Subscriber {
 			notify.await; // Try again when the state changes
 		}
 	}
+
+	/// Block until the next fragment in sequence order, or return a gap if a later fragment arrived first.
+	///
+	/// After a gap, call this again to wait for the missing fragment, or call [Subscriber::skip] to move past it.
+	/// The gap is skipped automatically once the segment is finished, since the missing fragments will never arrive.
+	pub async fn next_fragment(&mut self) -> Result<Option<Ordered>, CacheError> {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+
+				let expected = self.expected;
+				if let Some(fragment) = state.fragments.iter().find(|f| f.sequence.into_inner() == expected) {
+					self.expected += 1;
+					self.gap = false;
+					return Ok(Some(Ordered::Fragment(fragment.clone())));
+				}
+
+				let next = Self::next_after(&state, expected);
+
+				match (next, &state.closed) {
+					(Some(next), _) if !self.gap => {
+						self.gap = true;
+
+						// NOTE: This can't fail because it's smaller than next.
+						let missing = VarInt::try_from(expected).unwrap();
+						return Ok(Some(Ordered::Gap { missing, next }));
+					}
+					(Some(next), Err(_)) => {
+						self.expected = next.into_inner();
+						continue;
+					}
+					(None, Err(CacheError::Closed)) => return Ok(None),
+					(None, Err(err)) => return Err(err.clone()),
+					(_, Ok(())) => state.changed(),
+				}
+			};
+
+			notify.await; // Try again when the state changes
+		}
+	}
+
+	/// Skip any missing fragments, so [Subscriber::next_fragment] returns the next fragment that arrived.
+	pub fn skip(&mut self) {
+		let state = self.state.lock();
+		if let Some(next) = Self::next_after(&state, self.expected) {
+			self.expected = next.into_inner();
+			self.gap = false;
+		}
+	}
+
+	// The smallest fragment sequence after the given one.
+	fn next_after(state: &State, sequence: u64) -> Option<VarInt> {
+		state
+			.fragments
+			.iter()
+			.map(|f| f.sequence)
+			.filter(|s| s.into_inner() > sequence)
+			.min()
+	}
+}
+
+/// A fragment returned in sequence order by [Subscriber::next_fragment], or a gap before it.
+#[derive(Debug)]
+pub enum Ordered {
+	/// The next fragment in sequence.
+	Fragment(fragment::Subscriber),
+
+	/// The fragments from `missing` up to `next` haven't arrived, but `next` has.
+	Gap { missing: VarInt, next: VarInt },
 }
 
 impl Deref for Subscriber {
@@ -226,3 +335,48 @@ impl Drop for Dropped {
 		self.state.lock_mut().close(CacheError::Closed).ok();
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn reassembly() {
+		let (mut publisher, mut subscriber) = new(Info {
+			sequence: VarInt::ZERO,
+			priority: 0,
+			expires: None,
+		});
+
+		let arrival = publisher.arrival(VarInt::from_u32(2));
+		assert_eq!(arrival, Arrival::Gap(2));
+
+		publisher.fragment(VarInt::from_u32(2), 0).unwrap();
+		assert_eq!(publisher.arrival(VarInt::ZERO), Arrival::Reordered);
+
+		// The later fragment arrived first, so the missing fragments are reported.
+		match subscriber.next_fragment().await.unwrap().unwrap() {
+			Ordered::Gap { missing, next } => {
+				assert_eq!(missing, VarInt::ZERO);
+				assert_eq!(next, VarInt::from_u32(2));
+			}
+			ordered => panic!("expected a gap: {:?}", ordered),
+		}
+
+		// The missing fragment is returned once it arrives, in order.
+		publisher.fragment(VarInt::ZERO, 0).unwrap();
+		drop(publisher);
+
+		let mut ordered = Vec::new();
+		while let Some(next) = subscriber.next_fragment().await.unwrap() {
+			ordered.push(match next {
+				Ordered::Fragment(fragment) => Ok(fragment.sequence.into_inner()),
+				Ordered::Gap { missing, .. } => Err(missing.into_inner()),
+			});
+		}
+
+		// Fragment 1 never arrived, so it's reported once and then skipped.
+		assert_eq!(ordered, [Ok(0), Err(1), Ok(2)]);
+		assert!(subscriber.next_fragment().await.unwrap().is_none());
+	}
+}
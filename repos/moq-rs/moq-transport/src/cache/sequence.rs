@@ -0,0 +1,57 @@
+use crate::VarInt;
+
+/// How a segment or fragment arrived relative to the ones before it, used to detect gaps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrival {
+	/// The first or the next sequence number.
+	InOrder,
+
+	/// A later sequence number, skipping this many that haven't arrived (yet).
+	Gap(u64),
+
+	/// An earlier sequence number, which fills in a previous gap.
+	Reordered,
+
+	/// The same sequence number as the largest so far.
+	Duplicate,
+}
+
+// Remembers the largest sequence number so far, to classify the next arrival.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Sequencer {
+	// The expected first sequence number, or None if any is fine.
+	first: Option<u64>,
+
+	largest: Option<u64>,
+}
+
+impl Sequencer {
+	pub fn starting_at(first: u64) -> Self {
+		Self {
+			first: Some(first),
+			largest: None,
+		}
+	}
+
+	// Classify the sequence number without recording it.
+	pub fn peek(&self, sequence: VarInt) -> Arrival {
+		let sequence = sequence.into_inner();
+
+		match self.largest {
+			None => match self.first {
+				Some(first) if sequence > first => Arrival::Gap(sequence - first),
+				_ => Arrival::InOrder,
+			},
+			Some(largest) if sequence == largest + 1 => Arrival::InOrder,
+			Some(largest) if sequence > largest => Arrival::Gap(sequence - largest - 1),
+			Some(largest) if sequence == largest => Arrival::Duplicate,
+			Some(_) => Arrival::Reordered,
+		}
+	}
+
+	// Record the sequence number.
+	pub fn insert(&mut self, sequence: VarInt) {
+		let sequence = sequence.into_inner();
+		self.largest = Some(self.largest.map_or(sequence, |largest| largest.max(sequence)));
+	}
+}
@@ -16,7 +16,7 @@ use std::{collections::BinaryHeap, fmt, ops::Deref, sync::Arc, time};
 
 use indexmap::IndexMap;
 
-use super::{segment, CacheError, Watch};
+use super::{segment, Arrival, CacheError, Sequencer, Watch};
 use crate::VarInt;
 
 /// Create a track with the given name.
@@ -48,6 +48,12 @@ struct State {
 	// The number of None entries removed from the start of the lookup.
 	pruned: usize,
 
+	// The number of segments that have expired.
+	expired: usize,
+
+	// Used to detect gaps in the segment sequence numbers.
+	sequencer: Sequencer,
+
 	// Set when the publisher is closed/dropped, or all subscribers are dropped.
 	closed: Result<(), CacheError>,
 }
@@ -70,10 +76,11 @@ impl State {
 		if let Some(expires) = segment.expires {
 			self.expires.push(SegmentExpiration {
 				sequence: segment.sequence,
-				expires: time::Instant::now() + expires,
+				expires: tokio::time::Instant::now().into_std() + expires,
 			});
 		}
 
+		self.sequencer.insert(segment.sequence);
 		entry.insert(Some(segment));
 
 		// Expire any existing segments on insert.
@@ -86,7 +93,7 @@ impl State {
 
 	// Try expiring any segments
 	pub fn expire(&mut self) {
-		let now = time::Instant::now();
+		let now = tokio::time::Instant::now().into_std();
 		while let Some(segment) = self.expires.peek() {
 			if segment.expires > now {
 				break;
@@ -99,6 +106,7 @@ impl State {
 			};
 
 			self.expires.pop();
+			self.expired += 1;
 		}
 
 		// Remove None entries from the start of the lookup.
@@ -115,6 +123,8 @@ impl Default for State {
 			lookup: Default::default(),
 			expires: Default::default(),
 			pruned: 0,
+			expired: 0,
+			sequencer: Default::default(),
 			closed: Ok(()),
 		}
 	}
@@ -155,6 +165,11 @@ impl Publisher {
 		Ok(publisher)
 	}
 
+	/// Check how a segment with this sequence number would arrive, relative to the segments so far.
+	pub fn arrival(&self, sequence: VarInt) -> Arrival {
+		self.state.lock().sequencer.peek(sequence)
+	}
+
 	/// Close the segment with an error.
 	pub fn close(self, err: CacheError) -> Result<(), CacheError> {
 		self.state.lock_mut().close(err)
@@ -190,6 +205,10 @@ pub struct Subscriber {
 	// If there are multiple segments to return, we put them in here to return them in priority order.
 	pending: BinaryHeap<SegmentPriority>,
 
+	// Used to detect gaps in the segments returned so far, and how the last one arrived.
+	returned: Sequencer,
+	arrival: Arrival,
+
 	// Dropped when all subscribers are dropped.
 	_dropped: Arc<Dropped>,
 }
@@ -202,10 +221,30 @@ impl Subscriber {
 			info,
 			index: 0,
 			pending: Default::default(),
+			returned: Default::default(),
+			arrival: Arrival::InOrder,
 			_dropped,
 		}
 	}
 
+	/// Return a summary of the segments currently in the cache.
+	pub fn cached(&self) -> Cached {
+		let state = self.state.lock();
+		let sequences = state
+			.lookup
+			.iter()
+			.filter(|(_, segment)| segment.is_some())
+			.map(|(sequence, _)| *sequence);
+
+		Cached {
+			segments: sequences.clone().count(),
+			bytes: state.lookup.values().flatten().map(|segment| segment.size()).sum(),
+			expired: state.expired,
+			first: sequences.clone().min(),
+			last: sequences.max(),
+		}
+	}
+
 	/// Block until the next segment arrives
 	pub async fn segment(&mut self) -> Result<Option<segment::Subscriber>, CacheError> {
 		loop {
@@ -234,6 +273,10 @@ impl Subscriber {
 				// Return the higher priority segment.
 				if let Some(segment) = self.pending.pop() {
 					log::trace!("got segment: {:?}", segment.0);
+
+					self.arrival = self.returned.peek(segment.0.sequence);
+					self.returned.insert(segment.0.sequence);
+
 					return Ok(Some(segment.0));
 				}
 
@@ -248,6 +291,13 @@ impl Subscriber {
 			notify.await
 		}
 	}
+
+	/// How the segment last returned by [Subscriber::segment] arrived, relative to the segments returned before it.
+	///
+	/// An [Arrival::Gap] means some groups are missing, which may arrive later or may have been dropped or expired.
+	pub fn arrival(&self) -> Arrival {
+		self.arrival
+	}
 }
 
 impl Deref for Subscriber {
@@ -268,6 +318,23 @@ impl fmt::Debug for Subscriber {
 	}
 }
 
+/// A summary of the segments in the cache, see [Subscriber::cached].
+#[derive(Clone, Debug, Default)]
+pub struct Cached {
+	/// The number of segments that haven't expired yet.
+	pub segments: usize,
+
+	/// The number of payload bytes in the segments that haven't expired yet.
+	pub bytes: usize,
+
+	/// The number of segments that expired and were evicted.
+	pub expired: usize,
+
+	/// The smallest and largest sequence numbers of the segments that haven't expired yet.
+	pub first: Option<VarInt>,
+	pub last: Option<VarInt>,
+}
+
 // Closes the track on Drop.
 struct Dropped {
 	state: Watch<State>,
@@ -337,3 +404,45 @@ impl PartialEq for SegmentPriority {
 }
 
 impl Eq for SegmentPriority {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn info(sequence: u32, priority: u32) -> segment::Info {
+		segment::Info {
+			sequence: VarInt::from_u32(sequence),
+			priority,
+			expires: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn arrival() {
+		let (mut publisher, mut subscriber) = new("video");
+
+		publisher.create_segment(info(0, 0)).unwrap();
+		subscriber.segment().await.unwrap().unwrap();
+		assert_eq!(subscriber.arrival(), Arrival::InOrder);
+
+		// Group 1 is missing, which the subscriber sees as a gap.
+		publisher.create_segment(info(2, 0)).unwrap();
+		subscriber.segment().await.unwrap().unwrap();
+		assert_eq!(subscriber.arrival(), Arrival::Gap(1));
+
+		publisher.create_segment(info(1, 0)).unwrap();
+		subscriber.segment().await.unwrap().unwrap();
+		assert_eq!(subscriber.arrival(), Arrival::Reordered);
+
+		// Segments queued together are returned in priority order, so the later group can come first.
+		publisher.create_segment(info(3, 1)).unwrap();
+		publisher.create_segment(info(4, 0)).unwrap();
+
+		let segment = subscriber.segment().await.unwrap().unwrap();
+		assert_eq!(segment.sequence, VarInt::from_u32(4));
+		assert_eq!(subscriber.arrival(), Arrival::Gap(1));
+
+		subscriber.segment().await.unwrap().unwrap();
+		assert_eq!(subscriber.arrival(), Arrival::Reordered);
+	}
+}
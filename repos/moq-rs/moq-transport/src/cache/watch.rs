@@ -57,14 +57,14 @@ impl<T> Watch<T> {
 		Self { state }
 	}
 
-	pub fn lock(&self) -> WatchRef<T> {
+	pub fn lock(&self) -> WatchRef<'_, T> {
 		WatchRef {
 			state: self.state.clone(),
 			lock: self.state.lock().unwrap(),
 		}
 	}
 
-	pub fn lock_mut(&self) -> WatchMut<T> {
+	pub fn lock_mut(&self) -> WatchMut<'_, T> {
 		WatchMut {
 			lock: self.state.lock().unwrap(),
 		}
@@ -0,0 +1,39 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::{Decode, DecodeError, Encode, EncodeError};
+
+#[async_trait::async_trait]
+impl Decode for u8 {
+	/// Decode a single byte.
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(r.read_u8().await?)
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for u8 {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		w.write_u8(*self).await?;
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl Decode for bool {
+	/// Decode a single byte that must be 0 or 1.
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		match u8::decode(r).await? {
+			0 => Ok(false),
+			1 => Ok(true),
+			b => Err(DecodeError::InvalidValue(b.into())),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for bool {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		u8::from(*self).encode(w).await
+	}
+}
@@ -1,5 +1,9 @@
 use super::{BoundsExceeded, VarInt};
-use std::{io, str};
+use std::{
+	future::Future,
+	io, pin, str,
+	task::{self, Poll},
+};
 
 use thiserror::Error;
 
@@ -7,6 +11,8 @@ use thiserror::Error;
 // TODO Use trait aliases when they're stable, or add these bounds to every method.
 pub trait AsyncRead: tokio::io::AsyncRead + Unpin + Send {}
 impl AsyncRead for webtransport_quinn::RecvStream {}
+impl AsyncRead for quinn::RecvStream {}
+impl AsyncRead for Box<dyn crate::transport::RecvStream> {}
 impl<T> AsyncRead for tokio::io::Take<&mut T> where T: AsyncRead {}
 impl<T: AsRef<[u8]> + Unpin + Send> AsyncRead for io::Cursor<T> {}
 
@@ -33,6 +39,12 @@ pub enum DecodeError {
 	#[error("invalid subscribe location")]
 	InvalidSubscribeLocation,
 
+	#[error("invalid value: {0:?}")]
+	InvalidValue(VarInt),
+
+	#[error("invalid message length")]
+	InvalidLength,
+
 	#[error("varint bounds exceeded")]
 	BoundsExceeded(#[from] BoundsExceeded),
 
@@ -53,3 +65,17 @@ pub enum DecodeError {
 	#[error("no more messages")]
 	Final,
 }
+
+/// Run a decode future to completion without an async runtime.
+///
+/// Only valid for readers that never block, like an in-memory buffer.
+pub(crate) fn decode_now<T, F: Future<Output = Result<T, DecodeError>>>(f: F) -> Result<T, DecodeError> {
+	let mut f = pin::pin!(f);
+	let mut cx = task::Context::from_waker(task::Waker::noop());
+
+	match f.as_mut().poll(&mut cx) {
+		Poll::Ready(res) => res,
+		// An in-memory buffer never blocks, so this means the reader was misused.
+		Poll::Pending => Err(DecodeError::UnexpectedEnd),
+	}
+}
@@ -6,6 +6,8 @@ use thiserror::Error;
 // TODO Use trait aliases when they're stable, or add these bounds to every method.
 pub trait AsyncWrite: tokio::io::AsyncWrite + Unpin + Send {}
 impl AsyncWrite for webtransport_quinn::SendStream {}
+impl AsyncWrite for Box<dyn crate::transport::SendStream> {}
+impl AsyncWrite for quinn::SendStream {}
 impl AsyncWrite for Vec<u8> {}
 
 #[async_trait::async_trait]
@@ -22,6 +24,18 @@ pub enum EncodeError {
 	#[error("invalid value")]
 	InvalidValue,
 
+	#[error("missing field: {0}")]
+	MissingField(&'static str),
+
+	#[error("field requires an extension that was not negotiated: {0}")]
+	UnexpectedField(&'static str),
+
+	#[error("invalid subscribe location")]
+	InvalidSubscribeLocation,
+
+	#[error("message requires an extension that was not negotiated: {0}")]
+	MissingExtension(&'static str),
+
 	#[error("i/o error: {0}")]
 	IoError(#[from] std::io::Error),
 }
@@ -1,3 +1,4 @@
+mod byte;
 mod decode;
 mod encode;
 mod params;
@@ -7,5 +8,4 @@ mod varint;
 pub use decode::*;
 pub use encode::*;
 pub use params::*;
-pub use string::*;
 pub use varint::*;
@@ -10,7 +10,7 @@ use crate::{
 	VarInt,
 };
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Params(pub HashMap<VarInt, Vec<u8>>);
 
 #[async_trait::async_trait]
@@ -33,6 +33,12 @@ impl Decode for Params {
 			let mut pr = r.take(size.into_inner());
 			let mut buf = Vec::with_capacity(max(1024, pr.limit() as usize));
 			pr.read_to_end(&mut buf).await?;
+
+			// The stream ended before the full value was read.
+			if buf.len() as u64 != size.into_inner() {
+				return Err(DecodeError::UnexpectedEnd);
+			}
+
 			params.insert(kind, buf);
 
 			r = pr.into_inner();
@@ -24,6 +24,12 @@ impl Decode for String {
 		let size = VarInt::decode(r).await?.into_inner();
 		let mut str = String::with_capacity(min(1024, size) as usize);
 		r.take(size).read_to_string(&mut str).await?;
+
+		// The stream ended before the full string was read.
+		if str.len() as u64 != size {
+			return Err(DecodeError::UnexpectedEnd);
+		}
+
 		Ok(str)
 	}
 }
@@ -40,6 +40,19 @@ impl VarInt {
 	pub const fn into_inner(self) -> u64 {
 		self.0
 	}
+
+	/// The number of bytes needed to encode this value.
+	pub const fn size(self) -> usize {
+		if self.0 < 2u64.pow(6) {
+			1
+		} else if self.0 < 2u64.pow(14) {
+			2
+		} else if self.0 < 2u64.pow(30) {
+			4
+		} else {
+			8
+		}
+	}
 }
 
 impl From<VarInt> for u64 {
@@ -0,0 +1,34 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params};
+
+use super::TrackNamespace;
+
+/// Sent by the publisher to announce the availability of a group of tracks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Announce {
+	/// The track namespace
+	pub namespace: TrackNamespace,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+#[async_trait::async_trait]
+impl Decode for Announce {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let namespace = TrackNamespace::decode(r).await?;
+		let params = Params::decode(r).await?;
+
+		Ok(Self { namespace, params })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for Announce {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.namespace.encode(w).await?;
+		self.params.encode(w).await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,43 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+use super::TrackNamespace;
+
+/// Sent by the subscriber to reject an Announce.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnounceError {
+	// Echo back the namespace that was rejected.
+	pub namespace: TrackNamespace,
+
+	// An error code.
+	pub code: u32,
+
+	// An optional, human-readable reason.
+	pub reason: String,
+}
+
+#[async_trait::async_trait]
+impl Decode for AnnounceError {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let namespace = TrackNamespace::decode(r).await?;
+		let code = VarInt::decode(r).await?.try_into()?;
+		let reason = String::decode(r).await?;
+
+		Ok(Self {
+			namespace,
+			code,
+			reason,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for AnnounceError {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.namespace.encode(w).await?;
+		VarInt::from_u32(self.code).encode(w).await?;
+		self.reason.encode(w).await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,26 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+use super::TrackNamespace;
+
+/// Sent by the subscriber to accept an Announce.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnounceOk {
+	// Echo back the namespace that was announced.
+	pub namespace: TrackNamespace,
+}
+
+#[async_trait::async_trait]
+impl Decode for AnnounceOk {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let namespace = TrackNamespace::decode(r).await?;
+		Ok(Self { namespace })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for AnnounceOk {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.namespace.encode(w).await
+	}
+}
@@ -0,0 +1,73 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+
+use super::{GroupOrder, Location, TrackNamespace};
+
+/// Sent by the subscriber to request a range of past objects, delivered over a single data stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fetch {
+	/// An ID we choose, shared with the Subscribe ID space.
+	pub id: VarInt,
+
+	/// The track namespace.
+	pub namespace: TrackNamespace,
+
+	/// The track name.
+	pub name: String,
+
+	/// The priority of this fetch relative to others, where **smaller** values are sent first.
+	pub priority: u8,
+
+	/// The order in which groups should be delivered.
+	pub group_order: GroupOrder,
+
+	/// The first group/object requested.
+	pub start: Location,
+
+	/// The last group/object requested, where an object of zero means the entire group.
+	pub end: Location,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+#[async_trait::async_trait]
+impl Decode for Fetch {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let namespace = TrackNamespace::decode(r).await?;
+		let name = String::decode(r).await?;
+		let priority = u8::decode(r).await?;
+		let group_order = GroupOrder::decode(r).await?;
+		let start = Location::decode(r).await?;
+		let end = Location::decode(r).await?;
+		let params = Params::decode(r).await?;
+
+		Ok(Self {
+			id,
+			namespace,
+			name,
+			priority,
+			group_order,
+			start,
+			end,
+			params,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for Fetch {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		self.namespace.encode(w).await?;
+		self.name.encode(w).await?;
+		self.priority.encode(w).await?;
+		self.group_order.encode(w).await?;
+		self.start.encode(w).await?;
+		self.end.encode(w).await?;
+		self.params.encode(w).await?;
+
+		Ok(())
+	}
+}
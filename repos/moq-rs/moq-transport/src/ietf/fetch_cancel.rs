@@ -0,0 +1,24 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// Sent by the subscriber to terminate a Fetch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchCancel {
+	/// The ID of the Fetch.
+	pub id: VarInt,
+}
+
+#[async_trait::async_trait]
+impl Decode for FetchCancel {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		Ok(Self { id })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for FetchCancel {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await
+	}
+}
@@ -0,0 +1,46 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// Sent by the publisher to reject a Fetch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchError {
+	/// The ID of the Fetch.
+	pub id: VarInt,
+
+	/// An error code, see the `CODE_*` constants.
+	pub code: u32,
+
+	/// An optional, human-readable reason.
+	pub reason: String,
+}
+
+impl FetchError {
+	pub const CODE_INTERNAL_ERROR: u32 = 0x0;
+	pub const CODE_UNAUTHORIZED: u32 = 0x1;
+	pub const CODE_TIMEOUT: u32 = 0x2;
+	pub const CODE_NOT_SUPPORTED: u32 = 0x3;
+	pub const CODE_TRACK_DOES_NOT_EXIST: u32 = 0x4;
+	pub const CODE_INVALID_RANGE: u32 = 0x5;
+}
+
+#[async_trait::async_trait]
+impl Decode for FetchError {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let code = VarInt::decode(r).await?.try_into()?;
+		let reason = String::decode(r).await?;
+
+		Ok(Self { id, code, reason })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for FetchError {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		VarInt::from_u32(self.code).encode(w).await?;
+		self.reason.encode(w).await?;
+
+		Ok(())
+	}
+}
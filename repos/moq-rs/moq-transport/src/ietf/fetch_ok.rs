@@ -0,0 +1,55 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+
+use super::{GroupOrder, Location};
+
+/// Sent by the publisher to accept a Fetch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchOk {
+	/// The ID of the Fetch.
+	pub id: VarInt,
+
+	/// The order in which groups will be delivered.
+	pub group_order: GroupOrder,
+
+	/// Set if the track has ended and `largest` is the final object.
+	pub end_of_track: bool,
+
+	/// The largest group/object available.
+	pub largest: Location,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+#[async_trait::async_trait]
+impl Decode for FetchOk {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let group_order = GroupOrder::decode(r).await?;
+		let end_of_track = bool::decode(r).await?;
+		let largest = Location::decode(r).await?;
+		let params = Params::decode(r).await?;
+
+		Ok(Self {
+			id,
+			group_order,
+			end_of_track,
+			largest,
+			params,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for FetchOk {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		self.group_order.encode(w).await?;
+		self.end_of_track.encode(w).await?;
+		self.largest.encode(w).await?;
+		self.params.encode(w).await?;
+
+		Ok(())
+	}
+}
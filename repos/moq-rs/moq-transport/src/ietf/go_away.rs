@@ -0,0 +1,24 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+/// Sent by the server to indicate that the client should connect to a different server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GoAway {
+	/// The new session URI, or empty to reuse the current one.
+	pub url: String,
+}
+
+#[async_trait::async_trait]
+impl Decode for GoAway {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let url = String::decode(r).await?;
+		Ok(Self { url })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for GoAway {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.url.encode(w).await
+	}
+}
@@ -0,0 +1,50 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// An absolute group/object pair within a track.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+	pub group: VarInt,
+	pub object: VarInt,
+}
+
+impl Location {
+	// Decode a location prefixed with a ContentExists/EndOfTrack byte.
+	pub(super) async fn decode_optional<R: AsyncRead>(r: &mut R) -> Result<Option<Self>, DecodeError> {
+		match bool::decode(r).await? {
+			true => Ok(Some(Self::decode(r).await?)),
+			false => Ok(None),
+		}
+	}
+
+	// Encode a location prefixed with a ContentExists/EndOfTrack byte.
+	pub(super) async fn encode_optional<W: AsyncWrite>(location: &Option<Self>, w: &mut W) -> Result<(), EncodeError> {
+		location.is_some().encode(w).await?;
+
+		if let Some(location) = location {
+			location.encode(w).await?;
+		}
+
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl Decode for Location {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let group = VarInt::decode(r).await?;
+		let object = VarInt::decode(r).await?;
+
+		Ok(Self { group, object })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for Location {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.group.encode(w).await?;
+		self.object.encode(w).await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,24 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// Sent by the publisher to increase the maximum subscribe ID the subscriber may use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaxSubscribeId {
+	/// One more than the largest subscribe ID allowed.
+	pub id: VarInt,
+}
+
+#[async_trait::async_trait]
+impl Decode for MaxSubscribeId {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		Ok(Self { id })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for MaxSubscribeId {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await
+	}
+}
@@ -0,0 +1,203 @@
+//! The wire format used by newer IETF drafts, currently [draft-07](https://www.ietf.org/archive/id/draft-ietf-moq-transport-07.html).
+//!
+//! This is a separate codec from [crate::message], used when [Version::DRAFT_07](crate::setup::Version::DRAFT_07) is negotiated.
+//! The session converts between the two, so the rest of the crate only deals with [crate::message].
+//!
+//! Rough list of differences from the fork:
+//! - Control messages are prefixed with their length, including SETUP.
+//! - SUBSCRIBE contains a `track_alias`, used by data streams instead of the subscribe ID.
+//! - SUBSCRIBE uses a [FilterType] instead of start/end locations.
+//! - SUBSCRIBE_RESET and SUBSCRIBE_FIN are merged into SUBSCRIBE_DONE.
+//! - FETCH requests a range of past objects, delivered over a single data stream.
+//! - Track namespaces are a tuple of strings, see [TrackNamespace].
+//! - Data streams start with a [StreamHeader], followed by length-prefixed objects.
+//! - OBJECT `expires` and `ntp_timestamp` don't exist, and the priority is a single byte.
+mod announce;
+mod announce_cancel;
+mod announce_error;
+mod announce_ok;
+mod fetch;
+mod fetch_cancel;
+mod fetch_error;
+mod fetch_ok;
+mod go_away;
+mod location;
+mod max_subscribe_id;
+mod namespace;
+mod object;
+mod stream_header;
+mod subscribe;
+mod subscribe_done;
+mod subscribe_error;
+mod subscribe_ok;
+mod subscribe_update;
+mod unannounce;
+mod unsubscribe;
+
+pub use announce::*;
+pub use announce_cancel::*;
+pub use announce_error::*;
+pub use announce_ok::*;
+pub use fetch::*;
+pub use fetch_cancel::*;
+pub use fetch_error::*;
+pub use fetch_ok::*;
+pub use go_away::*;
+pub use location::*;
+pub use max_subscribe_id::*;
+pub use namespace::*;
+pub use object::*;
+pub use stream_header::*;
+pub use subscribe::*;
+pub use subscribe_done::*;
+pub use subscribe_error::*;
+pub use subscribe_ok::*;
+pub use subscribe_update::*;
+pub use unannounce::*;
+pub use unsubscribe::*;
+
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, io};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+// Later drafts limit control messages to a 16-bit length, so enforce it now to avoid large allocations.
+const MAX_MESSAGE_SIZE: u64 = u16::MAX as u64;
+
+// Use a macro to generate the message types rather than copy-paste.
+// Unlike the fork, each message is prefixed with its type and the length of the payload.
+macro_rules! message_types {
+    {$($name:ident = $val:expr,)*} => {
+		/// All supported control messages.
+		#[derive(Clone, PartialEq, Eq)]
+		pub enum Message {
+			$($name($name)),*
+		}
+
+		impl Message {
+			pub fn id(&self) -> VarInt {
+				match self {
+					$(Self::$name(_) => {
+						VarInt::from_u32($val)
+					},)*
+				}
+			}
+
+			pub fn name(&self) -> &'static str {
+				match self {
+					$(Self::$name(_) => {
+						stringify!($name)
+					},)*
+				}
+			}
+		}
+
+		#[async_trait::async_trait]
+		impl Decode for Message {
+			async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+				let t = VarInt::decode(r).await?;
+
+				let size = VarInt::decode(r).await?.into_inner();
+				if size > MAX_MESSAGE_SIZE {
+					return Err(DecodeError::InvalidLength);
+				}
+
+				let mut buf = vec![0; size as usize];
+				r.read_exact(&mut buf).await?;
+
+				let mut payload = io::Cursor::new(buf);
+
+				let msg = match t.into_inner() {
+					$($val => Self::$name($name::decode(&mut payload).await?),)*
+					_ => return Err(DecodeError::InvalidMessage(t)),
+				};
+
+				// The payload must be consumed exactly.
+				if payload.position() != size {
+					return Err(DecodeError::InvalidLength);
+				}
+
+				Ok(msg)
+			}
+		}
+
+		#[async_trait::async_trait]
+		impl Encode for Message {
+			async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+				// Encode the payload first so we know the length.
+				let mut payload = Vec::new();
+				match self {
+					$(Self::$name(ref m) => m.encode(&mut payload).await?,)*
+				}
+
+				if payload.len() as u64 > MAX_MESSAGE_SIZE {
+					return Err(EncodeError::InvalidValue);
+				}
+
+				self.id().encode(w).await?;
+				VarInt::try_from(payload.len())?.encode(w).await?;
+				w.write_all(&payload).await?;
+
+				Ok(())
+			}
+		}
+
+		$(impl From<$name> for Message {
+			fn from(m: $name) -> Self {
+				Message::$name(m)
+			}
+		})*
+
+		impl fmt::Debug for Message {
+			// Delegate to the message formatter
+			fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				match self {
+					$(Self::$name(ref m) => m.fmt(f),)*
+				}
+			}
+		}
+    }
+}
+
+// Each message is prefixed with the given VarInt type and a VarInt length.
+message_types! {
+	// NOTE: Setup is handled by the setup module, which detects the framing.
+	// ClientSetup = 0x40
+	// ServerSetup = 0x41
+
+	// SUBSCRIBE family, sent by subscriber
+	SubscribeUpdate = 0x2,
+	Subscribe = 0x3,
+	Unsubscribe = 0xa,
+
+	// SUBSCRIBE family, sent by publisher
+	SubscribeOk = 0x4,
+	SubscribeError = 0x5,
+	SubscribeDone = 0xb,
+
+	// ANNOUNCE family, sent by publisher
+	Announce = 0x6,
+	Unannounce = 0x9,
+
+	// ANNOUNCE family, sent by subscriber
+	AnnounceOk = 0x7,
+	AnnounceError = 0x8,
+	AnnounceCancel = 0xc,
+
+	// FETCH family, sent by subscriber
+	Fetch = 0x16,
+	FetchCancel = 0x17,
+
+	// FETCH family, sent by publisher
+	FetchOk = 0x18,
+	FetchError = 0x19,
+
+	// Misc
+	GoAway = 0x10,
+	MaxSubscribeId = 0x15,
+}
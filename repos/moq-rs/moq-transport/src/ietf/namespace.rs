@@ -0,0 +1,50 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// A track namespace, encoded as a tuple of strings.
+///
+/// The fork uses a single string instead, so we convert by splitting/joining on `/`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TrackNamespace(pub Vec<String>);
+
+impl TrackNamespace {
+	/// Split a path into a tuple, where the empty path is the empty tuple.
+	pub fn from_path(path: &str) -> Self {
+		match path {
+			"" => Self::default(),
+			path => Self(path.split('/').map(String::from).collect()),
+		}
+	}
+
+	/// Join the tuple into a path.
+	pub fn to_path(&self) -> String {
+		self.0.join("/")
+	}
+}
+
+#[async_trait::async_trait]
+impl Decode for TrackNamespace {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let count = VarInt::decode(r).await?.into_inner();
+
+		let mut parts = Vec::new();
+		for _ in 0..count {
+			parts.push(String::decode(r).await?);
+		}
+
+		Ok(Self(parts))
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for TrackNamespace {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		VarInt::try_from(self.0.len())?.encode(w).await?;
+
+		for part in &self.0 {
+			part.encode(w).await?;
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,160 @@
+use std::io;
+
+use tokio::io::AsyncReadExt;
+
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// The header of each object on a subgroup stream, followed by `size` bytes of payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubgroupObject {
+	/// The sequence number within the group.
+	pub id: VarInt,
+
+	/// The size of the payload.
+	pub size: VarInt,
+
+	/// The status, which is only encoded when the payload is empty.
+	pub status: ObjectStatus,
+}
+
+#[async_trait::async_trait]
+impl Decode for SubgroupObject {
+	/// Decode the next object, returning [DecodeError::Final] if the stream naturally ended.
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = match r.read_u8().await {
+			Ok(b) => VarInt::decode_byte(b, r).await?,
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(DecodeError::Final),
+			Err(e) => return Err(e.into()),
+		};
+
+		let size = VarInt::decode(r).await?;
+		let status = ObjectStatus::decode_sized(size, r).await?;
+
+		Ok(Self { id, size, status })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for SubgroupObject {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		self.size.encode(w).await?;
+		self.status.encode_sized(self.size, w).await?;
+
+		Ok(())
+	}
+}
+
+/// The header of each object on a fetch stream, followed by `size` bytes of payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchObject {
+	/// The sequence number within the track.
+	pub group: VarInt,
+
+	/// The subgroup within the group.
+	pub subgroup: VarInt,
+
+	/// The sequence number within the group.
+	pub id: VarInt,
+
+	/// The publisher priority, where **smaller** values are sent first.
+	pub priority: u8,
+
+	/// The size of the payload.
+	pub size: VarInt,
+
+	/// The status, which is only encoded when the payload is empty.
+	pub status: ObjectStatus,
+}
+
+#[async_trait::async_trait]
+impl Decode for FetchObject {
+	/// Decode the next object, returning [DecodeError::Final] if the stream naturally ended.
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let group = match r.read_u8().await {
+			Ok(b) => VarInt::decode_byte(b, r).await?,
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(DecodeError::Final),
+			Err(e) => return Err(e.into()),
+		};
+
+		let subgroup = VarInt::decode(r).await?;
+		let id = VarInt::decode(r).await?;
+		let priority = u8::decode(r).await?;
+		let size = VarInt::decode(r).await?;
+		let status = ObjectStatus::decode_sized(size, r).await?;
+
+		Ok(Self {
+			group,
+			subgroup,
+			id,
+			priority,
+			size,
+			status,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for FetchObject {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.group.encode(w).await?;
+		self.subgroup.encode(w).await?;
+		self.id.encode(w).await?;
+		self.priority.encode(w).await?;
+		self.size.encode(w).await?;
+		self.status.encode_sized(self.size, w).await?;
+
+		Ok(())
+	}
+}
+
+/// Signals the absence of an object, or the end of a group/track.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ObjectStatus {
+	#[default]
+	Normal,
+	DoesNotExist,
+	EndOfGroup,
+	EndOfTrackAndGroup,
+	EndOfTrack,
+}
+
+impl ObjectStatus {
+	// The status is only present when the payload is empty.
+	async fn decode_sized<R: AsyncRead>(size: VarInt, r: &mut R) -> Result<Self, DecodeError> {
+		if size != VarInt::ZERO {
+			return Ok(Self::Normal);
+		}
+
+		let status = VarInt::decode(r).await?;
+		match status.into_inner() {
+			0x0 => Ok(Self::Normal),
+			0x1 => Ok(Self::DoesNotExist),
+			0x3 => Ok(Self::EndOfGroup),
+			0x4 => Ok(Self::EndOfTrackAndGroup),
+			0x5 => Ok(Self::EndOfTrack),
+			_ => Err(DecodeError::InvalidValue(status)),
+		}
+	}
+
+	async fn encode_sized<W: AsyncWrite>(&self, size: VarInt, w: &mut W) -> Result<(), EncodeError> {
+		if size != VarInt::ZERO {
+			// Only normal objects can have a payload.
+			return match self {
+				Self::Normal => Ok(()),
+				_ => Err(EncodeError::InvalidValue),
+			};
+		}
+
+		let status = match self {
+			Self::Normal => 0x0,
+			Self::DoesNotExist => 0x1,
+			Self::EndOfGroup => 0x3,
+			Self::EndOfTrackAndGroup => 0x4,
+			Self::EndOfTrack => 0x5,
+		};
+
+		VarInt::from_u32(status).encode(w).await
+	}
+}
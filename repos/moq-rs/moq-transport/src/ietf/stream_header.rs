@@ -0,0 +1,117 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// Sent at the start of each unidirectional data stream, followed by zero or more objects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StreamHeader {
+	/// Followed by [SubgroupObject](super::SubgroupObject)s, all within the same group.
+	Subgroup(SubgroupHeader),
+
+	/// Followed by [FetchObject](super::FetchObject)s, in response to a Fetch.
+	Fetch(FetchHeader),
+}
+
+impl StreamHeader {
+	const SUBGROUP: u32 = 0x4;
+	const FETCH: u32 = 0x5;
+
+	/// The stream type.
+	pub fn id(&self) -> VarInt {
+		match self {
+			Self::Subgroup(_) => VarInt::from_u32(Self::SUBGROUP),
+			Self::Fetch(_) => VarInt::from_u32(Self::FETCH),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Decode for StreamHeader {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let typ = VarInt::decode(r).await?;
+
+		match typ.into_inner() {
+			t if t == Self::SUBGROUP as u64 => Ok(Self::Subgroup(SubgroupHeader::decode(r).await?)),
+			t if t == Self::FETCH as u64 => Ok(Self::Fetch(FetchHeader::decode(r).await?)),
+			_ => Err(DecodeError::InvalidMessage(typ)),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for StreamHeader {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id().encode(w).await?;
+
+		match self {
+			Self::Subgroup(header) => header.encode(w).await,
+			Self::Fetch(header) => header.encode(w).await,
+		}
+	}
+}
+
+/// The header of a stream containing objects from the same group and subgroup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubgroupHeader {
+	/// The alias chosen by the subscriber in the Subscribe.
+	pub track_alias: VarInt,
+
+	/// The sequence number within the track.
+	pub group: VarInt,
+
+	/// The sequence number within the group.
+	pub subgroup: VarInt,
+
+	/// The publisher priority, where **smaller** values are sent first.
+	pub priority: u8,
+}
+
+#[async_trait::async_trait]
+impl Decode for SubgroupHeader {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let track_alias = VarInt::decode(r).await?;
+		let group = VarInt::decode(r).await?;
+		let subgroup = VarInt::decode(r).await?;
+		let priority = u8::decode(r).await?;
+
+		Ok(Self {
+			track_alias,
+			group,
+			subgroup,
+			priority,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for SubgroupHeader {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.track_alias.encode(w).await?;
+		self.group.encode(w).await?;
+		self.subgroup.encode(w).await?;
+		self.priority.encode(w).await?;
+
+		Ok(())
+	}
+}
+
+/// The header of a stream sent in response to a Fetch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchHeader {
+	/// The ID of the Fetch.
+	pub id: VarInt,
+}
+
+#[async_trait::async_trait]
+impl Decode for FetchHeader {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		Ok(Self { id })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for FetchHeader {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await
+	}
+}
@@ -0,0 +1,179 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+
+use super::TrackNamespace;
+
+/// Sent by the subscriber to request objects for the given track.
+///
+/// Objects will use the provided track alias instead of the full track name, to save bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subscribe {
+	/// An ID we choose so we can refer to this subscription.
+	pub id: VarInt,
+
+	/// An alias we choose, used by data streams instead of the full track name.
+	pub track_alias: VarInt,
+
+	/// The track namespace.
+	pub namespace: TrackNamespace,
+
+	/// The track name.
+	pub name: String,
+
+	/// The priority of this subscription relative to others, where **smaller** values are sent first.
+	pub priority: u8,
+
+	/// The order in which groups should be delivered.
+	pub group_order: GroupOrder,
+
+	/// Which objects should be delivered.
+	pub filter: FilterType,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+#[async_trait::async_trait]
+impl Decode for Subscribe {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let track_alias = VarInt::decode(r).await?;
+		let namespace = TrackNamespace::decode(r).await?;
+		let name = String::decode(r).await?;
+		let priority = u8::decode(r).await?;
+		let group_order = GroupOrder::decode(r).await?;
+		let filter = FilterType::decode(r).await?;
+		let params = Params::decode(r).await?;
+
+		Ok(Self {
+			id,
+			track_alias,
+			namespace,
+			name,
+			priority,
+			group_order,
+			filter,
+			params,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for Subscribe {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		self.track_alias.encode(w).await?;
+		self.namespace.encode(w).await?;
+		self.name.encode(w).await?;
+		self.priority.encode(w).await?;
+		self.group_order.encode(w).await?;
+		self.filter.encode(w).await?;
+		self.params.encode(w).await?;
+
+		Ok(())
+	}
+}
+
+/// Signal which objects should be delivered, replacing the start/end locations of the fork.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterType {
+	/// Start at the beginning of the latest group.
+	LatestGroup,
+
+	/// Start at the latest object.
+	LatestObject,
+
+	/// Start at the given group/object, with no end.
+	AbsoluteStart { group: VarInt, object: VarInt },
+
+	/// Start at the given group/object and end after the given group.
+	AbsoluteRange {
+		start_group: VarInt,
+		start_object: VarInt,
+		end_group: VarInt,
+	},
+}
+
+#[async_trait::async_trait]
+impl Decode for FilterType {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let kind = VarInt::decode(r).await?;
+
+		match kind.into_inner() {
+			0x1 => Ok(Self::LatestGroup),
+			0x2 => Ok(Self::LatestObject),
+			0x3 => Ok(Self::AbsoluteStart {
+				group: VarInt::decode(r).await?,
+				object: VarInt::decode(r).await?,
+			}),
+			0x4 => Ok(Self::AbsoluteRange {
+				start_group: VarInt::decode(r).await?,
+				start_object: VarInt::decode(r).await?,
+				end_group: VarInt::decode(r).await?,
+			}),
+			_ => Err(DecodeError::InvalidValue(kind)),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for FilterType {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		match self {
+			Self::LatestGroup => VarInt::from_u32(0x1).encode(w).await?,
+			Self::LatestObject => VarInt::from_u32(0x2).encode(w).await?,
+			Self::AbsoluteStart { group, object } => {
+				VarInt::from_u32(0x3).encode(w).await?;
+				group.encode(w).await?;
+				object.encode(w).await?;
+			}
+			Self::AbsoluteRange {
+				start_group,
+				start_object,
+				end_group,
+			} => {
+				VarInt::from_u32(0x4).encode(w).await?;
+				start_group.encode(w).await?;
+				start_object.encode(w).await?;
+				end_group.encode(w).await?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// The order in which groups are delivered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GroupOrder {
+	/// Use the order chosen by the publisher. Not valid in responses.
+	#[default]
+	Publisher,
+	Ascending,
+	Descending,
+}
+
+#[async_trait::async_trait]
+impl Decode for GroupOrder {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		match u8::decode(r).await? {
+			0x0 => Ok(Self::Publisher),
+			0x1 => Ok(Self::Ascending),
+			0x2 => Ok(Self::Descending),
+			b => Err(DecodeError::InvalidValue(b.into())),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for GroupOrder {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		let b: u8 = match self {
+			Self::Publisher => 0x0,
+			Self::Ascending => 0x1,
+			Self::Descending => 0x2,
+		};
+
+		b.encode(w).await
+	}
+}
@@ -0,0 +1,54 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+use super::Location;
+
+/// Sent by the publisher to terminate a Subscribe, replacing SUBSCRIBE_FIN and SUBSCRIBE_RESET.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscribeDone {
+	/// The ID for this subscription.
+	pub id: VarInt,
+
+	/// A status code, see the `STATUS_*` constants.
+	pub code: u32,
+
+	/// An optional, human-readable reason.
+	pub reason: String,
+
+	/// The final group/object sent on this subscription, if any.
+	pub last: Option<Location>,
+}
+
+impl SubscribeDone {
+	pub const STATUS_UNSUBSCRIBED: u32 = 0x0;
+	pub const STATUS_INTERNAL_ERROR: u32 = 0x1;
+	pub const STATUS_UNAUTHORIZED: u32 = 0x2;
+	pub const STATUS_TRACK_ENDED: u32 = 0x3;
+	pub const STATUS_SUBSCRIPTION_ENDED: u32 = 0x4;
+	pub const STATUS_GOING_AWAY: u32 = 0x5;
+	pub const STATUS_EXPIRED: u32 = 0x6;
+}
+
+#[async_trait::async_trait]
+impl Decode for SubscribeDone {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let code = VarInt::decode(r).await?.try_into()?;
+		let reason = String::decode(r).await?;
+		let last = Location::decode_optional(r).await?;
+
+		Ok(Self { id, code, reason, last })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for SubscribeDone {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		VarInt::from_u32(self.code).encode(w).await?;
+		self.reason.encode(w).await?;
+		Location::encode_optional(&self.last, w).await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,47 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// Sent by the publisher to reject a Subscribe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscribeError {
+	/// The ID for this subscription.
+	pub id: VarInt,
+
+	/// An error code.
+	pub code: u32,
+
+	/// An optional, human-readable reason.
+	pub reason: String,
+
+	/// The track alias, which the subscriber can reuse with a new Subscribe.
+	pub track_alias: VarInt,
+}
+
+#[async_trait::async_trait]
+impl Decode for SubscribeError {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let code = VarInt::decode(r).await?.try_into()?;
+		let reason = String::decode(r).await?;
+		let track_alias = VarInt::decode(r).await?;
+
+		Ok(Self {
+			id,
+			code,
+			reason,
+			track_alias,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for SubscribeError {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		VarInt::from_u32(self.code).encode(w).await?;
+		self.reason.encode(w).await?;
+		self.track_alias.encode(w).await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,55 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+
+use super::{GroupOrder, Location};
+
+/// Sent by the publisher to accept a Subscribe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscribeOk {
+	/// The ID for this subscription.
+	pub id: VarInt,
+
+	/// The subscription will expire in this many milliseconds, or zero for never.
+	pub expires: VarInt,
+
+	/// The order in which groups will be delivered.
+	pub group_order: GroupOrder,
+
+	/// The largest group/object available, if any.
+	pub largest: Option<Location>,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+#[async_trait::async_trait]
+impl Decode for SubscribeOk {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let expires = VarInt::decode(r).await?;
+		let group_order = GroupOrder::decode(r).await?;
+		let largest = Location::decode_optional(r).await?;
+		let params = Params::decode(r).await?;
+
+		Ok(Self {
+			id,
+			expires,
+			group_order,
+			largest,
+			params,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for SubscribeOk {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		self.expires.encode(w).await?;
+		self.group_order.encode(w).await?;
+		Location::encode_optional(&self.largest, w).await?;
+		self.params.encode(w).await?;
+
+		Ok(())
+	}
+}
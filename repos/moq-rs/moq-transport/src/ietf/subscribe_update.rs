@@ -0,0 +1,57 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+
+/// Sent by the subscriber to narrow an existing Subscribe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscribeUpdate {
+	/// The ID for this subscription.
+	pub id: VarInt,
+
+	/// The new start group/object.
+	pub start_group: VarInt,
+	pub start_object: VarInt,
+
+	/// The new end group, plus one, or zero for open-ended.
+	pub end_group: VarInt,
+
+	/// The new subscriber priority.
+	pub priority: u8,
+
+	/// Optional parameters
+	pub params: Params,
+}
+
+#[async_trait::async_trait]
+impl Decode for SubscribeUpdate {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let start_group = VarInt::decode(r).await?;
+		let start_object = VarInt::decode(r).await?;
+		let end_group = VarInt::decode(r).await?;
+		let priority = u8::decode(r).await?;
+		let params = Params::decode(r).await?;
+
+		Ok(Self {
+			id,
+			start_group,
+			start_object,
+			end_group,
+			priority,
+			params,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for SubscribeUpdate {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await?;
+		self.start_group.encode(w).await?;
+		self.start_object.encode(w).await?;
+		self.end_group.encode(w).await?;
+		self.priority.encode(w).await?;
+		self.params.encode(w).await?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,258 @@
+use proptest::prelude::*;
+
+use super::*;
+use crate::coding::Params;
+
+fn block_on<F: std::future::Future>(f: F) -> F::Output {
+	tokio::runtime::Builder::new_current_thread()
+		.build()
+		.unwrap()
+		.block_on(f)
+}
+
+fn varint() -> impl Strategy<Value = VarInt> {
+	(0..=VarInt::MAX.into_inner()).prop_map(|v| VarInt::try_from(v).unwrap())
+}
+
+fn params() -> impl Strategy<Value = Params> {
+	prop::collection::hash_map(varint(), prop::collection::vec(any::<u8>(), 0..16), 0..4).prop_map(Params)
+}
+
+fn namespace() -> impl Strategy<Value = TrackNamespace> {
+	prop::collection::vec(any::<String>(), 0..4).prop_map(TrackNamespace)
+}
+
+fn location() -> impl Strategy<Value = Location> {
+	(varint(), varint()).prop_map(|(group, object)| Location { group, object })
+}
+
+fn group_order() -> impl Strategy<Value = GroupOrder> {
+	prop_oneof![
+		Just(GroupOrder::Publisher),
+		Just(GroupOrder::Ascending),
+		Just(GroupOrder::Descending),
+	]
+}
+
+fn filter() -> impl Strategy<Value = FilterType> {
+	prop_oneof![
+		Just(FilterType::LatestGroup),
+		Just(FilterType::LatestObject),
+		(varint(), varint()).prop_map(|(group, object)| FilterType::AbsoluteStart { group, object }),
+		(varint(), varint(), varint()).prop_map(|(start_group, start_object, end_group)| {
+			FilterType::AbsoluteRange {
+				start_group,
+				start_object,
+				end_group,
+			}
+		}),
+	]
+}
+
+fn status() -> impl Strategy<Value = ObjectStatus> {
+	prop_oneof![
+		Just(ObjectStatus::Normal),
+		Just(ObjectStatus::DoesNotExist),
+		Just(ObjectStatus::EndOfGroup),
+		Just(ObjectStatus::EndOfTrackAndGroup),
+		Just(ObjectStatus::EndOfTrack),
+	]
+}
+
+// Generate a size and status, where only empty objects carry a status.
+fn sized() -> impl Strategy<Value = (VarInt, ObjectStatus)> {
+	prop_oneof![
+		(1..=VarInt::MAX.into_inner()).prop_map(|size| (VarInt::try_from(size).unwrap(), ObjectStatus::Normal)),
+		status().prop_map(|status| (VarInt::ZERO, status)),
+	]
+}
+
+fn subscribe() -> impl Strategy<Value = Message> {
+	(
+		varint(),
+		varint(),
+		namespace(),
+		any::<String>(),
+		any::<u8>(),
+		group_order(),
+		filter(),
+		params(),
+	)
+		.prop_map(
+			|(id, track_alias, namespace, name, priority, group_order, filter, params)| {
+				Subscribe {
+					id,
+					track_alias,
+					namespace,
+					name,
+					priority,
+					group_order,
+					filter,
+					params,
+				}
+				.into()
+			},
+		)
+}
+
+fn fetch() -> impl Strategy<Value = Message> {
+	(
+		varint(),
+		namespace(),
+		any::<String>(),
+		any::<u8>(),
+		group_order(),
+		location(),
+		location(),
+		params(),
+	)
+		.prop_map(|(id, namespace, name, priority, group_order, start, end, params)| {
+			Fetch {
+				id,
+				namespace,
+				name,
+				priority,
+				group_order,
+				start,
+				end,
+				params,
+			}
+			.into()
+		})
+}
+
+fn message() -> impl Strategy<Value = Message> {
+	prop_oneof![
+		(varint(), varint(), varint(), varint(), any::<u8>(), params()).prop_map(
+			|(id, start_group, start_object, end_group, priority, params)| SubscribeUpdate {
+				id,
+				start_group,
+				start_object,
+				end_group,
+				priority,
+				params,
+			}
+			.into()
+		),
+		subscribe(),
+		varint().prop_map(|id| Unsubscribe { id }.into()),
+		(
+			varint(),
+			varint(),
+			group_order(),
+			prop::option::of(location()),
+			params()
+		)
+			.prop_map(|(id, expires, group_order, largest, params)| SubscribeOk {
+				id,
+				expires,
+				group_order,
+				largest,
+				params,
+			}
+			.into()),
+		(varint(), any::<u32>(), any::<String>(), varint()).prop_map(|(id, code, reason, track_alias)| {
+			SubscribeError {
+				id,
+				code,
+				reason,
+				track_alias,
+			}
+			.into()
+		}),
+		(varint(), any::<u32>(), any::<String>(), prop::option::of(location()))
+			.prop_map(|(id, code, reason, last)| SubscribeDone { id, code, reason, last }.into()),
+		(namespace(), params()).prop_map(|(namespace, params)| Announce { namespace, params }.into()),
+		namespace().prop_map(|namespace| Unannounce { namespace }.into()),
+		namespace().prop_map(|namespace| AnnounceOk { namespace }.into()),
+		(namespace(), any::<u32>(), any::<String>()).prop_map(|(namespace, code, reason)| AnnounceError {
+			namespace,
+			code,
+			reason
+		}
+		.into()),
+		(namespace(), any::<u32>(), any::<String>()).prop_map(|(namespace, code, reason)| AnnounceCancel {
+			namespace,
+			code,
+			reason
+		}
+		.into()),
+		fetch(),
+		varint().prop_map(|id| FetchCancel { id }.into()),
+		(varint(), group_order(), any::<bool>(), location(), params()).prop_map(
+			|(id, group_order, end_of_track, largest, params)| FetchOk {
+				id,
+				group_order,
+				end_of_track,
+				largest,
+				params,
+			}
+			.into()
+		),
+		(varint(), any::<u32>(), any::<String>()).prop_map(|(id, code, reason)| FetchError { id, code, reason }.into()),
+		any::<String>().prop_map(|url| GoAway { url }.into()),
+		varint().prop_map(|id| MaxSubscribeId { id }.into()),
+	]
+}
+
+fn stream_header() -> impl Strategy<Value = StreamHeader> {
+	prop_oneof![
+		(varint(), varint(), varint(), any::<u8>()).prop_map(|(track_alias, group, subgroup, priority)| {
+			StreamHeader::Subgroup(SubgroupHeader {
+				track_alias,
+				group,
+				subgroup,
+				priority,
+			})
+		}),
+		varint().prop_map(|id| StreamHeader::Fetch(FetchHeader { id })),
+	]
+}
+
+fn round_trip<T: Encode + Decode>(value: &T) -> (T, usize, usize) {
+	let mut buf = Vec::new();
+	block_on(value.encode(&mut buf)).unwrap();
+
+	let mut cursor = io::Cursor::new(&buf);
+	let decoded = block_on(T::decode(&mut cursor)).unwrap();
+	(decoded, cursor.position() as usize, buf.len())
+}
+
+proptest! {
+	#[test]
+	fn message_round_trip(msg in message()) {
+		let (decoded, read, size) = round_trip(&msg);
+		prop_assert_eq!(decoded, msg);
+		prop_assert_eq!(read, size);
+	}
+
+	#[test]
+	fn stream_header_round_trip(header in stream_header()) {
+		let (decoded, read, size) = round_trip(&header);
+		prop_assert_eq!(decoded, header);
+		prop_assert_eq!(read, size);
+	}
+
+	#[test]
+	fn subgroup_object_round_trip(id in varint(), (size, status) in sized()) {
+		let object = SubgroupObject { id, size, status };
+		let (decoded, read, len) = round_trip(&object);
+		prop_assert_eq!(decoded, object);
+		prop_assert_eq!(read, len);
+	}
+
+	#[test]
+	fn fetch_object_round_trip(group in varint(), subgroup in varint(), id in varint(), priority in any::<u8>(), (size, status) in sized()) {
+		let object = FetchObject { group, subgroup, id, priority, size, status };
+		let (decoded, read, len) = round_trip(&object);
+		prop_assert_eq!(decoded, object);
+		prop_assert_eq!(read, len);
+	}
+
+	#[test]
+	fn decode_arbitrary(buf in prop::collection::vec(any::<u8>(), 0..256)) {
+		// Garbage may fail to decode, but it must never panic.
+		let _ = block_on(Message::decode(&mut io::Cursor::new(&buf)));
+		let _ = block_on(StreamHeader::decode(&mut io::Cursor::new(&buf)));
+	}
+}
@@ -0,0 +1,26 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError};
+
+use super::TrackNamespace;
+
+/// Sent by the publisher to terminate an Announce.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unannounce {
+	// Echo back the namespace that was announced.
+	pub namespace: TrackNamespace,
+}
+
+#[async_trait::async_trait]
+impl Decode for Unannounce {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let namespace = TrackNamespace::decode(r).await?;
+		Ok(Self { namespace })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for Unannounce {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.namespace.encode(w).await
+	}
+}
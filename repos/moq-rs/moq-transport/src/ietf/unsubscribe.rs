@@ -0,0 +1,24 @@
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+/// Sent by the subscriber to terminate a Subscribe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unsubscribe {
+	/// The ID for this subscription.
+	pub id: VarInt,
+}
+
+#[async_trait::async_trait]
+impl Decode for Unsubscribe {
+	async fn decode<R: AsyncRead>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		Ok(Self { id })
+	}
+}
+
+#[async_trait::async_trait]
+impl Encode for Unsubscribe {
+	async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
+		self.id.encode(w).await
+	}
+}
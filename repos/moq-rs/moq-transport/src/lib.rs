@@ -10,9 +10,11 @@ mod coding;
 mod error;
 
 pub mod cache;
+pub mod ietf;
 pub mod message;
 pub mod session;
 pub mod setup;
+pub mod transport;
 
 pub use coding::VarInt;
 pub use error::MoqError;
@@ -4,7 +4,7 @@ use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
 /// Sent by the publisher to announce the availability of a group of tracks.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Announce {
 	/// The track namespace
 	pub namespace: String,
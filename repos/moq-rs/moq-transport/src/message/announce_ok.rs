@@ -4,7 +4,7 @@ use crate::{
 };
 
 /// Sent by the subscriber to accept an Announce.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AnnounceOk {
 	// Echo back the namespace that was announced.
 	// TODO Propose using an ID to save bytes.
@@ -4,7 +4,7 @@ use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
 /// Sent by the subscriber to reject an Announce.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct AnnounceError {
 	// Echo back the namespace that was reset
 	pub namespace: String,
@@ -4,7 +4,7 @@ use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
 /// Sent by the server to indicate that the client should connect to a different server.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GoAway {
 	pub url: String,
 }
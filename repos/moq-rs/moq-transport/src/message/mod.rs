@@ -2,7 +2,8 @@
 //!
 //! All of these messages are sent over a bidirectional QUIC stream.
 //! This introduces some head-of-line blocking but preserves ordering.
-//! The only exception are OBJECT "messages", which are sent over dedicated QUIC streams.
+//! The only exception are OBJECT "messages", which are sent over dedicated QUIC streams,
+//! or as datagrams when negotiated with the datagram extension and requested with [SUBSCRIBE_DATAGRAM].
 //!
 //! Messages sent by the publisher:
 //! - [Announce]
@@ -10,6 +11,7 @@
 //! - [SubscribeOk]
 //! - [SubscribeError]
 //! - [SubscribeReset]
+//! - [ProbeOk]
 //! - [Object]
 //!
 //! Messages sent by the subscriber:
@@ -17,6 +19,7 @@
 //! - [Unsubscribe]
 //! - [AnnounceOk]
 //! - [AnnounceError]
+//! - [Probe]
 //!
 //! Example flow:
 //! ```test
@@ -35,6 +38,8 @@ mod announce_ok;
 mod announce_reset;
 mod go_away;
 mod object;
+mod probe;
+mod probe_ok;
 mod subscribe;
 mod subscribe_error;
 mod subscribe_fin;
@@ -42,12 +47,18 @@ mod subscribe_ok;
 mod subscribe_reset;
 mod unannounce;
 mod unsubscribe;
+mod validate;
+
+#[cfg(test)]
+mod tests;
 
 pub use announce::*;
 pub use announce_ok::*;
 pub use announce_reset::*;
 pub use go_away::*;
 pub use object::*;
+pub use probe::*;
+pub use probe_ok::*;
 pub use subscribe::*;
 pub use subscribe_error::*;
 pub use subscribe_fin::*;
@@ -55,10 +66,11 @@ pub use subscribe_ok::*;
 pub use subscribe_reset::*;
 pub use unannounce::*;
 pub use unsubscribe::*;
+pub use validate::*;
 
-use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+use crate::coding::{decode_now, Decode, DecodeError, Encode, EncodeError, VarInt};
 
-use std::fmt;
+use std::{fmt, io};
 
 use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
@@ -68,7 +80,7 @@ use crate::setup::Extensions;
 macro_rules! message_types {
     {$($name:ident = $val:expr,)*} => {
 		/// All supported message types.
-		#[derive(Clone)]
+		#[derive(Clone, PartialEq, Eq)]
 		pub enum Message {
 			$($name($name)),*
 		}
@@ -89,6 +101,15 @@ macro_rules! message_types {
 				}
 			}
 
+			/// Decode a message from a buffer, returning the number of bytes consumed.
+			///
+			/// This doesn't require an async runtime, so it's suitable for fuzzing.
+			pub fn decode_slice(buf: &[u8], ext: &Extensions) -> Result<(Self, usize), DecodeError> {
+				let mut cursor = io::Cursor::new(buf);
+				let msg = decode_now(Self::decode(&mut cursor, ext))?;
+				Ok((msg, cursor.position() as usize))
+			}
+
 			pub async fn encode<W: AsyncWrite>(&self, w: &mut W, ext: &Extensions) -> Result<(), EncodeError> {
 				match self {
 					$(Self::$name(ref m) => {
@@ -98,6 +119,13 @@ macro_rules! message_types {
 				}
 			}
 
+			/// Check that the message can be encoded with the negotiated extensions.
+			pub fn validate(&self, ext: &Extensions) -> Result<(), EncodeError> {
+				match self {
+					$(Self::$name(ref m) => m.validate(ext),)*
+				}
+			}
+
 			pub fn id(&self) -> VarInt {
 				match self {
 					$(Self::$name(_) => {
@@ -160,4 +188,8 @@ message_types! {
 
 	// Misc
 	GoAway = 0x10,
+
+	// PROBE family, requires the probe extension
+	Probe = 0x20,
+	ProbeOk = 0x21,
 }
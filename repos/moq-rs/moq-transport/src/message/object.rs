@@ -6,8 +6,10 @@ use crate::coding::{AsyncRead, AsyncWrite};
 use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
 use crate::setup;
 
+use super::Validate;
+
 /// Sent by the publisher as the header of each data stream.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Object {
 	// An ID for this track.
 	// Proposal: https://github.com/moq-wg/moq-transport/issues/209
@@ -86,6 +88,8 @@ impl Object {
 	}
 
 	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, extensions: &setup::Extensions) -> Result<(), EncodeError> {
+		self.validate(extensions)?;
+
 		// The kind changes based on the presence of the size.
 		let kind = match self.size {
 			Some(_) => VarInt::from_u32(2),
@@ -107,7 +111,6 @@ impl Object {
 		// Round up if there's any decimal points.
 		let expires = match self.expires {
 			None => 0,
-			Some(time::Duration::ZERO) => return Err(EncodeError::InvalidValue), // there's no way of expressing zero currently.
 			Some(expires) if expires.subsec_nanos() > 0 => expires.as_secs() + 1,
 			Some(expires) => expires.as_secs(),
 		};
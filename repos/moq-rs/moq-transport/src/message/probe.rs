@@ -0,0 +1,60 @@
+use std::time;
+
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::setup::Extensions;
+
+use super::Validate;
+
+/// Sent by the subscriber to measure the available bandwidth.
+///
+/// The publisher responds with `count` OBJECTs of `size` bytes, each on a new data stream using `id` as the track.
+/// Requires the `probe` extension.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+	/// An ID we choose, shared with the SUBSCRIBE ID space.
+	pub id: VarInt,
+
+	/// The size of each OBJECT payload.
+	pub size: VarInt,
+
+	/// The priority, where **smaller** values are sent first.
+	pub priority: u32,
+
+	/// The number of OBJECTs to send.
+	pub count: VarInt,
+
+	/// The delay between each OBJECT, encoded in milliseconds.
+	pub pacing: time::Duration,
+}
+
+impl Probe {
+	pub async fn decode<R: AsyncRead>(r: &mut R, _ext: &Extensions) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+		let size = VarInt::decode(r).await?;
+		let priority = VarInt::decode(r).await?.try_into()?;
+		let count = VarInt::decode(r).await?;
+		let pacing = time::Duration::from_millis(VarInt::decode(r).await?.into_inner());
+
+		Ok(Self {
+			id,
+			size,
+			priority,
+			count,
+			pacing,
+		})
+	}
+
+	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, ext: &Extensions) -> Result<(), EncodeError> {
+		self.validate(ext)?;
+
+		self.id.encode(w).await?;
+		self.size.encode(w).await?;
+		VarInt::from_u32(self.priority).encode(w).await?;
+		self.count.encode(w).await?;
+		VarInt::try_from(self.pacing.as_millis())?.encode(w).await?;
+
+		Ok(())
+	}
+}
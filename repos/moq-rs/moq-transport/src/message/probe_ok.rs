@@ -0,0 +1,51 @@
+use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
+
+use crate::coding::{AsyncRead, AsyncWrite};
+use crate::setup::Extensions;
+
+use super::Validate;
+
+/// Sent by the publisher after every OBJECT requested by a Probe was written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProbeOk {
+	/// The ID of the Probe.
+	pub id: VarInt,
+
+	/// When each OBJECT started sending, in milliseconds since the Unix epoch.
+	pub sent: Vec<VarInt>,
+}
+
+impl ProbeOk {
+	/// The most OBJECTs a single PROBE can request, bounding the size of the PROBE_OK.
+	pub const MAX_SENT: u64 = 1024;
+
+	pub async fn decode<R: AsyncRead>(r: &mut R, _ext: &Extensions) -> Result<Self, DecodeError> {
+		let id = VarInt::decode(r).await?;
+
+		// Don't let the publisher make us allocate an arbitrary amount.
+		let count = VarInt::decode(r).await?.into_inner();
+		if count > Self::MAX_SENT {
+			return Err(DecodeError::InvalidLength);
+		}
+
+		let mut sent = Vec::new();
+		for _ in 0..count {
+			sent.push(VarInt::decode(r).await?);
+		}
+
+		Ok(Self { id, sent })
+	}
+
+	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, ext: &Extensions) -> Result<(), EncodeError> {
+		self.validate(ext)?;
+
+		self.id.encode(w).await?;
+
+		VarInt::try_from(self.sent.len())?.encode(w).await?;
+		for sent in &self.sent {
+			sent.encode(w).await?;
+		}
+
+		Ok(())
+	}
+}
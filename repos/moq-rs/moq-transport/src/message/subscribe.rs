@@ -1,12 +1,31 @@
-use crate::coding::{Decode, DecodeError, Encode, EncodeError, Params, VarInt};
+use std::{io, time};
+
+use crate::coding::{decode_now, Decode, DecodeError, Encode, EncodeError, Params, VarInt};
 
 use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
+use super::Validate;
+
+/// The SUBSCRIBE parameter asking for each OBJECT to be sent as a datagram, falling back to streams when it doesn't fit.
+///
+/// The value is empty, and the parameter is ignored unless the datagram extension was negotiated.
+pub const SUBSCRIBE_DATAGRAM: VarInt = VarInt::from_u32(0xe0119);
+
+/// The SUBSCRIBE parameter containing the subscriber's priority for the track, where **smaller** values are sent first.
+///
+/// The value is a single byte, matching the priority field in newer drafts.
+pub const SUBSCRIBE_PRIORITY: VarInt = VarInt::from_u32(0xe011a);
+
+/// The SUBSCRIBE parameter containing the maximum latency in milliseconds, after which a group is no longer useful.
+///
+/// The publisher drops any group older than this, instead of sending it late.
+pub const SUBSCRIBE_MAX_LATENCY: VarInt = VarInt::from_u32(0xe011b);
+
 /// Sent by the subscriber to request all future objects for the given track.
 ///
 /// Objects will use the provided ID instead of the full track name, to save bytes.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Subscribe {
 	/// An ID we choose so we can map to the track_name.
 	// Proposal: https://github.com/moq-wg/moq-transport/issues/209
@@ -34,6 +53,22 @@ pub struct Subscribe {
 }
 
 impl Subscribe {
+	/// The priority requested with [SUBSCRIBE_PRIORITY], if any.
+	pub fn priority(&self) -> Option<u8> {
+		match self.params.0.get(&SUBSCRIBE_PRIORITY)?.as_slice() {
+			[priority] => Some(*priority),
+			_ => None,
+		}
+	}
+
+	/// The maximum latency requested with [SUBSCRIBE_MAX_LATENCY], if any.
+	pub fn max_latency(&self) -> Option<time::Duration> {
+		let value = self.params.0.get(&SUBSCRIBE_MAX_LATENCY)?;
+		let millis = decode_now(VarInt::decode(&mut io::Cursor::new(value))).ok()?;
+
+		Some(time::Duration::from_millis(millis.into_inner()))
+	}
+
 	pub async fn decode<R: AsyncRead>(r: &mut R, ext: &Extensions) -> Result<Self, DecodeError> {
 		let id = VarInt::decode(r).await?;
 
@@ -68,7 +103,6 @@ impl Subscribe {
 		};
 		log::debug!("switch_track_id: {:?}", switch_track_id);
 
-
 		// NOTE: There's some more location restrictions in the draft, but they're enforced at a higher level.
 
 		let params = Params::decode(r).await?;
@@ -89,14 +123,12 @@ impl Subscribe {
 	}
 
 	pub async fn encode<W: AsyncWrite>(&self, w: &mut W, ext: &Extensions) -> Result<(), EncodeError> {
-		self.id.encode(w).await?;
+		self.validate(ext)?;
 
-		if self.namespace.is_some() != ext.subscribe_split {
-			panic!("namespace must be None if subscribe_split is false");
-		}
+		self.id.encode(w).await?;
 
-		if ext.subscribe_split {
-			self.namespace.as_ref().unwrap().encode(w).await?;
+		if let Some(namespace) = &self.namespace {
+			namespace.encode(w).await?;
 		}
 
 		self.name.encode(w).await?;
@@ -106,12 +138,8 @@ impl Subscribe {
 		self.end_group.encode(w).await?;
 		self.end_object.encode(w).await?;
 
-		if self.switch_track_id.is_some() != ext.switch_track_id {
-			panic!("switch_track_id must be None if subscribe_switch_track is false");
-		}
-
-		if ext.switch_track_id {
-			self.switch_track_id.as_ref().unwrap().encode(w).await?;
+		if let Some(switch_track_id) = &self.switch_track_id {
+			switch_track_id.encode(w).await?;
 		}
 
 		self.params.encode(w).await?;
@@ -121,7 +149,7 @@ impl Subscribe {
 }
 
 /// Signal where the subscription should begin, relative to the current cache.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SubscribeLocation {
 	None,
 	Absolute(VarInt),
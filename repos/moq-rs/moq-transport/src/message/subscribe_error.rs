@@ -3,7 +3,7 @@ use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
 use crate::setup::Extensions;
 
 /// Sent by the publisher to reject a Subscribe.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SubscribeError {
 	// NOTE: No full track name because of this proposal: https://github.com/moq-wg/moq-transport/issues/209
 
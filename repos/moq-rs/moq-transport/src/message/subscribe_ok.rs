@@ -4,7 +4,7 @@ use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
 /// Sent by the publisher to accept a Subscribe.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SubscribeOk {
 	// NOTE: No full track name because of this proposal: https://github.com/moq-wg/moq-transport/issues/209
 	/// The ID for this track.
@@ -3,7 +3,7 @@ use crate::coding::{Decode, DecodeError, Encode, EncodeError, VarInt};
 use crate::setup::Extensions;
 
 /// Sent by the publisher to terminate a Subscribe.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SubscribeReset {
 	// NOTE: No full track name because of this proposal: https://github.com/moq-wg/moq-transport/issues/209
 	/// The ID for this subscription.
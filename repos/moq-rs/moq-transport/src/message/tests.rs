@@ -0,0 +1,237 @@
+use std::time;
+
+use proptest::prelude::*;
+
+use super::*;
+use crate::coding::Params;
+use crate::setup::Extensions;
+
+fn block_on<F: std::future::Future>(f: F) -> F::Output {
+	tokio::runtime::Builder::new_current_thread()
+		.build()
+		.unwrap()
+		.block_on(f)
+}
+
+fn varint() -> impl Strategy<Value = VarInt> {
+	(0..=VarInt::MAX.into_inner()).prop_map(|v| VarInt::try_from(v).unwrap())
+}
+
+fn params() -> impl Strategy<Value = Params> {
+	prop::collection::hash_map(varint(), prop::collection::vec(any::<u8>(), 0..16), 0..4).prop_map(Params)
+}
+
+fn extensions() -> impl Strategy<Value = Extensions> {
+	any::<[bool; 7]>().prop_map(
+		|[object_expires, subscriber_id, subscribe_split, ntp_timestamp, switch_track_id, probe, datagram]| {
+			Extensions {
+				object_expires,
+				subscriber_id,
+				subscribe_split,
+				ntp_timestamp,
+				switch_track_id,
+				probe,
+				datagram,
+			}
+		},
+	)
+}
+
+fn location() -> impl Strategy<Value = SubscribeLocation> {
+	prop_oneof![
+		Just(SubscribeLocation::None),
+		varint().prop_map(SubscribeLocation::Absolute),
+		varint().prop_map(SubscribeLocation::Latest),
+		varint().prop_map(SubscribeLocation::Future),
+	]
+}
+
+// Generate a pair of locations, where the object is None if the group is None.
+fn locations() -> impl Strategy<Value = (SubscribeLocation, SubscribeLocation)> {
+	(location(), location()).prop_map(|(group, object)| match group {
+		SubscribeLocation::None => (group, SubscribeLocation::None),
+		_ => (group, object),
+	})
+}
+
+fn subscribe(ext: &Extensions) -> impl Strategy<Value = Subscribe> {
+	let namespace = match ext.subscribe_split {
+		true => any::<String>().prop_map(Some).boxed(),
+		false => Just(None).boxed(),
+	};
+
+	let switch_track_id = match ext.switch_track_id {
+		true => varint().prop_map(Some).boxed(),
+		false => Just(None).boxed(),
+	};
+
+	(
+		varint(),
+		namespace,
+		any::<String>(),
+		locations(),
+		locations(),
+		switch_track_id,
+		params(),
+	)
+		.prop_map(
+			|(id, namespace, name, (start_group, start_object), (end_group, end_object), switch_track_id, params)| {
+				Subscribe {
+					id,
+					namespace,
+					name,
+					start_group,
+					start_object,
+					end_group,
+					end_object,
+					switch_track_id,
+					params,
+				}
+			},
+		)
+}
+
+// PROBE messages can only be encoded when the extension was negotiated.
+fn probe(ext: &Extensions) -> BoxedStrategy<Message> {
+	if !ext.probe {
+		return any::<String>().prop_map(|url| GoAway { url }.into()).boxed();
+	}
+
+	prop_oneof![
+		(varint(), varint(), any::<u32>(), varint(), 0..VarInt::MAX.into_inner()).prop_map(
+			|(id, size, priority, count, pacing)| Probe {
+				id,
+				size,
+				priority,
+				count,
+				pacing: time::Duration::from_millis(pacing),
+			}
+			.into()
+		),
+		(varint(), prop::collection::vec(varint(), 0..8)).prop_map(|(id, sent)| ProbeOk { id, sent }.into()),
+	]
+	.boxed()
+}
+
+fn message(ext: &Extensions) -> impl Strategy<Value = Message> {
+	prop_oneof![
+		subscribe(ext).prop_map(Message::from),
+		varint().prop_map(|id| Unsubscribe { id }.into()),
+		(varint(), varint()).prop_map(|(id, expires)| SubscribeOk { id, expires }.into()),
+		(varint(), any::<u32>(), any::<String>())
+			.prop_map(|(id, code, reason)| SubscribeError { id, code, reason }.into()),
+		(varint(), varint(), varint()).prop_map(|(id, final_group, final_object)| SubscribeFin {
+			id,
+			final_group,
+			final_object
+		}
+		.into()),
+		(varint(), any::<u32>(), any::<String>(), varint(), varint()).prop_map(
+			|(id, code, reason, final_group, final_object)| SubscribeReset {
+				id,
+				code,
+				reason,
+				final_group,
+				final_object
+			}
+			.into()
+		),
+		(any::<String>(), params()).prop_map(|(namespace, params)| Announce { namespace, params }.into()),
+		any::<String>().prop_map(|namespace| Unannounce { namespace }.into()),
+		any::<String>().prop_map(|namespace| AnnounceOk { namespace }.into()),
+		(any::<String>(), any::<u32>(), any::<String>()).prop_map(|(namespace, code, reason)| AnnounceError {
+			namespace,
+			code,
+			reason
+		}
+		.into()),
+		any::<String>().prop_map(|url| GoAway { url }.into()),
+		probe(ext),
+	]
+}
+
+fn object(ext: &Extensions) -> impl Strategy<Value = Object> {
+	// Expires is encoded in whole seconds, where zero means never.
+	let expires = match ext.object_expires {
+		true => prop::option::of((1..u32::MAX as u64).prop_map(time::Duration::from_secs)).boxed(),
+		false => Just(None).boxed(),
+	};
+
+	let ntp_timestamp = match ext.ntp_timestamp {
+		true => varint().prop_map(Some).boxed(),
+		false => Just(None).boxed(),
+	};
+
+	(
+		varint(),
+		varint(),
+		varint(),
+		any::<u32>(),
+		expires,
+		prop::option::of(varint()),
+		ntp_timestamp,
+	)
+		.prop_map(
+			|(track, group, sequence, priority, expires, size, ntp_timestamp)| Object {
+				track,
+				group,
+				sequence,
+				priority,
+				expires,
+				size,
+				ntp_timestamp,
+			},
+		)
+}
+
+fn with_extensions<S: Strategy, F: Fn(&Extensions) -> S>(f: F) -> impl Strategy<Value = (Extensions, S::Value)> {
+	extensions().prop_flat_map(move |ext| (Just(ext.clone()), f(&ext)))
+}
+
+proptest! {
+	#[test]
+	fn message_round_trip((ext, msg) in with_extensions(message)) {
+		let mut buf = Vec::new();
+		block_on(msg.encode(&mut buf, &ext)).unwrap();
+
+		let (decoded, size) = Message::decode_slice(&buf, &ext).unwrap();
+		prop_assert_eq!(decoded, msg);
+		prop_assert_eq!(size, buf.len());
+	}
+
+	#[test]
+	fn object_round_trip((ext, object) in with_extensions(object)) {
+		let mut buf = Vec::new();
+		block_on(object.encode(&mut buf, &ext)).unwrap();
+
+		let decoded = block_on(Object::decode(&mut std::io::Cursor::new(&buf), &ext)).unwrap();
+		prop_assert_eq!(decoded, object);
+	}
+
+	#[test]
+	fn decode_arbitrary(ext in extensions(), buf in prop::collection::vec(any::<u8>(), 0..256)) {
+		// Garbage may fail to decode, but it must never panic.
+		let _ = Message::decode_slice(&buf, &ext);
+	}
+
+	#[test]
+	fn subscribe_mismatched_extensions((ext, msg) in with_extensions(subscribe)) {
+		let mut ext = ext;
+		ext.subscribe_split = !ext.subscribe_split;
+
+		let mut buf = Vec::new();
+		let res = block_on(msg.encode(&mut buf, &ext));
+		prop_assert!(matches!(res, Err(EncodeError::MissingField("namespace") | EncodeError::UnexpectedField("namespace"))));
+	}
+
+	#[test]
+	fn probe_requires_extension((ext, msg) in with_extensions(probe)) {
+		let mut ext = ext;
+		let expected = ext.probe;
+		ext.probe = false;
+
+		let mut buf = Vec::new();
+		let res = block_on(msg.encode(&mut buf, &ext));
+		prop_assert_eq!(matches!(res, Err(EncodeError::MissingExtension("probe"))), expected);
+	}
+}
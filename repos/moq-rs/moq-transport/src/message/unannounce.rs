@@ -4,7 +4,7 @@ use crate::coding::{AsyncRead, AsyncWrite};
 use crate::setup::Extensions;
 
 /// Sent by the publisher to terminate an Announce.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Unannounce {
 	// Echo back the namespace that was reset
 	pub namespace: String,
@@ -0,0 +1,91 @@
+use crate::coding::EncodeError;
+use crate::setup::Extensions;
+
+use super::*;
+
+/// Check that a message can be encoded with the negotiated extensions.
+///
+/// Some fields are only encoded when an extension is negotiated, so their presence must agree with [Extensions].
+/// This is called automatically when encoding, but can be used to reject a message before it's queued.
+pub trait Validate {
+	fn validate(&self, _ext: &Extensions) -> Result<(), EncodeError> {
+		Ok(())
+	}
+}
+
+impl Validate for Subscribe {
+	fn validate(&self, ext: &Extensions) -> Result<(), EncodeError> {
+		match (self.namespace.is_some(), ext.subscribe_split) {
+			(false, true) => return Err(EncodeError::MissingField("namespace")),
+			(true, false) => return Err(EncodeError::UnexpectedField("namespace")),
+			_ => {}
+		}
+
+		match (self.switch_track_id.is_some(), ext.switch_track_id) {
+			(false, true) => return Err(EncodeError::MissingField("switch_track_id")),
+			(true, false) => return Err(EncodeError::UnexpectedField("switch_track_id")),
+			_ => {}
+		}
+
+		// You can't have a start object without a start group, or an end object without an end group.
+		if self.start_group == SubscribeLocation::None && self.start_object != SubscribeLocation::None {
+			return Err(EncodeError::InvalidSubscribeLocation);
+		}
+
+		if self.end_group == SubscribeLocation::None && self.end_object != SubscribeLocation::None {
+			return Err(EncodeError::InvalidSubscribeLocation);
+		}
+
+		Ok(())
+	}
+}
+
+impl Validate for Object {
+	fn validate(&self, ext: &Extensions) -> Result<(), EncodeError> {
+		// The timestamp is optional in the struct but not on the wire.
+		if ext.ntp_timestamp && self.ntp_timestamp.is_none() {
+			return Err(EncodeError::MissingField("ntp_timestamp"));
+		}
+
+		// There's no way of expressing zero, since it means never expire.
+		if ext.object_expires && self.expires == Some(std::time::Duration::ZERO) {
+			return Err(EncodeError::InvalidValue);
+		}
+
+		Ok(())
+	}
+}
+
+impl Validate for Probe {
+	fn validate(&self, ext: &Extensions) -> Result<(), EncodeError> {
+		match ext.probe {
+			true => Ok(()),
+			false => Err(EncodeError::MissingExtension("probe")),
+		}
+	}
+}
+
+impl Validate for ProbeOk {
+	fn validate(&self, ext: &Extensions) -> Result<(), EncodeError> {
+		if !ext.probe {
+			return Err(EncodeError::MissingExtension("probe"));
+		}
+
+		match self.sent.len() as u64 <= Self::MAX_SENT {
+			true => Ok(()),
+			false => Err(EncodeError::InvalidValue),
+		}
+	}
+}
+
+// These messages don't depend on any extensions.
+impl Validate for Announce {}
+impl Validate for AnnounceOk {}
+impl Validate for AnnounceError {}
+impl Validate for GoAway {}
+impl Validate for SubscribeError {}
+impl Validate for SubscribeFin {}
+impl Validate for SubscribeOk {}
+impl Validate for SubscribeReset {}
+impl Validate for Unannounce {}
+impl Validate for Unsubscribe {}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+use crate::{cache::broadcast, VarInt};
+
+use super::Stats;
+
+/// The state of a subscription when the first segment of a new group arrives, used to pick the track to serve.
+#[derive(Debug)]
+pub struct AbrContext<'a> {
+	/// The subscribe ID chosen by the remote subscriber.
+	pub id: VarInt,
+
+	/// The broadcast serving the subscription, which contains the other renditions.
+	pub broadcast: &'a broadcast::Subscriber,
+
+	/// The name of the track in the SUBSCRIBE.
+	pub requested: &'a str,
+
+	/// The name of the track currently served, which differs from the requested track after a switch.
+	pub current: &'a str,
+
+	/// The sequence number of the new group.
+	pub group: VarInt,
+
+	/// A snapshot of the session's transport metrics.
+	pub stats: &'a Stats,
+}
+
+/// Switches the track served by a [Publisher](super::Publisher) subscription between renditions of the same content.
+///
+/// The new track takes over from the group being decided onwards, like a SUBSCRIBE with `switch_track_id`.
+/// The renditions must use aligned group sequence numbers and a shared init segment, so the subscriber can keep decoding.
+pub trait AbrPolicy: Send + Sync + fmt::Debug {
+	/// Return the name of the track to serve from this group onwards, or None to keep the current track.
+	fn select(&self, ctx: &AbrContext) -> Option<String>;
+
+	/// Called once the subscription ends, so any per-subscription state can be removed.
+	fn finish(&self, _id: VarInt) {}
+}
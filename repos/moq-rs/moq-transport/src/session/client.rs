@@ -1,41 +1,98 @@
-use super::{Control, Publisher, SessionError, Subscriber};
-use crate::{cache::broadcast, setup};
-use webtransport_quinn::Session;
+use super::{Control, PubSub, Publisher, SessionError, Subscriber};
+use std::sync::Arc;
+
+use crate::{cache::broadcast, coding::Params, setup, transport, VarInt};
 
 /// An endpoint that connects to a URL to publish and/or consume live streams.
-pub struct Client {}
+#[derive(Clone, Debug)]
+pub struct Client {
+	// The versions offered during the handshake, in preferred order.
+	versions: setup::Versions,
+
+	// The path sent during the handshake, only used directly over QUIC.
+	path: Option<String>,
+}
 
 impl Client {
+	/// Offer the given versions during the handshake.
+	///
+	/// The first version determines the wire format of the handshake, see [setup::Version::is_ietf].
+	pub fn new(versions: setup::Versions) -> Self {
+		Self { versions, path: None }
+	}
+
+	/// Send the path of a `moqt://` URL during the handshake, see [transport::quic].
+	///
+	/// WebTransport sessions don't need this since the path is part of the CONNECT request.
+	pub fn with_path(mut self, path: &str) -> Self {
+		self.path = Some(path.to_string());
+		self
+	}
+
+	/// Send the path and query of the URL during the handshake if it's `moqt://`, see [Client::with_path].
+	pub fn with_url(self, url: &url::Url) -> Self {
+		match url.scheme() {
+			transport::quic::SCHEME => self.with_path(&url[url::Position::BeforePath..url::Position::AfterQuery]),
+			_ => self,
+		}
+	}
+
+	/// Offer the newer IETF draft instead of the fork, to interoperate with other implementations.
+	pub fn ietf() -> Self {
+		Self::new([setup::Version::DRAFT_07].into())
+	}
+
 	/// Connect using an established WebTransport session, performing the MoQ handshake as a publisher.
-	pub async fn publisher(session: Session, source: broadcast::Subscriber) -> Result<Publisher, SessionError> {
-		let control = Self::send_setup(&session, setup::Role::Publisher).await?;
+	pub async fn publisher<S: transport::Session>(
+		&self,
+		session: S,
+		source: broadcast::Subscriber,
+	) -> Result<Publisher, SessionError> {
+		let session = Arc::new(session);
+		let control = self.send_setup(&*session, setup::Role::Publisher).await?;
 		let publisher = Publisher::new(session, control, source);
 		Ok(publisher)
 	}
 
 	/// Connect using an established WebTransport session, performing the MoQ handshake as a subscriber.
-	pub async fn subscriber(session: Session, source: broadcast::Publisher) -> Result<Subscriber, SessionError> {
-		let control = Self::send_setup(&session, setup::Role::Subscriber).await?;
+	pub async fn subscriber<S: transport::Session>(
+		&self,
+		session: S,
+		source: broadcast::Publisher,
+	) -> Result<Subscriber, SessionError> {
+		let session = Arc::new(session);
+		let control = self.send_setup(&*session, setup::Role::Subscriber).await?;
 		let subscriber = Subscriber::new(session, control, source);
 		Ok(subscriber)
 	}
 
-	// TODO support performing both roles
-	/*
-		pub async fn connect(self) -> anyhow::Result<(Publisher, Subscriber)> {
-			self.connect_role(setup::Role::Both).await
-		}
-	*/
+	/// Connect using an established WebTransport session, performing the MoQ handshake as both a publisher and subscriber.
+	pub async fn pubsub<S: transport::Session>(
+		&self,
+		session: S,
+		publish: broadcast::Subscriber,
+		subscribe: broadcast::Publisher,
+	) -> Result<PubSub, SessionError> {
+		let session = Arc::new(session);
+		let control = self.send_setup(&*session, setup::Role::Both).await?;
+		let pubsub = PubSub::new(session, control, publish, subscribe);
+		Ok(pubsub)
+	}
 
-	async fn send_setup(session: &Session, role: setup::Role) -> Result<Control, SessionError> {
+	async fn send_setup(&self, session: &dyn transport::Session, role: setup::Role) -> Result<Control, SessionError> {
 		let mut control = session.open_bi().await?;
 
-		let versions: setup::Versions = [setup::Version::DRAFT_01, setup::Version::KIXEL_01].into();
+		let mut params = Params::default();
+		if self.versions.iter().any(setup::Version::is_ietf) {
+			// Newer drafts only allow SUBSCRIBE IDs below this limit, which defaults to zero.
+			params.set(setup::MAX_SUBSCRIBE_ID, VarInt::from_u32(u32::MAX)).await?;
+		}
 
 		let client = setup::Client {
 			role,
-			versions: versions.clone(),
-			params: Default::default(),
+			versions: self.versions.clone(),
+			path: self.path.clone(),
+			params,
 
 			// Offer all extensions
 			extensions: setup::Extensions {
@@ -44,6 +101,8 @@ impl Client {
 				subscribe_split: true,
 				ntp_timestamp: true,
 				switch_track_id: true,
+				probe: true,
+				datagram: true,
 			},
 		};
 
@@ -54,7 +113,18 @@ impl Client {
 
 		log::debug!("received server SETUP: {:?}", server);
 
+		if !self.versions.contains(&server.version) {
+			return Err(SessionError::Version(self.versions.clone(), [server.version].into()));
+		}
+
 		match server.version {
+			setup::Version::DRAFT_07 => {
+				// Newer drafts don't support extensions, but the namespace is always present.
+				server.extensions = setup::Extensions {
+					subscribe_split: true,
+					..Default::default()
+				}
+			}
 			setup::Version::DRAFT_01 => {
 				// We always require this extension
 				server.extensions.require_subscriber_id()?;
@@ -65,14 +135,25 @@ impl Client {
 				}
 			}
 			setup::Version::KIXEL_01 => {
-				// KIXEL_01 didn't support extensions; all were enabled.
-				server.extensions = client.extensions.clone()
+				// KIXEL_01 didn't support extensions; all were enabled except those added afterwards.
+				server.extensions = setup::Extensions {
+					probe: false,
+					datagram: false,
+					..client.extensions.clone()
+				}
 			}
-			_ => return Err(SessionError::Version(versions, [server.version].into())),
+			_ => return Err(SessionError::Version(self.versions.clone(), [server.version].into())),
 		}
 
-		let control = Control::new(control.0, control.1, server.extensions);
+		let control = Control::new(control.0, control.1, server.version, server.extensions);
 
 		Ok(control)
 	}
 }
+
+impl Default for Client {
+	/// Offer the fork versions, as used by the demo.
+	fn default() -> Self {
+		Self::new([setup::Version::DRAFT_01, setup::Version::KIXEL_01].into())
+	}
+}
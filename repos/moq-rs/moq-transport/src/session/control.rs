@@ -1,36 +1,86 @@
 // A helper class to guard sending control messages behind a Mutex.
 
-use std::{fmt, sync::Arc};
+use std::{collections::HashMap, fmt, sync::Arc};
 
-use tokio::sync::Mutex;
-use webtransport_quinn::{RecvStream, SendStream};
+use tokio::sync::{watch, Mutex};
 
-use super::SessionError;
-use crate::{message::Message, setup::Extensions};
+use super::{Counters, ObjectDecoder, ObjectEncoder, SessionError};
+use crate::{
+	coding::{Decode, Encode, EncodeError},
+	ietf,
+	message::{self, Message, SubscribeLocation},
+	setup::{Extensions, Version},
+	transport::{RecvStream, SendStream},
+	VarInt,
+};
+
+// The subscriber priority used by newer drafts when none was requested.
+const DEFAULT_PRIORITY: u8 = 0x80;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Control {
-	send: Arc<Mutex<SendStream>>,
-	recv: Arc<Mutex<RecvStream>>,
+	send: Arc<Mutex<Box<dyn SendStream>>>,
+	recv: Arc<Mutex<Box<dyn RecvStream>>>,
 	pub ext: Extensions,
+	pub version: Version,
+
+	// Track aliases chosen by the remote subscriber, keyed by subscribe ID.
+	// Only used by the IETF wire format; we always choose the subscribe ID as our alias.
+	aliases: Arc<std::sync::Mutex<HashMap<VarInt, VarInt>>>,
+
+	// Per-session counters, shared by the publisher and subscriber halves.
+	pub stats: Counters,
+
+	// The URL of the latest GOAWAY received, if any.
+	going_away: Arc<watch::Sender<Option<String>>>,
 }
 
 impl Control {
-	pub fn new(send: SendStream, recv: RecvStream, ext: Extensions) -> Self {
+	pub fn new(send: Box<dyn SendStream>, recv: Box<dyn RecvStream>, version: Version, ext: Extensions) -> Self {
 		Self {
 			send: Arc::new(Mutex::new(send)),
 			recv: Arc::new(Mutex::new(recv)),
 			ext,
+			version,
+			aliases: Default::default(),
+			stats: Default::default(),
+			going_away: Arc::new(watch::channel(None).0),
 		}
 	}
 
+	// Resolves with the URL of the first GOAWAY received, waiting for one if needed.
+	pub async fn going_away(&self) -> String {
+		let mut going_away = self.going_away.subscribe();
+
+		// The sender is held by self, so it can't be dropped while we wait.
+		let url = going_away
+			.wait_for(Option::is_some)
+			.await
+			.ok()
+			.and_then(|url| url.clone());
+		url.unwrap_or_default()
+	}
+
+	// Record a GOAWAY from the remote, waking any task waiting in going_away.
+	pub fn set_going_away(&self, url: &str) {
+		self.going_away.send_replace(Some(url.to_string()));
+	}
+
 	pub async fn send<T: Into<Message> + fmt::Debug>(&self, msg: T) -> Result<(), SessionError> {
-		let mut stream = self.send.lock().await;
 		log::info!("sending message: {:?}", msg);
-		msg.into()
-			.encode(&mut *stream, &self.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
+
+		if self.version.is_ietf() {
+			let msg = self.to_ietf(msg.into())?;
+			return self.send_ietf(msg).await;
+		}
+
+		let msg = msg.into();
+
+		// Validate before locking, so an invalid message doesn't interrupt the stream.
+		msg.validate(&self.ext)?;
+
+		let mut stream = self.send.lock().await;
+		msg.encode(&mut *stream, &self.ext).await?;
 		Ok(())
 	}
 
@@ -38,10 +88,312 @@ impl Control {
 	pub async fn recv(&self) -> Result<Message, SessionError> {
 		let mut stream = self.recv.lock().await;
 		log::debug!("waiting for message");
-		let msg = Message::decode(&mut *stream, &self.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
+
+		let msg = match self.version.is_ietf() {
+			true => loop {
+				let msg = ietf::Message::decode(&mut *stream).await?;
+
+				// Some messages have no equivalent in the fork and are handled here.
+				if let Some(msg) = self.recv_ietf(msg).await? {
+					break msg;
+				}
+			},
+			false => Message::decode(&mut *stream, &self.ext).await?,
+		};
+
 		log::debug!("received message: {:?}", msg);
 		Ok(msg)
 	}
+
+	/// Returns an encoder for the OBJECT headers of a new data stream.
+	pub fn object_encoder(&self, id: VarInt) -> ObjectEncoder {
+		let alias = self.aliases.lock().unwrap().get(&id).copied().unwrap_or(id);
+		ObjectEncoder::new(self.version, self.ext.clone(), alias)
+	}
+
+	/// Returns a decoder for the OBJECT headers of an incoming data stream.
+	pub fn object_decoder(&self) -> ObjectDecoder {
+		ObjectDecoder::new(self.version, self.ext.clone())
+	}
+
+	async fn send_ietf(&self, msg: ietf::Message) -> Result<(), SessionError> {
+		let mut stream = self.send.lock().await;
+		msg.encode(&mut *stream).await?;
+		Ok(())
+	}
+
+	// Convert a message into the IETF wire format.
+	fn to_ietf(&self, msg: Message) -> Result<ietf::Message, SessionError> {
+		let msg = match msg {
+			Message::Subscribe(mut msg) => {
+				// Newer drafts have a field for the subscriber priority instead of a parameter.
+				let priority = msg.priority().unwrap_or(DEFAULT_PRIORITY);
+				msg.params.0.remove(&message::SUBSCRIBE_PRIORITY);
+
+				let filter = match (&msg.start_group, &msg.start_object, &msg.end_group, &msg.end_object) {
+					(
+						SubscribeLocation::Latest(g),
+						SubscribeLocation::Absolute(o),
+						SubscribeLocation::None,
+						SubscribeLocation::None,
+					) if *g == VarInt::ZERO && *o == VarInt::ZERO => ietf::FilterType::LatestGroup,
+					(
+						SubscribeLocation::Latest(g),
+						SubscribeLocation::Latest(o),
+						SubscribeLocation::None,
+						SubscribeLocation::None,
+					) if *g == VarInt::ZERO && *o == VarInt::ZERO => ietf::FilterType::LatestObject,
+					(
+						SubscribeLocation::Absolute(group),
+						SubscribeLocation::Absolute(object),
+						SubscribeLocation::None,
+						SubscribeLocation::None,
+					) => ietf::FilterType::AbsoluteStart {
+						group: *group,
+						object: *object,
+					},
+					(
+						SubscribeLocation::Absolute(start_group),
+						SubscribeLocation::Absolute(start_object),
+						SubscribeLocation::Absolute(end_group),
+						SubscribeLocation::None,
+					) => ietf::FilterType::AbsoluteRange {
+						start_group: *start_group,
+						start_object: *start_object,
+						end_group: *end_group,
+					},
+					// The remaining combinations can't be expressed with a filter.
+					_ => return Err(EncodeError::InvalidValue.into()),
+				};
+
+				ietf::Subscribe {
+					id: msg.id,
+					// We always use the subscribe ID as the alias, so objects map back without a lookup.
+					track_alias: msg.id,
+					namespace: ietf::TrackNamespace::from_path(msg.namespace.as_deref().unwrap_or_default()),
+					name: msg.name,
+					priority,
+					group_order: ietf::GroupOrder::Publisher,
+					filter,
+					params: msg.params,
+				}
+				.into()
+			}
+			Message::Unsubscribe(msg) => ietf::Unsubscribe { id: msg.id }.into(),
+			Message::SubscribeOk(msg) => ietf::SubscribeOk {
+				id: msg.id,
+				expires: msg.expires,
+				group_order: ietf::GroupOrder::Ascending,
+				largest: None,
+				params: Default::default(),
+			}
+			.into(),
+			Message::SubscribeError(msg) => ietf::SubscribeError {
+				id: msg.id,
+				code: msg.code,
+				reason: msg.reason,
+				track_alias: self.aliases.lock().unwrap().remove(&msg.id).unwrap_or(msg.id),
+			}
+			.into(),
+			Message::SubscribeFin(msg) => {
+				self.aliases.lock().unwrap().remove(&msg.id);
+
+				ietf::SubscribeDone {
+					id: msg.id,
+					code: ietf::SubscribeDone::STATUS_TRACK_ENDED,
+					reason: String::new(),
+					last: Some(ietf::Location {
+						group: msg.final_group,
+						object: msg.final_object,
+					}),
+				}
+				.into()
+			}
+			Message::SubscribeReset(msg) => {
+				self.aliases.lock().unwrap().remove(&msg.id);
+
+				ietf::SubscribeDone {
+					id: msg.id,
+					// A reset with code 0 means the track was closed normally.
+					code: match msg.code {
+						0 => ietf::SubscribeDone::STATUS_TRACK_ENDED,
+						code => code,
+					},
+					reason: msg.reason,
+					last: Some(ietf::Location {
+						group: msg.final_group,
+						object: msg.final_object,
+					}),
+				}
+				.into()
+			}
+			Message::Announce(msg) => ietf::Announce {
+				namespace: ietf::TrackNamespace::from_path(&msg.namespace),
+				params: msg.params,
+			}
+			.into(),
+			Message::Unannounce(msg) => ietf::Unannounce {
+				namespace: ietf::TrackNamespace::from_path(&msg.namespace),
+			}
+			.into(),
+			Message::AnnounceOk(msg) => ietf::AnnounceOk {
+				namespace: ietf::TrackNamespace::from_path(&msg.namespace),
+			}
+			.into(),
+			Message::AnnounceError(msg) => ietf::AnnounceError {
+				namespace: ietf::TrackNamespace::from_path(&msg.namespace),
+				code: msg.code,
+				reason: msg.reason,
+			}
+			.into(),
+			Message::GoAway(msg) => ietf::GoAway { url: msg.url }.into(),
+
+			// Newer drafts don't support extensions.
+			Message::Probe(_) | Message::ProbeOk(_) => return Err(EncodeError::MissingExtension("probe").into()),
+		};
+
+		Ok(msg)
+	}
+
+	// Convert a message received in the IETF wire format, returning None if it was handled here instead.
+	async fn recv_ietf(&self, msg: ietf::Message) -> Result<Option<Message>, SessionError> {
+		let msg = match msg {
+			ietf::Message::Subscribe(msg) => {
+				let (start_group, start_object, end_group) = match msg.filter {
+					ietf::FilterType::LatestGroup => (
+						SubscribeLocation::Latest(VarInt::ZERO),
+						SubscribeLocation::Absolute(VarInt::ZERO),
+						SubscribeLocation::None,
+					),
+					ietf::FilterType::LatestObject => (
+						SubscribeLocation::Latest(VarInt::ZERO),
+						SubscribeLocation::Latest(VarInt::ZERO),
+						SubscribeLocation::None,
+					),
+					ietf::FilterType::AbsoluteStart { group, object } => (
+						SubscribeLocation::Absolute(group),
+						SubscribeLocation::Absolute(object),
+						SubscribeLocation::None,
+					),
+					ietf::FilterType::AbsoluteRange {
+						start_group,
+						start_object,
+						end_group,
+					} => (
+						SubscribeLocation::Absolute(start_group),
+						SubscribeLocation::Absolute(start_object),
+						SubscribeLocation::Absolute(end_group),
+					),
+				};
+
+				// Remember the alias so our data streams use it.
+				self.aliases.lock().unwrap().insert(msg.id, msg.track_alias);
+
+				// Only use the priority if it was chosen, since it reduces the precision of the publisher's priority.
+				let mut params = msg.params;
+				if msg.priority != DEFAULT_PRIORITY {
+					params.0.insert(message::SUBSCRIBE_PRIORITY, vec![msg.priority]);
+				}
+
+				message::Subscribe {
+					id: msg.id,
+					namespace: Some(msg.namespace.to_path()),
+					name: msg.name,
+					start_group,
+					start_object,
+					end_group,
+					end_object: SubscribeLocation::None,
+					switch_track_id: None,
+					params,
+				}
+				.into()
+			}
+			ietf::Message::Unsubscribe(msg) => {
+				// The subscriber won't use the alias again, even if we still send the final SUBSCRIBE_DONE.
+				self.aliases.lock().unwrap().remove(&msg.id);
+				message::Unsubscribe { id: msg.id }.into()
+			}
+			ietf::Message::SubscribeOk(msg) => message::SubscribeOk {
+				id: msg.id,
+				expires: msg.expires,
+			}
+			.into(),
+			ietf::Message::SubscribeError(msg) => message::SubscribeError {
+				id: msg.id,
+				code: msg.code,
+				reason: msg.reason,
+			}
+			.into(),
+			ietf::Message::SubscribeDone(msg) => {
+				let last = msg.last.unwrap_or_default();
+
+				match msg.code {
+					ietf::SubscribeDone::STATUS_TRACK_ENDED | ietf::SubscribeDone::STATUS_SUBSCRIPTION_ENDED => {
+						message::SubscribeFin {
+							id: msg.id,
+							final_group: last.group,
+							final_object: last.object,
+						}
+						.into()
+					}
+					code => message::SubscribeReset {
+						id: msg.id,
+						code,
+						reason: msg.reason,
+						final_group: last.group,
+						final_object: last.object,
+					}
+					.into(),
+				}
+			}
+			ietf::Message::Announce(msg) => message::Announce {
+				namespace: msg.namespace.to_path(),
+				params: msg.params,
+			}
+			.into(),
+			ietf::Message::Unannounce(msg) => message::Unannounce {
+				namespace: msg.namespace.to_path(),
+			}
+			.into(),
+			ietf::Message::AnnounceOk(msg) => message::AnnounceOk {
+				namespace: msg.namespace.to_path(),
+			}
+			.into(),
+			ietf::Message::AnnounceError(msg) => message::AnnounceError {
+				namespace: msg.namespace.to_path(),
+				code: msg.code,
+				reason: msg.reason,
+			}
+			.into(),
+			ietf::Message::GoAway(msg) => message::GoAway { url: msg.url }.into(),
+
+			// We don't serve past objects yet, so reject every FETCH.
+			ietf::Message::Fetch(msg) => {
+				self.send_ietf(
+					ietf::FetchError {
+						id: msg.id,
+						code: ietf::FetchError::CODE_NOT_SUPPORTED,
+						reason: "fetch not supported".to_string(),
+					}
+					.into(),
+				)
+				.await?;
+
+				return Ok(None);
+			}
+
+			// We never send a FETCH or limit the subscribe ID, and we can't narrow a subscription yet.
+			msg @ (ietf::Message::FetchOk(_)
+			| ietf::Message::FetchError(_)
+			| ietf::Message::FetchCancel(_)
+			| ietf::Message::MaxSubscribeId(_)
+			| ietf::Message::SubscribeUpdate(_)
+			| ietf::Message::AnnounceCancel(_)) => {
+				log::info!("ignoring message: {:?}", msg);
+				return Ok(None);
+			}
+		};
+
+		Ok(Some(msg))
+	}
 }
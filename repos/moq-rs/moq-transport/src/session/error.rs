@@ -48,6 +48,22 @@ pub enum SessionError {
 	#[error("required extension not offered: {0:?}")]
 	RequiredExtension(VarInt),
 
+	/// The transport session was closed.
+	#[error("session closed: code={0}")]
+	Closed(u32),
+
+	/// The stream was reset or stopped by the remote.
+	#[error("stream closed: code={0}")]
+	StreamClosed(u32),
+
+	/// The transport or the remote doesn't support datagrams.
+	#[error("datagrams unsupported")]
+	DatagramUnsupported,
+
+	/// The datagram was larger than the path allows.
+	#[error("datagram too large: {0}")]
+	DatagramTooLarge(usize),
+
 	/// Some VarInt was too large and we were too lazy to handle it
 	#[error("varint bounds exceeded")]
 	BoundsExceeded(#[from] coding::BoundsExceeded),
@@ -76,6 +92,10 @@ impl MoqError for SessionError {
 			Self::InvalidSize(_) => 400,
 			Self::RequiredExtension(_) => 426,
 			Self::BoundsExceeded(_) => 500,
+			Self::Closed(_) => 503,
+			Self::StreamClosed(_) => 502,
+			Self::DatagramUnsupported => 501,
+			Self::DatagramTooLarge(_) => 413,
 		}
 	}
 
@@ -102,6 +122,10 @@ impl MoqError for SessionError {
 			Self::InvalidSize(size) => format!("invalid size: {}", size),
 			Self::RequiredExtension(id) => format!("required extension was missing: {:?}", id),
 			Self::BoundsExceeded(_) => "varint bounds exceeded".to_string(),
+			Self::Closed(code) => format!("session closed: code={}", code),
+			Self::StreamClosed(code) => format!("stream closed: code={}", code),
+			Self::DatagramUnsupported => "datagrams unsupported".to_owned(),
+			Self::DatagramTooLarge(size) => format!("datagram too large: {}", size),
 		}
 	}
 }
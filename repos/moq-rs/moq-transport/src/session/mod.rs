@@ -7,21 +7,40 @@
 //! 3. Complete the MoQ handshake.
 //!
 //! Use [Client] or [Server] for the MoQ handshake depending on the endpoint.
-//! Then, decide if you want to create a [Publisher] or [Subscriber], or both (TODO).
+//! The WebTransport session can be replaced by any [transport::Session](crate::transport::Session), such as an in-memory [loopback](crate::transport::loopback) for tests.
+//! Then, decide if you want to create a [Publisher] or [Subscriber], or both with [PubSub].
 //!
 //! A [Publisher] can announce broadcasts, which will automatically be served over the network.
 //! A [Subscriber] can subscribe to broadcasts, which will automatically be served over the network.
+//! A [PubSub] does both over a single session, sharing the control stream.
 
+mod abr;
 mod client;
 mod control;
 mod error;
+mod object;
+mod priority;
+mod probe;
 mod publisher;
+mod pubsub;
 mod server;
+mod stats;
 mod subscriber;
+mod subscription;
+mod write;
 
+pub use abr::*;
 pub use client::*;
 pub(crate) use control::*;
 pub use error::*;
+pub(crate) use object::*;
+pub use priority::*;
+pub use probe::ProbeResult;
 pub use publisher::*;
+pub use pubsub::*;
 pub use server::*;
+pub(crate) use stats::Counters;
+pub use stats::{log_stats, Stats, SubscriptionStats};
 pub use subscriber::*;
+pub use subscription::{SubscribeOptions, Subscription};
+pub use write::WriteStrategy;
@@ -0,0 +1,129 @@
+// Reads and writes OBJECT headers on data streams, using the wire format negotiated during the handshake.
+
+use std::time;
+
+use crate::{
+	coding::{AsyncRead, AsyncWrite, Decode, DecodeError, Encode, EncodeError},
+	ietf, message,
+	setup::{Extensions, Version},
+	VarInt,
+};
+
+// Newer drafts don't have a per-object expiration, so cache objects for this long instead.
+const IETF_OBJECT_EXPIRES: time::Duration = time::Duration::from_secs(30);
+
+/// Writes the OBJECT headers for a single data stream.
+#[derive(Debug)]
+pub(crate) struct ObjectEncoder {
+	version: Version,
+	ext: Extensions,
+
+	// The track alias chosen by the remote subscriber.
+	alias: VarInt,
+
+	// Set once the stream header has been written.
+	header: bool,
+}
+
+impl ObjectEncoder {
+	pub fn new(version: Version, ext: Extensions, alias: VarInt) -> Self {
+		Self {
+			version,
+			ext,
+			alias,
+			header: false,
+		}
+	}
+
+	/// Newer drafts require every object to be prefixed with its size.
+	pub fn requires_size(&self) -> bool {
+		self.version.is_ietf()
+	}
+
+	pub async fn encode<W: AsyncWrite>(&mut self, w: &mut W, object: &message::Object) -> Result<(), EncodeError> {
+		if !self.version.is_ietf() {
+			return object.encode(w, &self.ext).await;
+		}
+
+		// The caller needs to buffer unbounded objects; see requires_size.
+		let size = object.size.ok_or(EncodeError::InvalidValue)?;
+
+		if !self.header {
+			let header = ietf::SubgroupHeader {
+				track_alias: self.alias,
+				group: object.group,
+				subgroup: VarInt::ZERO,
+				priority: object.priority.min(u8::MAX.into()) as u8,
+			};
+
+			ietf::StreamHeader::Subgroup(header).encode(w).await?;
+			self.header = true;
+		}
+
+		ietf::SubgroupObject {
+			id: object.sequence,
+			size,
+			status: ietf::ObjectStatus::Normal,
+		}
+		.encode(w)
+		.await
+	}
+}
+
+/// Reads the OBJECT headers for a single data stream.
+#[derive(Debug)]
+pub(crate) struct ObjectDecoder {
+	version: Version,
+	ext: Extensions,
+
+	// The stream header, once it has been read.
+	header: Option<ietf::SubgroupHeader>,
+}
+
+impl ObjectDecoder {
+	pub fn new(version: Version, ext: Extensions) -> Self {
+		Self {
+			version,
+			ext,
+			header: None,
+		}
+	}
+
+	/// Decode the next object, returning [DecodeError::Final] if the stream naturally ended.
+	pub async fn decode<R: AsyncRead>(&mut self, r: &mut R) -> Result<message::Object, DecodeError> {
+		if !self.version.is_ietf() {
+			return message::Object::decode(r, &self.ext).await;
+		}
+
+		let header = match &self.header {
+			Some(header) => header.clone(),
+			None => match ietf::StreamHeader::decode(r).await? {
+				ietf::StreamHeader::Subgroup(header) => self.header.insert(header).clone(),
+				// We never send a FETCH.
+				header @ ietf::StreamHeader::Fetch(_) => return Err(DecodeError::InvalidMessage(header.id())),
+			},
+		};
+
+		loop {
+			let object = ietf::SubgroupObject::decode(r).await?;
+
+			match object.status {
+				ietf::ObjectStatus::Normal => {
+					return Ok(message::Object {
+						// We use the subscribe ID as our track alias.
+						track: header.track_alias,
+						group: header.group,
+						sequence: object.id,
+						priority: header.priority.into(),
+						expires: Some(IETF_OBJECT_EXPIRES),
+						size: Some(object.size),
+						ntp_timestamp: None,
+					});
+				}
+				ietf::ObjectStatus::DoesNotExist => continue,
+				// Nothing else will be sent on this stream.
+				_ => return Err(DecodeError::Final),
+			}
+		}
+	}
+}
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, fmt, sync::Mutex, time};
+
+use crate::VarInt;
+
+/// The properties of a segment that are used to pick its stream priority.
+#[derive(Clone, Debug)]
+pub struct PriorityContext<'a> {
+	/// The subscribe ID chosen by the remote subscriber.
+	pub id: VarInt,
+
+	/// The name of the track.
+	pub track: &'a str,
+
+	/// The sequence number of the segment within the track.
+	pub group: VarInt,
+
+	/// The priority chosen by the publisher, where **larger** values are sent first.
+	pub priority: u32,
+
+	/// The priority requested in the SUBSCRIBE, if any, where **smaller** values are sent first.
+	pub subscriber_priority: Option<u8>,
+
+	/// How long the segment is cached, which bounds how long it's useful for.
+	pub expires: Option<time::Duration>,
+}
+
+/// Decides the QUIC stream priority of each segment served by a [Publisher](super::Publisher).
+///
+/// QUIC sends streams with a **larger** priority first, and streams with the same priority round-robin.
+pub trait PriorityPolicy: Send + Sync + fmt::Debug {
+	fn priority(&self, segment: &PriorityContext) -> i32;
+}
+
+/// The default policy, using the publisher's segment priority as is.
+///
+/// If the subscriber requested a priority, it's used for the most significant 8 bits instead.
+/// The publisher's priority is then truncated to the remaining 24 bits, so segments whose priorities differ only in the lowest 8 bits are sent round-robin.
+#[derive(Clone, Debug, Default)]
+pub struct SegmentPriority;
+
+impl PriorityPolicy for SegmentPriority {
+	fn priority(&self, segment: &PriorityContext) -> i32 {
+		match segment.subscriber_priority {
+			Some(subscriber) => {
+				// Invert the subscriber priority since smaller values are sent first.
+				let combined = ((u8::MAX - subscriber) as u32) << 24 | segment.priority >> 8;
+				(combined as i64 + i32::MIN as i64) as i32
+			}
+
+			// Convert the u32 to a i32, since the Quinn set_priority is signed.
+			None => (segment.priority as i64 - i32::MAX as i64) as i32,
+		}
+	}
+}
+
+/// Always send the newest group first, ignoring any priorities.
+///
+/// This is the best choice for live playback, as old groups are only sent once new ones are finished.
+#[derive(Clone, Debug, Default)]
+pub struct NewestFirst;
+
+impl PriorityPolicy for NewestFirst {
+	fn priority(&self, segment: &PriorityContext) -> i32 {
+		i32::try_from(segment.group.into_inner()).unwrap_or(i32::MAX)
+	}
+}
+
+/// Share the bandwidth between tracks in proportion to their weight.
+///
+/// Each new segment is scheduled after the previous segment of the same track, advanced by the inverse of its weight.
+/// Tracks without a configured weight use the subscriber priority if provided, otherwise a weight of 1.
+/// The schedule is stateful, so each session should use a separate instance.
+#[derive(Debug, Default)]
+pub struct WeightedRoundRobin {
+	weights: HashMap<String, u32>,
+
+	// The virtual time at which each track was last scheduled.
+	state: Mutex<WeightedState>,
+}
+
+// Rebase the virtual time well before the priority saturates, at a resolution of 1/1000th of a segment.
+const WEIGHTED_REBASE: f64 = 1_000_000.0;
+
+#[derive(Debug, Default)]
+struct WeightedState {
+	// The virtual time of the most recently scheduled segment.
+	now: f64,
+	tracks: HashMap<String, f64>,
+}
+
+impl WeightedRoundRobin {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the weight of the named track, where zero is treated as one.
+	pub fn with_weight(mut self, track: &str, weight: u32) -> Self {
+		self.weights.insert(track.to_string(), weight.max(1));
+		self
+	}
+
+	fn weight(&self, segment: &PriorityContext) -> f64 {
+		match self.weights.get(segment.track) {
+			Some(weight) => *weight as f64,
+			// A smaller subscriber priority gets a larger weight.
+			None => segment.subscriber_priority.map(|p| 256 - p as u32).unwrap_or(1) as f64,
+		}
+	}
+}
+
+impl PriorityPolicy for WeightedRoundRobin {
+	fn priority(&self, segment: &PriorityContext) -> i32 {
+		let weight = self.weight(segment);
+
+		let mut state = self.state.lock().unwrap();
+
+		// Shift the schedule back to zero, otherwise every priority would eventually saturate.
+		// NOTE: Segments still in flight during the rebase are briefly sent after newer segments.
+		if state.now >= WEIGHTED_REBASE {
+			let base = state.now;
+			state.now = 0.0;
+			state
+				.tracks
+				.values_mut()
+				.for_each(|next| *next = (*next - base).max(0.0));
+		}
+
+		// Idle tracks don't get to save up their share.
+		let now = state.now;
+		let next = state.tracks.entry(segment.track.to_string()).or_insert(now);
+		*next = next.max(now) + 1.0 / weight;
+
+		let scheduled = *next;
+		state.now = state.now.max(scheduled - 1.0 / weight);
+
+		// Segments scheduled earlier are sent first; the resolution is 1/1000th of a segment.
+		i32::MAX.saturating_sub((scheduled * 1000.0).min(i32::MAX as f64) as i32)
+	}
+}
+
+/// Send the segment with the earliest deadline first, based on when it expires.
+///
+/// Segments without an expiration use the default deadline.
+#[derive(Clone, Debug)]
+pub struct EarliestDeadline {
+	default: time::Duration,
+
+	// The deadlines are relative to this instant, so they fit in the priority.
+	epoch: time::Instant,
+}
+
+impl EarliestDeadline {
+	pub fn new(default: time::Duration) -> Self {
+		Self {
+			default,
+			epoch: time::Instant::now(),
+		}
+	}
+}
+
+impl Default for EarliestDeadline {
+	fn default() -> Self {
+		Self::new(time::Duration::from_secs(30))
+	}
+}
+
+impl PriorityPolicy for EarliestDeadline {
+	fn priority(&self, segment: &PriorityContext) -> i32 {
+		let deadline = self.epoch.elapsed() + segment.expires.unwrap_or(self.default);
+		let millis = i32::try_from(deadline.as_millis()).unwrap_or(i32::MAX);
+
+		// NOTE: After ~24 days every deadline saturates, falling back to round-robin.
+		i32::MAX - millis
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn context(track: &str, group: u32, priority: u32, subscriber_priority: Option<u8>) -> PriorityContext<'_> {
+		PriorityContext {
+			id: VarInt::ZERO,
+			track,
+			group: VarInt::from_u32(group),
+			priority,
+			subscriber_priority,
+			expires: None,
+		}
+	}
+
+	#[test]
+	fn priority_order() {
+		// Larger segment priorities are sent first, unless the subscriber asked for a priority.
+		let policy = SegmentPriority;
+		assert!(policy.priority(&context("a", 0, 2, None)) > policy.priority(&context("a", 0, 1, None)));
+		assert!(policy.priority(&context("a", 0, 1, Some(0))) > policy.priority(&context("a", 0, 2, Some(1))));
+		assert!(policy.priority(&context("a", 0, 1 << 20, Some(0))) > policy.priority(&context("a", 0, 0, Some(0))));
+
+		let policy = NewestFirst;
+		assert!(policy.priority(&context("a", 2, 0, None)) > policy.priority(&context("a", 1, 9, None)));
+
+		// A track with twice the weight gets two segments for every one of the other track.
+		let policy = WeightedRoundRobin::new().with_weight("video", 2);
+		let mut order: Vec<_> = (0..3)
+			.flat_map(|group| {
+				[
+					("video", policy.priority(&context("video", group, 0, None))),
+					("audio", policy.priority(&context("audio", group, 0, None))),
+				]
+			})
+			.collect();
+
+		order.sort_by_key(|(_, priority)| -priority);
+		let order: Vec<_> = order.into_iter().map(|(track, _)| track).take(3).collect();
+		assert_eq!(order.iter().filter(|track| **track == "video").count(), 2);
+	}
+
+	#[test]
+	fn weighted_rebase() {
+		let policy = WeightedRoundRobin::new();
+
+		// Schedule enough segments that the priority would otherwise saturate.
+		for group in 0..2_500_000 {
+			policy.priority(&context("a", group, 0, None));
+		}
+
+		// Later segments are still sent after earlier ones.
+		let first = policy.priority(&context("a", 0, 0, None));
+		let second = policy.priority(&context("a", 0, 0, None));
+		assert!(first > second);
+	}
+}
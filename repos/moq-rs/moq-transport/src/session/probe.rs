@@ -0,0 +1,115 @@
+use std::time;
+
+use crate::cache::{CacheError, Watch};
+
+/// The result of a bandwidth probe, returned by [Subscriber::probe](super::Subscriber::probe).
+#[derive(Clone, Debug)]
+pub struct ProbeResult {
+	/// The number of payload bytes received.
+	pub bytes: u64,
+
+	/// The time between receiving the first and last payload byte.
+	pub elapsed: time::Duration,
+
+	/// When the publisher started sending each OBJECT, in milliseconds since the Unix epoch.
+	pub sent: Vec<u64>,
+}
+
+impl ProbeResult {
+	/// The measured throughput in bits per second, or None if the transfer was too quick to measure.
+	pub fn throughput(&self) -> Option<u64> {
+		let micros = self.elapsed.as_micros();
+		if micros == 0 {
+			return None;
+		}
+
+		let bits = self.bytes as u128 * 8;
+		Some((bits * 1_000_000 / micros).min(u64::MAX as u128) as u64)
+	}
+}
+
+#[derive(Debug, Default)]
+struct State {
+	// The total number of payload bytes we expect to receive.
+	expected: u64,
+
+	bytes: u64,
+	first: Option<time::Instant>,
+	last: Option<time::Instant>,
+
+	// Set when PROBE_OK is received.
+	sent: Option<Vec<u64>>,
+
+	// Set when the probe was reset by the publisher.
+	closed: Option<CacheError>,
+}
+
+/// Records the data received for an outstanding PROBE.
+#[derive(Clone, Debug)]
+pub(crate) struct Probe {
+	state: Watch<State>,
+}
+
+impl Probe {
+	pub fn new(expected: u64) -> Self {
+		let state = State {
+			expected,
+			..Default::default()
+		};
+
+		Self {
+			state: Watch::new(state),
+		}
+	}
+
+	/// Record that a chunk of payload arrived.
+	pub fn recv(&self, size: usize) {
+		let now = time::Instant::now();
+
+		let mut state = self.state.lock_mut();
+		state.first.get_or_insert(now);
+		state.last = Some(now);
+		state.bytes += size as u64;
+	}
+
+	/// Record the PROBE_OK, containing when each OBJECT was sent.
+	pub fn done(&self, sent: Vec<u64>) {
+		self.state.lock_mut().sent = Some(sent);
+	}
+
+	pub fn close(&self, err: CacheError) {
+		self.state.lock_mut().closed = Some(err);
+	}
+
+	/// Wait until both the PROBE_OK and every OBJECT have been received.
+	pub async fn result(&self) -> Result<ProbeResult, CacheError> {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+
+				if let Some(err) = &state.closed {
+					return Err(err.clone());
+				}
+
+				if let Some(sent) = &state.sent {
+					if state.bytes >= state.expected {
+						let elapsed = match (state.first, state.last) {
+							(Some(first), Some(last)) => last - first,
+							_ => time::Duration::ZERO,
+						};
+
+						return Ok(ProbeResult {
+							bytes: state.bytes,
+							elapsed,
+							sent: sent.clone(),
+						});
+					}
+				}
+
+				state.changed()
+			};
+
+			notify.await;
+		}
+	}
+}
@@ -1,41 +1,118 @@
 use std::{
-	collections::{hash_map, HashMap}, result, sync::{Arc, Mutex}, thread::JoinHandle, u32
+	collections::{hash_map, HashMap},
+	fmt,
+	sync::{Arc, Mutex},
+	time,
 };
 
+use bytes::{Bytes, BytesMut};
 use tokio::task::AbortHandle;
-use webtransport_quinn::Session;
 
 use crate::{
 	cache::{broadcast, segment, track, CacheError},
+	coding::DecodeError,
 	message,
 	message::Message,
-	MoqError, VarInt,
+	transport, MoqError, VarInt,
 };
 
-use super::{Control, SessionError};
+use super::{
+	write::Writer, AbrContext, AbrPolicy, Control, PriorityContext, PriorityPolicy, SegmentPriority, SessionError,
+	Stats, WriteStrategy,
+};
+
+// The most data a single PROBE can request, across all of its OBJECTs.
+const MAX_PROBE_BYTES: usize = 64 * 1024 * 1024;
+
+// An active subscription, along with the state needed to switch to another track.
+#[derive(Debug)]
+struct Serve {
+	abort: AbortHandle,
+	name: String,
+
+	// The largest group queued so far.
+	last: Option<VarInt>,
+
+	// Set when switching to another track, which takes over from this group onwards.
+	boundary: Option<VarInt>,
+}
+
+// What to do with the next segment of a subscription.
+enum Schedule {
+	Send,
+	Skip,
+	Removed,
+	Switched,
+}
 
+/// Resolves the namespace of a SUBSCRIBE to the broadcast that should serve it.
+///
+/// Without a router, a [Publisher] only serves the empty namespace from its source broadcast.
+pub trait Router: Send + Sync + fmt::Debug {
+	fn route(&self, namespace: &str) -> Result<broadcast::Subscriber, CacheError>;
+}
 
 /// Serves broadcasts over the network, automatically handling subscriptions and caching.
 // TODO Clone specific fields when a task actually needs it.
 #[derive(Clone, Debug)]
 pub struct Publisher {
 	// A map of active subscriptions, containing an abort handle to cancel them.
-	subscribes: Arc<Mutex<HashMap<VarInt, AbortHandle>>>,
-	webtransport: Session,
+	subscribes: Arc<Mutex<HashMap<VarInt, Serve>>>,
+	transport: Arc<dyn transport::Session>,
 	control: Control,
 	source: broadcast::Subscriber,
+
+	// Used to serve SUBSCRIBEs with a non-empty namespace.
+	router: Option<Arc<dyn Router>>,
+
+	// Decides the stream priority of each segment.
+	priority: Arc<dyn PriorityPolicy>,
+
+	// Decides how OBJECTs are written to each stream.
+	write: WriteStrategy,
+
+	// Switches subscriptions between renditions, if any.
+	abr: Option<Arc<dyn AbrPolicy>>,
 }
 
 impl Publisher {
-	pub(crate) fn new(webtransport: Session, control: Control, source: broadcast::Subscriber) -> Self {
+	pub(crate) fn new(transport: Arc<dyn transport::Session>, control: Control, source: broadcast::Subscriber) -> Self {
 		Self {
-			webtransport,
+			transport,
 			control,
 			subscribes: Default::default(),
 			source,
+			router: None,
+			priority: Arc::new(SegmentPriority),
+			write: Default::default(),
+			abr: None,
 		}
 	}
 
+	/// Serve SUBSCRIBEs with a non-empty namespace using the provided router.
+	pub fn with_router(mut self, router: Arc<dyn Router>) -> Self {
+		self.router = Some(router);
+		self
+	}
+
+	/// Prioritize segments using the provided policy instead of [SegmentPriority].
+	pub fn with_priority(mut self, policy: Arc<dyn PriorityPolicy>) -> Self {
+		self.priority = policy;
+		self
+	}
+
+	/// Batch or pace the writes to each stream using the provided strategy.
+	pub fn with_write_strategy(mut self, strategy: WriteStrategy) -> Self {
+		self.write = strategy;
+		self
+	}
+
+	/// Let the provided policy switch each subscription between renditions at group boundaries.
+	pub fn with_abr(mut self, policy: Arc<dyn AbrPolicy>) -> Self {
+		self.abr = Some(policy);
+		self
+	}
+
 	// TODO Serve a broadcast without sending an ANNOUNCE.
 	// fn serve(&mut self, broadcast: broadcast::Subscriber) -> Result<(), SessionError> {
 
@@ -46,20 +123,24 @@ impl Publisher {
 		let res = self.run_inner().await;
 
 		// Terminate all active subscribes on error.
-		self.subscribes
-			.lock()
-			.unwrap()
-			.drain()
-			.for_each(|(_, abort)| abort.abort());
+		self.terminate();
 
 		res
 	}
 
-	pub async fn run_inner(&mut self) -> Result<(), SessionError> {
+	// Abort all active subscribes.
+	pub(crate) fn terminate(&self) {
+		self.subscribes.lock().unwrap().drain().for_each(|(id, serve)| {
+			self.control.stats.unserve(id);
+			serve.abort.abort();
+		});
+	}
+
+	async fn run_inner(&mut self) -> Result<(), SessionError> {
 		log::debug!("running publisher");
 		loop {
 			tokio::select! {
-				stream = self.webtransport.accept_uni() => {
+				stream = self.transport.accept_uni() => {
 					stream?;
 					return Err(SessionError::RoleViolation(VarInt::ZERO));
 				},
@@ -74,31 +155,52 @@ impl Publisher {
 				},
 				// No more broadcasts are available.
 				err = self.source.closed() => {
-					self.webtransport.close(err.code(), err.reason().as_bytes());
+					self.transport.close(err.code(), err.reason().as_bytes());
 					return Ok(());
 				},
 			}
 		}
 	}
 
-	/* TODO: Uncomment here when it's implemented in webtransport-quinn
-	pub fn get_throughput(&self) -> u64 {
-		// self.webtransport.throughput()
-		0
+	/// Snapshot the QUIC metrics and per-subscription counters.
+	pub fn stats(&self) -> Stats {
+		Stats::new(&*self.transport, &self.control.stats)
+	}
+
+	/// Send a GOAWAY, asking the subscriber to reconnect to `url`, or the same URL if it's empty.
+	///
+	/// The session keeps serving until the subscriber disconnects, so it can switch without a gap.
+	pub async fn go_away(&self, url: &str) -> Result<(), SessionError> {
+		self.control.send(message::GoAway { url: url.to_string() }).await
 	}
-	*/
 
-	async fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
+	/// Resolves once the subscriber sends a GOAWAY, returning the URL to reconnect to, or an empty string for the same URL.
+	///
+	/// The session keeps serving, so the application decides when to reconnect.
+	pub async fn going_away(&self) -> String {
+		self.control.going_away().await
+	}
+
+	pub(crate) async fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
 		log::info!("received message: {:?}", msg);
 		match msg {
 			Message::AnnounceOk(msg) => self.recv_announce_ok(msg).await,
 			Message::AnnounceError(msg) => self.recv_announce_error(msg).await,
 			Message::Subscribe(msg) => self.recv_subscribe(msg).await,
 			Message::Unsubscribe(msg) => self.recv_unsubscribe(msg).await,
+			Message::Probe(msg) => self.recv_probe(msg).await,
+			Message::GoAway(msg) => self.recv_go_away(msg),
 			_ => Err(SessionError::RoleViolation(msg.id())),
 		}
 	}
 
+	fn recv_go_away(&mut self, msg: &message::GoAway) -> Result<(), SessionError> {
+		// Keep publishing until the application reconnects or the server closes the session.
+		log::info!("received GOAWAY: url={:?}", msg.url);
+		self.control.set_going_away(&msg.url);
+		Ok(())
+	}
+
 	async fn recv_announce_ok(&mut self, _msg: &message::AnnounceOk) -> Result<(), SessionError> {
 		// We didn't send an announce.
 		Err(CacheError::NotFound.into())
@@ -112,14 +214,20 @@ impl Publisher {
 	async fn recv_subscribe(&mut self, msg: &message::Subscribe) -> Result<(), SessionError> {
 		log::info!("received subscribe: {:?}", msg);
 		// Assume that the subscribe ID is unique for now.
-		if msg.name.starts_with(".probe") {
-			let mut probe_size = 20000;
-			let mut probe_priority = 0;
-			if msg.name.starts_with(".probe:") {
-				let parameters = msg.name.split(":").collect::<Vec<&str>>();
-				probe_size = parameters.get(1).unwrap().parse().unwrap();
-				probe_priority = parameters.get(2).unwrap().parse().unwrap();
+		// Peers that negotiated the probe extension use PROBE instead of these magic track names.
+		if msg.name.starts_with(".probe") && !self.control.ext.probe {
+			let (probe_size, probe_priority) = match Self::parse_probe(&msg.name) {
+				Ok(probe) => probe,
+				Err(err) => return self.reset_subscribe(msg.id, SessionError::from(err)).await,
+			};
+
+			// The same limit as PROBE, so the track name can't make us allocate an arbitrary amount either.
+			if probe_size as usize > MAX_PROBE_BYTES {
+				return self
+					.reset_subscribe(msg.id, SessionError::InvalidSize(VarInt::from(probe_size)))
+					.await;
 			}
+
 			let mut this = self.clone();
 			let probe_msg = msg.clone();
 			tokio::spawn(async move {
@@ -140,8 +248,15 @@ impl Publisher {
 			// Insert the abort handle into the lookup table.
 			match self.subscribes.lock().unwrap().entry(msg.id) {
 				hash_map::Entry::Occupied(_) => return Err(CacheError::Duplicate.into()), // TODO fatal, because we already started the task
-				hash_map::Entry::Vacant(entry) => entry.insert(abort),
+				hash_map::Entry::Vacant(entry) => entry.insert(Serve {
+					abort,
+					name: msg.name.clone(),
+					last: None,
+					boundary: None,
+				}),
 			};
+
+			self.control.stats.serve(msg.id, &msg.name);
 		}
 
 		self.control
@@ -152,6 +267,24 @@ impl Publisher {
 			.await
 	}
 
+	// Parse the optional size and priority from a ".probe:size:priority" track name.
+	fn parse_probe(name: &str) -> Result<(u32, u32), DecodeError> {
+		let parameters = match name.strip_prefix(".probe:") {
+			Some(parameters) => parameters,
+			None => return Ok((20000, 0)),
+		};
+
+		let mut parameters = parameters.split(':');
+		let mut next = || -> Result<u32, DecodeError> {
+			parameters
+				.next()
+				.and_then(|p| p.parse().ok())
+				.ok_or(DecodeError::InvalidParameter)
+		};
+
+		Ok((next()?, next()?))
+	}
+
 	async fn reset_subscribe<E: MoqError>(&mut self, id: VarInt, err: E) -> Result<(), SessionError> {
 		let msg = message::SubscribeReset {
 			id,
@@ -167,7 +300,22 @@ impl Publisher {
 		self.control.send(msg).await
 	}
 
-	async fn send_probe_data(&mut self, id: VarInt, probe_size: u32, probe_priority: u32) -> Result<(), SessionError>{
+	async fn fin_subscribe(
+		&mut self,
+		id: VarInt,
+		final_group: VarInt,
+		final_object: VarInt,
+	) -> Result<(), SessionError> {
+		let msg = message::SubscribeFin {
+			id,
+			final_group,
+			final_object,
+		};
+
+		self.control.send(msg).await
+	}
+
+	async fn send_probe_data(&mut self, id: VarInt, probe_size: u32, probe_priority: u32) -> Result<(), SessionError> {
 		log::info!("sending probe data");
 
 		let stream_priority: i32 = match probe_priority {
@@ -175,65 +323,156 @@ impl Publisher {
 			_ => 0,
 		};
 
-		let mut stream = self.webtransport.open_uni().await?;
-		// Convert the u32 to a i32, since the Quinn set_priority is signed.
-		stream.set_priority(stream_priority).ok();
+		let result = self
+			.send_probe_object(id, VarInt::ZERO, probe_size as usize, probe_priority, stream_priority)
+			.await;
+		if let Err(err) = result {
+			log::warn!("failed to write probe data: {:?}", err);
+		}
+		log::info!("sent probe data");
+		Ok(())
+	}
+
+	async fn recv_probe(&mut self, msg: &message::Probe) -> Result<(), SessionError> {
+		self.control.ext.require_probe()?;
+
+		let size = usize::try_from(msg.size.into_inner()).unwrap_or(usize::MAX);
+		let count = usize::try_from(msg.count.into_inner()).unwrap_or(usize::MAX);
+
+		// Don't let the subscriber make us send an arbitrary amount of data.
+		if size.saturating_mul(count) > MAX_PROBE_BYTES {
+			return self.reset_subscribe(msg.id, SessionError::InvalidSize(msg.size)).await;
+		}
+
+		// Or an arbitrary number of OBJECTs, which wouldn't fit in the PROBE_OK.
+		if msg.count.into_inner() > message::ProbeOk::MAX_SENT {
+			return self.reset_subscribe(msg.id, SessionError::InvalidSize(msg.count)).await;
+		}
+
+		let mut this = self.clone();
+		let msg = msg.clone();
+
+		tokio::spawn(async move {
+			if let Err(err) = this.run_probe(&msg, size, count).await {
+				log::warn!("failed to serve probe: id={} err={:?}", msg.id, err);
+				this.reset_subscribe(msg.id, err).await.ok();
+			}
+		});
+
+		Ok(())
+	}
+
+	async fn run_probe(&mut self, msg: &message::Probe, size: usize, count: usize) -> Result<(), SessionError> {
+		log::info!("serving probe: {:?}", msg);
+
+		let mut sent = Vec::with_capacity(count);
+
+		for group in 0..count {
+			if group > 0 && !msg.pacing.is_zero() {
+				tokio::time::sleep(msg.pacing).await;
+			}
+
+			let group = VarInt::try_from(group)?;
+			let stream_priority = self.priority.priority(&PriorityContext {
+				id: msg.id,
+				track: ".probe",
+				group,
+				priority: msg.priority,
+				subscriber_priority: None,
+				expires: None,
+			});
+			let timestamp = self
+				.send_probe_object(msg.id, group, size, msg.priority, stream_priority)
+				.await?;
+
+			sent.push(timestamp);
+		}
+
+		self.control.send(message::ProbeOk { id: msg.id, sent }).await
+	}
+
+	// Write a single OBJECT of zeros on a new stream, returning when it started sending.
+	async fn send_probe_object(
+		&self,
+		id: VarInt,
+		group: VarInt,
+		size: usize,
+		priority: u32,
+		stream_priority: i32,
+	) -> Result<VarInt, SessionError> {
+		let mut stream = self.transport.open_uni().await?;
+		stream.set_priority(stream_priority);
 
 		let ntp_timestamp = match VarInt::try_from(chrono::Utc::now().timestamp_millis() as u64) {
 			Ok(ntp_timestamp) => ntp_timestamp,
 			Err(e) => return Err(SessionError::BoundsExceeded(e)),
 		};
 
-		let payload = vec![0_u8; probe_size.try_into().unwrap()];
+		let payload = Bytes::from(vec![0_u8; size]);
 
 		// write the object
 
-
 		let object = message::Object {
 			track: id,
-			group: VarInt::from_u32(0),
-			priority: probe_priority,
-			sequence: VarInt::from_u32(0),
+			group,
+			priority,
+			sequence: VarInt::ZERO,
 			expires: None,
 			ntp_timestamp: Option::from(ntp_timestamp),
-			size: Some(VarInt::try_from(probe_size).unwrap())
+			size: Some(VarInt::try_from(size)?),
 		};
 
-		object
-			.encode(&mut stream, &self.control.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
+		self.control.object_encoder(id).encode(&mut stream, &object).await?;
 
 		// write the payload
-		let result = stream.write_all(&payload).await;
-		if let Err(err) = result {
-			log::warn!("failed to write probe data: {:?}", err);
-		}
-		log::info!("sent probe data");
-		Ok(())
+		stream.write_chunk(payload).await?;
+
+		Ok(ntp_timestamp)
 	}
 
 	fn start_subscribe(&mut self, msg: message::Subscribe) -> Result<AbortHandle, SessionError> {
-		// We currently don't use the namespace field in SUBSCRIBE
-		// Make sure the namespace is empty if it's provided.
-		if msg.namespace.as_ref().map_or(false, |namespace| !namespace.is_empty()) {
-			return Err(CacheError::NotFound.into());
-		}
+		// The empty namespace is served by our source, anything else needs a router.
+		let broadcast = match msg.namespace.as_deref() {
+			None | Some("") => self.source.clone(),
+			Some(namespace) => match &self.router {
+				Some(router) => router.route(namespace)?,
+				None => return Err(CacheError::NotFound.into()),
+			},
+		};
+
+		// Zero means this SUBSCRIBE doesn't replace another one.
+		let switch = msg.switch_track_id.filter(|id| *id != VarInt::ZERO);
+		let from = switch.and_then(|id| Some(self.subscribes.lock().unwrap().get(&id)?.name.clone()));
+
+		// Pass the switch along, so a relay can forward it upstream.
+		let mut track = match &from {
+			Some(from) => broadcast.switch_track(from, &msg.name)?,
+			None => broadcast.get_track(&msg.name)?,
+		};
+
+		// Send OBJECTs as datagrams if the subscriber asked and the extension was negotiated.
+		let datagram = self.control.ext.datagram && msg.params.has(message::SUBSCRIBE_DATAGRAM);
+		let priority = msg.priority();
+		let max_latency = msg.max_latency();
 
 		// TODO only clone the fields we need
 		let mut this = self.clone();
 
-		let mut track = self.source.get_track(&msg.name)?;
-
 		let handle = tokio::spawn(async move {
 			log::info!("serving track: name={}", track.name);
 
-			if msg.switch_track_id.is_some() && msg.switch_track_id.unwrap() != VarInt::from_u32(0) && this.subscribes.lock().unwrap().get(&msg.switch_track_id.unwrap()).is_some() {
-				log::info!("closing track: name={:?}", msg.switch_track_id);
-				this.subscribes.lock().unwrap().remove(&msg.switch_track_id.unwrap());
+			if let Some(from) = &from {
+				log::info!("switching track: from={} to={}", from, track.name);
+			}
+
+			let res = this
+				.run_subscribe(msg.id, &broadcast, &mut track, datagram, priority, max_latency, switch)
+				.await;
+
+			if let Some(abr) = &this.abr {
+				abr.finish(msg.id);
 			}
 
-			let res = this.run_subscribe(msg.id, &mut track).await;
 			if let Err(err) = &res {
 				log::warn!("failed to serve track: name={} err={:#?}", track.name, err);
 			}
@@ -243,78 +482,305 @@ impl Publisher {
 				log::warn!("subscribe not found: name={}", track.name);
 			} else {
 				log::info!("closing track: name={}", track.name);
-				// Make sure we send a reset at the end.
-				let err = res.err().unwrap_or(CacheError::Closed.into());
-				this.reset_subscribe(msg.id, err).await.ok();
+				// Make sure we send a SUBSCRIBE_FIN or SUBSCRIBE_RESET at the end.
+				match res {
+					Ok(Some((group, object))) => this.fin_subscribe(msg.id, group, object).await.ok(),
+					// Nothing was served, so there's no final group to report.
+					Ok(None) => this.reset_subscribe(msg.id, CacheError::Closed).await.ok(),
+					Err(err) => this.reset_subscribe(msg.id, err).await.ok(),
+				};
 
 				// We're all done, so clean up the abort handle.
 				this.subscribes.lock().unwrap().remove(&msg.id);
+				this.control.stats.unserve(msg.id);
 			}
 		});
 
 		Ok(handle.abort_handle())
 	}
 
-	async fn run_subscribe(&self, id: VarInt, track: &mut track::Subscriber) -> Result<(), SessionError> {
+	#[allow(clippy::too_many_arguments)]
+	async fn run_subscribe(
+		&self,
+		id: VarInt,
+		broadcast: &broadcast::Subscriber,
+		track: &mut track::Subscriber,
+		datagram: bool,
+		subscriber_priority: Option<u8>,
+		max_latency: Option<time::Duration>,
+		mut switch: Option<VarInt>,
+	) -> Result<Option<(VarInt, VarInt)>, SessionError> {
 		// TODO add an Ok method to track::Publisher so we can send SUBSCRIBE_OK
 
 		log::info!("in run_subscribe: {:?}", track);
 
+		// The newest segment queued, used to report the final group and object in SUBSCRIBE_FIN.
+		let mut last: Option<segment::Subscriber> = None;
+
+		// The track named in the SUBSCRIBE, which may be replaced by the ABR policy.
+		let requested = track.name.clone();
+
+		// The newest group decided by the ABR policy, and the group where it last switched tracks.
+		let mut decided: Option<VarInt> = None;
+		let mut start: Option<VarInt> = None;
+
 		while let Some(mut segment) = track.segment().await? {
-			// Check if the subscribe was removed while waiting for the segment.
-			if self.subscribes.lock().unwrap().get(&id).is_none() {
-				log::info!("run_subscribe | subscription removed, exiting | track:{} sequence:{:?} priority:{} index:{}", id, segment.sequence, segment.priority, segment.index);
-				break
+			// The new track may have older groups cached, which were already served by the previous track.
+			if start.is_some_and(|start| segment.sequence < start) {
+				continue;
 			}
 
-			if self.subscribes.lock().unwrap().get(&id).is_none() {
-				// possibly it was unsubscribed by the client
-				log::warn!("subscribe not found: {:?}", id);
-				return Ok(());
+			if let Some(next) = self.select(id, broadcast, &requested, track, segment.sequence, &mut decided) {
+				log::info!(
+					"abr switching track | track:{} from:{} to:{} group:{:?}",
+					id,
+					track.name,
+					next.name,
+					segment.sequence
+				);
+
+				*track = next;
+				start = Some(segment.sequence);
+				continue;
+			}
+
+			match self.schedule(id, segment.sequence, &mut switch) {
+				// Check if the subscribe was removed while waiting for the segment.
+				Schedule::Removed => {
+					log::info!(
+						"run_subscribe | subscription removed, exiting | track:{} sequence:{:?} priority:{} index:{}",
+						id,
+						segment.sequence,
+						segment.priority,
+						segment.index
+					);
+					break;
+				}
+				Schedule::Switched => {
+					log::info!(
+						"switched track | track:{} final:{:?}",
+						id,
+						last.as_ref().map(|last| last.sequence)
+					);
+					break;
+				}
+				Schedule::Skip => continue,
+				Schedule::Send => {
+					if last.as_ref().is_none_or(|last| segment.sequence >= last.sequence) {
+						last = Some(segment.clone());
+					}
+				}
+			}
+
+			// Don't bother sending a segment that is already too old.
+			let deadline = Self::deadline(&segment, max_latency);
+			if deadline.is_some_and(|deadline| deadline <= tokio::time::Instant::now().into_std()) {
+				log::debug!("skipping stale segment | track:{} sequence:{:?}", id, segment.sequence);
+				self.control.stats.dropped(id);
+				continue;
 			}
 
+			// Decide the priority when the segment is queued, so stateful policies see segments in order.
+			let priority = self.priority.priority(&PriorityContext {
+				id,
+				track: &track.name,
+				group: segment.sequence,
+				priority: segment.priority,
+				subscriber_priority,
+				expires: segment.expires,
+			});
+
 			// TODO only clone the fields we need
 			let this = self.clone();
 
 			tokio::spawn(async move {
-				if let Err(err) = this.run_segment(id, &mut segment).await {
+				let res = match datagram {
+					true => this.run_segment_datagram(id, &mut segment, priority, deadline).await,
+					false => this.run_segment(id, &mut segment, priority, deadline).await,
+				};
+
+				if let Err(err) = res {
 					log::warn!("failed to serve segment: {:?} {:?}", id, err)
 				}
 			});
+		}
 
+		match last {
+			Some(segment) => Ok(Some((segment.sequence, Self::final_object(segment).await))),
+			None => Ok(None),
 		}
+	}
 
-		Ok(())
+	// Wait for the segment to finish, returning the sequence of its last OBJECT.
+	async fn final_object(mut segment: segment::Subscriber) -> VarInt {
+		let mut last = VarInt::ZERO;
+
+		// An error means the segment was cut short, so report the last OBJECT that arrived.
+		while let Ok(Some(fragment)) = segment.fragment().await {
+			last = last.max(fragment.sequence);
+		}
+
+		last
 	}
 
-	async fn run_segment(&self, id: VarInt, segment: &mut segment::Subscriber) -> Result<(), SessionError> {
-		log::info!("serving segment | track:{} sequence:{:?} priority:{} index:{}", id, segment.sequence, segment.priority, segment.index);
+	// Ask the ABR policy for the track to serve, once per group.
+	fn select(
+		&self,
+		id: VarInt,
+		broadcast: &broadcast::Subscriber,
+		requested: &str,
+		track: &track::Subscriber,
+		group: VarInt,
+		decided: &mut Option<VarInt>,
+	) -> Option<track::Subscriber> {
+		let abr = self.abr.as_ref()?;
+
+		if decided.is_some_and(|decided| group <= decided) {
+			return None;
+		}
 
-		let mut stream = self.webtransport.open_uni().await?;
+		*decided = Some(group);
 
-		// Convert the u32 to a i32, since the Quinn set_priority is signed.
-		let priority = (segment.priority as i64 - i32::MAX as i64) as i32;
-		stream.set_priority(priority).ok();
+		let stats = self.stats();
+		let name = abr.select(&AbrContext {
+			id,
+			broadcast,
+			requested,
+			current: &track.name,
+			group,
+			stats: &stats,
+		})?;
+
+		if name == track.name {
+			return None;
+		}
 
-		let mut sent_chunk_count = 0u32;
-		let mut chunk_count = 0u32;
-		let chunk_sending_rate = 0;
+		// NOTE: This doesn't use switch_track, since a relay would end the upstream subscription for other subscribers.
+		match broadcast.get_track(&name) {
+			Ok(next) => Some(next),
+			Err(err) => {
+				log::warn!("abr failed to switch track | track:{} to:{} err:{:?}", id, name, err);
+				None
+			}
+		}
+	}
+
+	// Decide whether to send the next segment, handling any switch between subscriptions.
+	fn schedule(&self, id: VarInt, sequence: VarInt, switch: &mut Option<VarInt>) -> Schedule {
+		let mut subscribes = self.subscribes.lock().unwrap();
+
+		// Start at the group after the last one queued by the subscription we're replacing.
+		if let Some(from) = *switch {
+			match subscribes.get_mut(&from) {
+				Some(old) if old.last.is_some_and(|last| sequence <= last) => return Schedule::Skip,
+				Some(old) => {
+					old.boundary = Some(sequence);
+					*switch = None;
+				}
+
+				// The old subscription already ended.
+				None => *switch = None,
+			}
+		}
+
+		let serve = match subscribes.get_mut(&id) {
+			Some(serve) => serve,
+			None => return Schedule::Removed,
+		};
+
+		// The new subscription took over from this group onwards.
+		if serve.boundary.is_some_and(|boundary| sequence >= boundary) {
+			return Schedule::Switched;
+		}
+
+		serve.last = Some(serve.last.map_or(sequence, |last| last.max(sequence)));
+
+		Schedule::Send
+	}
+
+	// The time after which a segment is useless: when it expires, or once it's older than the max latency.
+	fn deadline(segment: &segment::Subscriber, max_latency: Option<time::Duration>) -> Option<time::Instant> {
+		let latency = max_latency.map(|latency| segment.created() + latency);
+
+		match (segment.deadline(), latency) {
+			(Some(expires), Some(latency)) => Some(expires.min(latency)),
+			(expires, latency) => expires.or(latency),
+		}
+	}
+
+	async fn run_segment(
+		&self,
+		id: VarInt,
+		segment: &mut segment::Subscriber,
+		priority: i32,
+		deadline: Option<time::Instant>,
+	) -> Result<(), SessionError> {
+		log::info!(
+			"serving segment | track:{} sequence:{:?} priority:{} index:{}",
+			id,
+			segment.sequence,
+			segment.priority,
+			segment.index
+		);
+
+		let mut stream = self.transport.open_uni().await?;
+		self.control.stats.stream_opened();
+
+		stream.set_priority(priority);
+
+		let deadline = match deadline {
+			Some(deadline) => deadline,
+			None => return self.write_segment(id, segment, &mut stream).await,
+		};
+
+		let expired = {
+			let write = self.write_segment(id, segment, &mut stream);
+
+			tokio::select! {
+				res = write => {
+					res?;
+					false
+				},
+				_ = tokio::time::sleep_until(deadline.into()) => true,
+			}
+		};
+
+		// Abandon the rest of the segment so it doesn't delay newer segments.
+		if expired {
+			log::debug!("segment expired | track:{} sequence:{:?}", id, segment.sequence);
+			stream.reset(CacheError::Expired.code());
+			self.control.stats.dropped(id);
+		}
+
+		Ok(())
+	}
 
-		let mut internal_buffer = Vec::new();
+	async fn write_segment(
+		&self,
+		id: VarInt,
+		segment: &mut segment::Subscriber,
+		stream: &mut Box<dyn transport::SendStream>,
+	) -> Result<(), SessionError> {
+		let mut writer = Writer::new(stream, &self.write);
+		let mut encoder = self.control.object_encoder(id);
+		let mut chunk_count = 0u32;
 
 		while let Some(mut fragment) = segment.fragment().await? {
-			log::info!("serving fragment | track:{} sequence:{:?} segment: {:?}", id, fragment.sequence, segment.sequence);
-			sent_chunk_count = 0;
-			chunk_count = 0;
+			log::info!(
+				"serving fragment | track:{} sequence:{:?} segment: {:?}",
+				id,
+				fragment.sequence,
+				segment.sequence
+			);
 
 			// TODO: use real NTP timestamp
 			//
 			let ntp_timestamp = match VarInt::try_from(chrono::Utc::now().timestamp_millis() as u64) {
 				Ok(ntp_timestamp) => ntp_timestamp,
-        		Err(e) => return Err(SessionError::BoundsExceeded(e)),
+				Err(e) => return Err(SessionError::BoundsExceeded(e)),
 			};
 
-			let object = message::Object {
+			let mut object = message::Object {
 				track: id,
 
 				// Properties of the segment
@@ -331,72 +797,172 @@ impl Publisher {
 				size: fragment.size.map(VarInt::try_from).transpose()?,
 			};
 
-			object
-			.encode(&mut stream, &self.control.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
-
-			// TODO:
-			// parse boxes
-			// if box length > 200000, send it in one chunk
-			// waiting room
-			while let Some(chunk) = fragment.chunk().await?
-			{
-				log::trace!("writing chunk of track: {:?}", chunk);
-				if chunk.len() > 0 {
-					chunk_count += 1;
-					if chunk_sending_rate == 0 {
-						let result = stream.write_all(&chunk).await;
-						if let Err(err) = result {
-							log::warn!("failed to write some chunks ({:?}:{:?} last chunk: {}): {:?}", id, segment.index, chunk_count, err);
-						} else {
-							// log::debug!("sent chunk | track:{} sequence:{:?} segment: {} chunk_read:{}", id, segment.sequence, segment.index, chunk_count);
-						}
-						sent_chunk_count += 1;
-					} else {
-						internal_buffer.append(chunk.to_vec().as_mut());
-					}
+			// Newer drafts require a size, so buffer unbounded fragments before writing the header.
+			if object.size.is_none() && encoder.requires_size() {
+				let mut payload = BytesMut::new();
+				while let Some(chunk) = fragment.chunk().await? {
+					payload.extend_from_slice(&chunk);
 				}
-				if chunk_sending_rate > 0 && chunk_count % chunk_sending_rate == 0 {
-					let result = stream.write_all(&internal_buffer).await;
-					if let Err(err) = result {
-						log::warn!("failed to write some chunks ({:?}:{:?} last chunk: {}): {:?}", id, segment.index, chunk_count, err);
-					} else {
-						// log::debug!("sent chunk | track:{} sequence:{:?} segment: {} chunk_read:{}", id, segment.sequence, segment.index, chunk_count);
+
+				object.size = Some(VarInt::try_from(payload.len())?);
+
+				// The header goes through the writer too, so it can be coalesced with the payload.
+				let mut header = Vec::new();
+				encoder.encode(&mut header, &object).await?;
+				writer.write(header.into()).await?;
+
+				chunk_count += 1;
+				self.control.stats.served(id, 1, payload.len() as u64);
+				writer.write(payload.freeze()).await?;
+
+				continue;
+			}
+
+			let mut header = Vec::new();
+			encoder.encode(&mut header, &object).await?;
+			writer.write(header.into()).await?;
+			self.control.stats.served(id, 1, 0);
+
+			loop {
+				// Flush any coalesced chunks if the next one takes too long.
+				let next = tokio::select! {
+					chunk = fragment.chunk() => Some(chunk?),
+					_ = writer.expired() => None,
+				};
+
+				let chunk = match next {
+					Some(Some(chunk)) => chunk,
+					Some(None) => break,
+					None => {
+						writer.flush().await?;
+						continue;
 					}
-					sent_chunk_count += chunk_sending_rate;
-					internal_buffer.clear();
+				};
+
+				log::trace!("writing chunk of track: {:?}", chunk);
+				if !chunk.is_empty() {
+					chunk_count += 1;
+					self.control.stats.served(id, 0, chunk.len() as u64);
+					writer.write(chunk).await?;
 				}
 			}
-			// log::debug!("finished fragment |track:{:?} segment: {} chunk_read:{} chunk_sent:{}", id, segment.sequence, chunk_count, sent_chunk_count);
 		}
 
+		writer.flush().await?;
+
 		if chunk_count == 0 {
 			log::warn!("no chunks sent for track: {:?}", id);
 			return Err(SessionError::Unknown("no chunks sent".to_string()));
-		} else if chunk_sending_rate > 0 && sent_chunk_count < chunk_count {
-			// we did not send the last remaining chunks
-			log::warn!("not all chunks sent for track: {:?} chunk count: {:?} sent: {}", id, chunk_count, sent_chunk_count);
-			let result = stream.write_all(&internal_buffer).await;
-			if let Err(err) = result {
-				log::warn!("failed to write some chunks ({:?}:{:?} last chunk: {}): {:?}", id, segment.index, chunk_count, err);
-			} else {
-				// log::debug!("sent chunk | track:{} sequence:{:?} segment: {} chunk_read:{} chunk_sent:{}", id, segment.sequence, segment.index, chunk_count, sent_chunk_count);
+		}
+
+		Ok(())
+	}
+
+	// Send each OBJECT as a datagram, falling back to a stream for the rest of the segment once one doesn't fit.
+	async fn run_segment_datagram(
+		&self,
+		id: VarInt,
+		segment: &mut segment::Subscriber,
+		priority: i32,
+		deadline: Option<time::Instant>,
+	) -> Result<(), SessionError> {
+		log::info!(
+			"serving segment as datagrams | track:{} sequence:{:?} priority:{}",
+			id,
+			segment.sequence,
+			segment.priority
+		);
+
+		let mut stream: Option<Box<dyn transport::SendStream>> = None;
+		let mut encoder = self.control.object_encoder(id);
+
+		while let Some(mut fragment) = segment.fragment().await? {
+			// Datagrams can't be split, so buffer the entire OBJECT.
+			let mut payload = BytesMut::new();
+			while let Some(chunk) = fragment.chunk().await? {
+				payload.extend_from_slice(&chunk);
 			}
-			internal_buffer.clear();
+
+			// Drop the rest of the segment once it's too old.
+			if deadline.is_some_and(|deadline| deadline <= tokio::time::Instant::now().into_std()) {
+				log::debug!("segment expired | track:{} sequence:{:?}", id, segment.sequence);
+
+				if let Some(mut stream) = stream {
+					stream.reset(CacheError::Expired.code());
+				}
+
+				self.control.stats.dropped(id);
+				return Ok(());
+			}
+
+			let ntp_timestamp = match VarInt::try_from(chrono::Utc::now().timestamp_millis() as u64) {
+				Ok(ntp_timestamp) => ntp_timestamp,
+				Err(e) => return Err(SessionError::BoundsExceeded(e)),
+			};
+
+			let mut object = message::Object {
+				track: id,
+				group: segment.sequence,
+				priority: segment.priority,
+				expires: segment.expires,
+				sequence: fragment.sequence,
+				ntp_timestamp: Some(ntp_timestamp),
+				size: None,
+			};
+
+			if stream.is_none() {
+				// The payload runs until the end of the datagram, so the size is omitted.
+				let mut datagram = Vec::with_capacity(payload.len() + 32);
+				object.encode(&mut datagram, &self.control.ext).await?;
+				datagram.extend_from_slice(&payload);
+
+				// The path MTU can change, so check the limit for every OBJECT.
+				if let Some(max) = self.transport.max_datagram_size().filter(|max| datagram.len() <= *max) {
+					log::trace!("sending datagram: size={} max={}", datagram.len(), max);
+
+					self.transport.send_datagram(datagram.into())?;
+					self.control.stats.datagram_sent();
+					self.control.stats.served(id, 1, payload.len() as u64);
+
+					continue;
+				}
+
+				log::debug!(
+					"falling back to stream | track:{} sequence:{:?} size:{}",
+					id,
+					segment.sequence,
+					datagram.len()
+				);
+
+				let mut send = self.transport.open_uni().await?;
+				self.control.stats.stream_opened();
+
+				send.set_priority(priority);
+				stream = Some(send);
+			}
+
+			// Multiple OBJECTs share the stream, so each one needs a size.
+			let send = stream.as_mut().unwrap();
+			object.size = Some(VarInt::try_from(payload.len())?);
+
+			encoder.encode(send, &object).await?;
+			self.control.stats.served(id, 1, payload.len() as u64);
+			send.write_chunk(payload.freeze()).await?;
 		}
 
 		Ok(())
 	}
 
 	async fn recv_unsubscribe(&mut self, msg: &message::Unsubscribe) -> Result<(), SessionError> {
-		let abort = self
+		let serve = self
 			.subscribes
 			.lock()
 			.unwrap()
 			.remove(&msg.id)
 			.ok_or(CacheError::NotFound)?;
-		abort.abort();
+		serve.abort.abort();
+
+		self.control.stats.unserve(msg.id);
 
 		self.reset_subscribe(msg.id, CacheError::Stop).await
 	}
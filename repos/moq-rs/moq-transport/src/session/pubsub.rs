@@ -0,0 +1,163 @@
+use std::{sync::Arc, time};
+
+use crate::{
+	cache::{broadcast, track},
+	message::Message,
+	session::{
+		AbrPolicy, Control, PriorityPolicy, Publisher, Router, SessionError, Stats, SubscribeOptions, Subscriber,
+		Subscription, WriteStrategy,
+	},
+	transport,
+};
+
+/// Publishes and subscribes over the same session, negotiated with [Role::Both](crate::setup::Role::Both).
+///
+/// Both halves share a single control stream, so incoming messages are demultiplexed by kind:
+/// messages sent by a subscriber are handled by the [Publisher] half and vice versa.
+#[derive(Clone, Debug)]
+pub struct PubSub {
+	publisher: Publisher,
+	subscriber: Subscriber,
+	control: Control,
+}
+
+impl PubSub {
+	pub(crate) fn new(
+		transport: Arc<dyn transport::Session>,
+		control: Control,
+		publish: broadcast::Subscriber,
+		subscribe: broadcast::Publisher,
+	) -> Self {
+		let publisher = Publisher::new(transport.clone(), control.clone(), publish);
+		let subscriber = Subscriber::new(transport, control.clone(), subscribe);
+
+		Self {
+			publisher,
+			subscriber,
+			control,
+		}
+	}
+
+	/// Serve SUBSCRIBEs with a non-empty namespace using the provided router.
+	pub fn with_router(mut self, router: Arc<dyn Router>) -> Self {
+		self.publisher = self.publisher.with_router(router);
+		self
+	}
+
+	/// Prioritize the segments we serve using the provided policy, see [Publisher::with_priority].
+	pub fn with_priority(mut self, policy: Arc<dyn PriorityPolicy>) -> Self {
+		self.publisher = self.publisher.with_priority(policy);
+		self
+	}
+
+	/// Batch or pace the writes of the segments we serve, see [Publisher::with_write_strategy].
+	pub fn with_write_strategy(mut self, strategy: WriteStrategy) -> Self {
+		self.publisher = self.publisher.with_write_strategy(strategy);
+		self
+	}
+
+	/// Switch the subscriptions we serve between renditions, see [Publisher::with_abr].
+	pub fn with_abr(mut self, policy: Arc<dyn AbrPolicy>) -> Self {
+		self.publisher = self.publisher.with_abr(policy);
+		self
+	}
+
+	/// Ask the remote to send the named track with the given priority, see [Subscriber::with_track_priority].
+	pub fn with_track_priority(mut self, track: &str, priority: u8) -> Self {
+		self.subscriber = self.subscriber.with_track_priority(track, priority);
+		self
+	}
+
+	/// Ask the remote to drop old groups of the named track, see [Subscriber::with_track_max_latency].
+	pub fn with_track_max_latency(mut self, track: &str, latency: time::Duration) -> Self {
+		self.subscriber = self.subscriber.with_track_max_latency(track, latency);
+		self
+	}
+
+	/// Bound the size of each OBJECT we receive, see [Subscriber::with_max_fragment_size].
+	pub fn with_max_fragment_size(mut self, size: usize) -> Self {
+		self.subscriber = self.subscriber.with_max_fragment_size(size);
+		self
+	}
+
+	/// Ask for the named tracks to be delivered as datagrams, see [Subscriber::with_datagrams].
+	pub fn with_datagrams<I, S>(mut self, tracks: I) -> Self
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		self.subscriber = self.subscriber.with_datagrams(tracks);
+		self
+	}
+
+	/// Subscribe to the named track and wait for SUBSCRIBE_OK, see [Subscriber::subscribe].
+	pub async fn subscribe(&self, name: &str, options: SubscribeOptions) -> Result<Subscription, SessionError> {
+		self.subscriber.subscribe(name, options).await
+	}
+
+	/// Remove the tracks fulfilled by this session, see [Subscriber::detach].
+	pub fn detach(&self) -> Vec<track::Publisher> {
+		self.subscriber.detach()
+	}
+
+	/// Send a GOAWAY, asking the client to reconnect to `url`, or the same URL if it's empty.
+	pub async fn go_away(&self, url: &str) -> Result<(), SessionError> {
+		self.publisher.go_away(url).await
+	}
+
+	/// Resolves once the remote sends a GOAWAY, see [Subscriber::going_away].
+	pub async fn going_away(&self) -> String {
+		self.publisher.going_away().await
+	}
+
+	/// Snapshot the QUIC metrics and the counters for both halves.
+	pub fn stats(&self) -> Stats {
+		self.publisher.stats()
+	}
+
+	pub async fn run(self) -> Result<(), SessionError> {
+		let inbound = self.clone().run_inbound();
+		let streams = self.subscriber.clone().run_streams();
+		let datagrams = self.subscriber.clone().run_datagrams();
+		let source = self.subscriber.clone().run_source();
+
+		// Return the first error.
+		let res = tokio::select! {
+			res = inbound => res,
+			res = streams => res,
+			res = datagrams => res,
+			res = source => res,
+		};
+
+		// Terminate all active subscribes on error.
+		self.publisher.terminate();
+		self.subscriber.terminate(&res);
+
+		res
+	}
+
+	async fn run_inbound(mut self) -> Result<(), SessionError> {
+		loop {
+			let msg = self.control.recv().await?;
+
+			log::info!("message received: {:?}", msg);
+			if let Err(err) = self.recv_message(&msg).await {
+				log::warn!("message error: {:?} {:?}", err, msg);
+			}
+		}
+	}
+
+	async fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
+		match msg {
+			// Sent by the remote subscriber, so handled by our publisher.
+			Message::Subscribe(_)
+			| Message::Unsubscribe(_)
+			| Message::AnnounceOk(_)
+			| Message::AnnounceError(_)
+			| Message::Probe(_) => self.publisher.recv_message(msg).await,
+
+			// Everything else is sent by the remote publisher.
+			_ => self.subscriber.recv_message(msg),
+		}
+	}
+}
@@ -1,7 +1,13 @@
-use super::{Control, Publisher, SessionError, Subscriber};
-use crate::{cache::broadcast, setup};
+use super::{Control, PubSub, Publisher, SessionError, Subscriber};
+use std::sync::Arc;
 
-use webtransport_quinn::{RecvStream, SendStream, Session};
+use crate::{
+	cache::broadcast,
+	coding::Params,
+	setup,
+	transport::{self, RecvStream, SendStream},
+	VarInt,
+};
 
 /// An endpoint that accepts connections, publishing and/or consuming live streams.
 pub struct Server {}
@@ -10,14 +16,22 @@ impl Server {
 	/// Accept an established Webtransport session, performing the MoQ handshake.
 	///
 	/// This returns a [Request] half-way through the handshake that allows the application to accept or deny the session.
-	pub async fn accept(session: Session) -> Result<Request, SessionError> {
+	pub async fn accept<S: transport::Session>(session: S) -> Result<Request, SessionError> {
 		let mut control = session.accept_bi().await?;
 
 		let mut client = setup::Client::decode(&mut control.1).await?;
 
 		log::debug!("received client SETUP: {:?}", client);
 
-		if client.versions.contains(&setup::Version::DRAFT_01) {
+		let version = if client.versions.contains(&setup::Version::DRAFT_07) {
+			// Newer drafts don't support extensions, but the namespace is always present.
+			client.extensions = setup::Extensions {
+				subscribe_split: true,
+				..Default::default()
+			};
+
+			setup::Version::DRAFT_07
+		} else if client.versions.contains(&setup::Version::DRAFT_01) {
 			// We always require subscriber ID.
 			client.extensions.require_subscriber_id()?;
 
@@ -26,8 +40,10 @@ impl Server {
 				client.extensions.require_object_expires()?;
 			}
 
-		// We don't require SUBSCRIBE_SPLIT since it's easy enough to support, but it's clearly an oversight.
-		// client.extensions.require(&Extension::SUBSCRIBE_SPLIT)?;
+			// We don't require SUBSCRIBE_SPLIT since it's easy enough to support, but it's clearly an oversight.
+			// client.extensions.require(&Extension::SUBSCRIBE_SPLIT)?;
+
+			setup::Version::DRAFT_01
 		} else if client.versions.contains(&setup::Version::KIXEL_01) {
 			// Extensions didn't exist in KIXEL_01, so we set them manually.
 			client.extensions = setup::Extensions {
@@ -36,17 +52,27 @@ impl Server {
 				subscribe_split: true,
 				switch_track_id: true,
 				ntp_timestamp: true,
+				probe: false,
+				datagram: false,
 			};
+
+			setup::Version::KIXEL_01
 		} else {
 			return Err(SessionError::Version(
 				client.versions,
-				[setup::Version::DRAFT_01, setup::Version::KIXEL_01].into(),
+				[
+					setup::Version::DRAFT_07,
+					setup::Version::DRAFT_01,
+					setup::Version::KIXEL_01,
+				]
+				.into(),
 			));
-		}
+		};
 
 		Ok(Request {
-			session,
+			session: Arc::new(session),
 			client,
+			version,
 			control,
 		})
 	}
@@ -54,45 +80,59 @@ impl Server {
 
 /// A partially complete MoQ Transport handshake.
 pub struct Request {
-	session: Session,
+	session: Arc<dyn transport::Session>,
 	client: setup::Client,
-	control: (SendStream, RecvStream),
+	version: setup::Version,
+	control: (Box<dyn SendStream>, Box<dyn RecvStream>),
 }
 
 impl Request {
 	/// Accept the session as a publisher, using the provided broadcast to serve subscriptions.
 	pub async fn publisher(mut self, source: broadcast::Subscriber) -> Result<Publisher, SessionError> {
-		let setup = self.setup(setup::Role::Publisher)?;
+		let setup = self.setup(setup::Role::Publisher).await?;
 		setup.encode(&mut self.control.0).await?;
 
-		let control = Control::new(self.control.0, self.control.1, setup.extensions);
+		let control = Control::new(self.control.0, self.control.1, setup.version, setup.extensions);
 		let publisher = Publisher::new(self.session, control, source);
 		Ok(publisher)
 	}
 
 	/// Accept the session as a subscriber only.
 	pub async fn subscriber(mut self, source: broadcast::Publisher) -> Result<Subscriber, SessionError> {
-		let setup = self.setup(setup::Role::Subscriber)?;
+		let setup = self.setup(setup::Role::Subscriber).await?;
 		setup.encode(&mut self.control.0).await?;
 
-		let control = Control::new(self.control.0, self.control.1, setup.extensions);
+		let control = Control::new(self.control.0, self.control.1, setup.version, setup.extensions);
 		let subscriber = Subscriber::new(self.session, control, source);
 		Ok(subscriber)
 	}
 
-	// TODO Accept the session and perform both roles.
-	/*
-	pub async fn accept(self) -> anyhow::Result<(Publisher, Subscriber)> {
-		self.ok(setup::Role::Both).await
+	/// Accept the session as both a publisher and subscriber, sharing the control stream.
+	pub async fn pubsub(
+		mut self,
+		publish: broadcast::Subscriber,
+		subscribe: broadcast::Publisher,
+	) -> Result<PubSub, SessionError> {
+		let setup = self.setup(setup::Role::Both).await?;
+		setup.encode(&mut self.control.0).await?;
+
+		let control = Control::new(self.control.0, self.control.1, setup.version, setup.extensions);
+		let pubsub = PubSub::new(self.session, control, publish, subscribe);
+		Ok(pubsub)
 	}
-	*/
 
-	fn setup(&mut self, role: setup::Role) -> Result<setup::Server, SessionError> {
+	async fn setup(&mut self, role: setup::Role) -> Result<setup::Server, SessionError> {
+		let mut params = Params::default();
+		if self.version.is_ietf() {
+			// Newer drafts only allow SUBSCRIBE IDs below this limit, which defaults to zero.
+			params.set(setup::MAX_SUBSCRIBE_ID, VarInt::from_u32(u32::MAX)).await?;
+		}
+
 		let server = setup::Server {
 			role,
-			version: setup::Version::DRAFT_01,
+			version: self.version,
 			extensions: self.client.extensions.clone(),
-			params: Default::default(),
+			params,
 		};
 
 		log::debug!("sending server SETUP: {:?}", server);
@@ -115,4 +155,14 @@ impl Request {
 	pub fn role(&self) -> setup::Role {
 		self.client.role
 	}
+
+	/// The path sent by the client, only used directly over QUIC, see [transport::quic].
+	pub fn path(&self) -> Option<&str> {
+		self.client.path.as_deref()
+	}
+
+	/// The version negotiated with the client.
+	pub fn version(&self) -> setup::Version {
+		self.version
+	}
 }
@@ -0,0 +1,273 @@
+use std::{
+	collections::HashMap,
+	fmt,
+	sync::{Arc, Mutex},
+	time,
+};
+
+use crate::{cache::Arrival, transport, VarInt};
+
+/// A snapshot of the transport metrics for a session, see [Publisher::stats](super::Publisher::stats).
+///
+/// The transport metrics cover the entire connection, while the subscription counters only cover active subscriptions.
+#[derive(Clone, Debug)]
+pub struct Stats {
+	/// When this snapshot was taken.
+	pub timestamp: time::Instant,
+
+	/// The current best estimate of the round-trip time.
+	pub rtt: time::Duration,
+
+	/// The current congestion window in bytes.
+	pub cwnd: u64,
+
+	/// The number of congestion events.
+	pub congestion_events: u64,
+
+	/// The number of UDP bytes sent and received, including retransmissions and QUIC overhead.
+	pub bytes_sent: u64,
+	pub bytes_received: u64,
+
+	/// The number of bytes declared lost.
+	pub bytes_lost: u64,
+
+	/// The number of packets sent and declared lost.
+	pub packets_sent: u64,
+	pub packets_lost: u64,
+
+	/// The number of data streams we opened, and accepted from the remote.
+	pub streams_opened: u64,
+	pub streams_accepted: u64,
+
+	/// The number of OBJECT datagrams we sent, and received from the remote.
+	pub datagrams_sent: u64,
+	pub datagrams_received: u64,
+
+	/// The number of OBJECTs we sent, and received from the remote, including finished subscriptions.
+	pub objects_sent: u64,
+	pub objects_received: u64,
+
+	/// Subscriptions we're serving, keyed by the remote's subscribe ID.
+	pub served: HashMap<VarInt, SubscriptionStats>,
+
+	/// Subscriptions we've made, keyed by our subscribe ID.
+	pub subscribed: HashMap<VarInt, SubscriptionStats>,
+}
+
+impl Stats {
+	pub(crate) fn new(session: &dyn transport::Session, counters: &Counters) -> Self {
+		let path = session.stats();
+		let state = counters.state.lock().unwrap();
+
+		Self {
+			timestamp: time::Instant::now(),
+			rtt: path.rtt,
+			cwnd: path.cwnd,
+			congestion_events: path.congestion_events,
+			bytes_sent: path.bytes_sent,
+			bytes_received: path.bytes_received,
+			bytes_lost: path.bytes_lost,
+			packets_sent: path.packets_sent,
+			packets_lost: path.packets_lost,
+			streams_opened: state.streams_opened,
+			streams_accepted: state.streams_accepted,
+			datagrams_sent: state.datagrams_sent,
+			datagrams_received: state.datagrams_received,
+			objects_sent: state.objects_sent,
+			objects_received: state.objects_received,
+			served: state.served.clone(),
+			subscribed: state.subscribed.clone(),
+		}
+	}
+
+	/// The fraction of sent packets that were declared lost.
+	pub fn loss(&self) -> f64 {
+		match self.packets_sent {
+			0 => 0.0,
+			sent => self.packets_lost as f64 / sent as f64,
+		}
+	}
+
+	/// The send throughput in bits per second since an earlier snapshot.
+	pub fn throughput(&self, earlier: &Stats) -> u64 {
+		let elapsed = self.timestamp.saturating_duration_since(earlier.timestamp);
+		let bytes = self.bytes_sent.saturating_sub(earlier.bytes_sent);
+
+		match elapsed.as_micros() {
+			0 => 0,
+			micros => (bytes as u128 * 8 * 1_000_000 / micros).min(u64::MAX as u128) as u64,
+		}
+	}
+}
+
+impl fmt::Display for Stats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"rtt={:?} cwnd={} sent={} received={} loss={:.2}% streams={}/{}",
+			self.rtt,
+			self.cwnd,
+			self.bytes_sent,
+			self.bytes_received,
+			self.loss() * 100.0,
+			self.streams_opened,
+			self.streams_accepted,
+		)
+	}
+}
+
+/// Periodically log the stats returned by `stats`, such as [Publisher::stats](super::Publisher::stats), never returning.
+///
+/// The session stats and throughput are logged at info level, and each subscription at debug level.
+/// Nothing is logged if `interval` is zero.
+pub async fn log_stats<F: Fn() -> Stats>(interval: time::Duration, stats: F) {
+	if interval.is_zero() {
+		return std::future::pending().await;
+	}
+
+	let mut interval = tokio::time::interval(interval);
+	interval.tick().await;
+
+	let mut last = stats();
+
+	loop {
+		interval.tick().await;
+
+		let next = stats();
+		log::info!("session stats: {} throughput={}bps", next, next.throughput(&last));
+
+		for (id, sub) in next.served.iter().chain(next.subscribed.iter()) {
+			log::debug!("subscription stats: id={} {}", id, sub);
+		}
+
+		last = next;
+	}
+}
+
+/// Counters for a single subscription.
+#[derive(Clone, Debug, Default)]
+pub struct SubscriptionStats {
+	/// The name of the track.
+	pub name: String,
+
+	/// The number of OBJECTs and payload bytes transferred.
+	pub objects: u64,
+	pub bytes: u64,
+
+	/// The number of groups that were skipped or reset because their deadline passed.
+	pub dropped: u64,
+
+	/// The number of groups and OBJECTs that were missing when a later one arrived, which may still arrive.
+	pub gaps: u64,
+
+	/// The number of groups and OBJECTs that arrived after a later one.
+	pub reordered: u64,
+}
+
+impl fmt::Display for SubscriptionStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"name={} objects={} bytes={} dropped={} gaps={} reordered={}",
+			self.name, self.objects, self.bytes, self.dropped, self.gaps, self.reordered
+		)
+	}
+}
+
+#[derive(Debug, Default)]
+struct CountersState {
+	streams_opened: u64,
+	streams_accepted: u64,
+	datagrams_sent: u64,
+	datagrams_received: u64,
+	objects_sent: u64,
+	objects_received: u64,
+	served: HashMap<VarInt, SubscriptionStats>,
+	subscribed: HashMap<VarInt, SubscriptionStats>,
+}
+
+/// The counters shared by the [Publisher](super::Publisher) and [Subscriber](super::Subscriber) halves of a session.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Counters {
+	state: Arc<Mutex<CountersState>>,
+}
+
+impl Counters {
+	pub fn stream_opened(&self) {
+		self.state.lock().unwrap().streams_opened += 1;
+	}
+
+	pub fn stream_accepted(&self) {
+		self.state.lock().unwrap().streams_accepted += 1;
+	}
+
+	pub fn datagram_sent(&self) {
+		self.state.lock().unwrap().datagrams_sent += 1;
+	}
+
+	pub fn datagram_received(&self) {
+		self.state.lock().unwrap().datagrams_received += 1;
+	}
+
+	pub fn serve(&self, id: VarInt, name: &str) {
+		let stats = SubscriptionStats {
+			name: name.to_string(),
+			..Default::default()
+		};
+
+		self.state.lock().unwrap().served.insert(id, stats);
+	}
+
+	pub fn served(&self, id: VarInt, objects: u64, bytes: u64) {
+		let mut state = self.state.lock().unwrap();
+		state.objects_sent += objects;
+
+		if let Some(stats) = state.served.get_mut(&id) {
+			stats.objects += objects;
+			stats.bytes += bytes;
+		}
+	}
+
+	pub fn dropped(&self, id: VarInt) {
+		if let Some(stats) = self.state.lock().unwrap().served.get_mut(&id) {
+			stats.dropped += 1;
+		}
+	}
+
+	pub fn unserve(&self, id: VarInt) {
+		self.state.lock().unwrap().served.remove(&id);
+	}
+
+	pub fn subscribe(&self, id: VarInt, name: &str) {
+		let stats = SubscriptionStats {
+			name: name.to_string(),
+			..Default::default()
+		};
+
+		self.state.lock().unwrap().subscribed.insert(id, stats);
+	}
+
+	pub fn subscribed(&self, id: VarInt, objects: u64, bytes: u64) {
+		let mut state = self.state.lock().unwrap();
+		state.objects_received += objects;
+
+		if let Some(stats) = state.subscribed.get_mut(&id) {
+			stats.objects += objects;
+			stats.bytes += bytes;
+		}
+	}
+
+	pub fn arrival(&self, id: VarInt, arrival: Arrival) {
+		if let Some(stats) = self.state.lock().unwrap().subscribed.get_mut(&id) {
+			match arrival {
+				Arrival::Gap(missing) => stats.gaps += missing,
+				Arrival::Reordered => stats.reordered += 1,
+				Arrival::InOrder | Arrival::Duplicate => {}
+			}
+		}
+	}
+
+	pub fn unsubscribe(&self, id: VarInt) {
+		self.state.lock().unwrap().subscribed.remove(&id);
+	}
+}
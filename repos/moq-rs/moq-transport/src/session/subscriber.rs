@@ -1,30 +1,57 @@
-use webtransport_quinn::{RecvStream, Session};
-
 use std::{
-	collections::HashMap,
+	collections::{hash_map, HashMap, HashSet},
+	io,
 	sync::{atomic, Arc, Mutex},
+	time,
 };
 
+use bytes::Bytes;
+
 use crate::{
-	cache::{broadcast, segment, track, CacheError},
-	coding::DecodeError,
+	cache::{broadcast, fragment, segment, track, CacheError},
+	coding::{self, DecodeError},
 	message,
 	message::Message,
-	session::{Control, SessionError},
-	VarInt,
+	session::{
+		probe::Probe, subscription::Status, Control, ProbeResult, SessionError, Stats, SubscribeOptions, Subscription,
+	},
+	transport::{self, RecvStream},
+	MoqError, VarInt,
 };
 
 /// Receives broadcasts over the network, automatically handling subscriptions and caching.
 // TODO Clone specific fields when a task actually needs it.
 #[derive(Clone, Debug)]
 pub struct Subscriber {
-	// The webtransport session.
-	webtransport: Session,
+	// The underlying transport session.
+	transport: Arc<dyn transport::Session>,
 
 	// The list of active subscriptions, each guarded by an mutex.
 	subscribes: Arc<Mutex<HashMap<VarInt, track::Publisher>>>,
 
+	// The responses for explicit subscriptions, created by subscribe().
+	statuses: Arc<Mutex<HashMap<VarInt, Status>>>,
+
+	// Outstanding PROBEs, which share the ID space with subscriptions.
+	probes: Arc<Mutex<HashMap<VarInt, Probe>>>,
+
+	// The names of tracks that should be delivered as datagrams.
+	datagrams: Arc<HashSet<String>>,
+
+	// The priority to request for each track name, where smaller values are sent first.
+	priorities: Arc<HashMap<String, u8>>,
+
+	// The maximum latency to request for each track name.
+	latencies: Arc<HashMap<String, time::Duration>>,
+
+	// The maximum size of each received OBJECT, used to bound the memory of each fragment.
+	max_fragment_size: Option<usize>,
+
+	// The latest group received via datagrams for each subscription, which is finished when replaced.
+	groups: Arc<Mutex<HashMap<VarInt, segment::Publisher>>>,
+
 	// The sequence number for the next subscription.
+	// Zero is never used, since a switch_track_id of zero means no switch.
 	next: Arc<atomic::AtomicU32>,
 
 	// A channel for sending messages.
@@ -35,27 +62,94 @@ pub struct Subscriber {
 }
 
 impl Subscriber {
-	pub(crate) fn new(webtransport: Session, control: Control, source: broadcast::Publisher) -> Self {
+	pub(crate) fn new(transport: Arc<dyn transport::Session>, control: Control, source: broadcast::Publisher) -> Self {
 		Self {
-			webtransport,
+			transport,
 			subscribes: Default::default(),
-			next: Default::default(),
+			statuses: Default::default(),
+			probes: Default::default(),
+			datagrams: Default::default(),
+			priorities: Default::default(),
+			latencies: Default::default(),
+			max_fragment_size: None,
+			groups: Default::default(),
+			next: Arc::new(atomic::AtomicU32::new(1)),
 			control,
 			source,
 		}
 	}
 
+	/// Ask for the named tracks to be delivered as datagrams, which is lower latency but unreliable.
+	///
+	/// OBJECTs that don't fit in a datagram are sent over a stream instead.
+	/// Requires the datagram extension, otherwise these tracks are delivered over streams like any other.
+	pub fn with_datagrams<I, S>(mut self, tracks: I) -> Self
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		self.datagrams = Arc::new(tracks.into_iter().map(Into::into).collect());
+		self
+	}
+
+	/// Ask the publisher to send the named track with the given priority, where **smaller** values are sent first.
+	///
+	/// How this is combined with the publisher's own priorities depends on its [PriorityPolicy](super::PriorityPolicy).
+	pub fn with_track_priority(mut self, track: &str, priority: u8) -> Self {
+		Arc::make_mut(&mut self.priorities).insert(track.to_string(), priority);
+		self
+	}
+
+	/// Ask the publisher to drop any group of the named track once it's older than `latency`, instead of sending it late.
+	pub fn with_track_max_latency(mut self, track: &str, latency: time::Duration) -> Self {
+		Arc::make_mut(&mut self.latencies).insert(track.to_string(), latency);
+		self
+	}
+
+	/// Reset any stream with an OBJECT larger than `size`, so a publisher can't make us cache an unbounded fragment.
+	pub fn with_max_fragment_size(mut self, size: usize) -> Self {
+		self.max_fragment_size = Some(size);
+		self
+	}
+
 	pub async fn run(self) -> Result<(), SessionError> {
 		let inbound = self.clone().run_inbound();
 		let streams = self.clone().run_streams();
+		let datagrams = self.clone().run_datagrams();
 		let source = self.clone().run_source();
 
 		// Return the first error.
-		tokio::select! {
+		let res = tokio::select! {
 			res = inbound => res,
 			res = streams => res,
+			res = datagrams => res,
 			res = source => res,
-		}
+		};
+
+		// Fail any outstanding requests so they don't wait forever.
+		self.terminate(&res);
+
+		res
+	}
+
+	// Close any outstanding subscriptions and probes with the session error.
+	pub(crate) fn terminate(&self, res: &Result<(), SessionError>) {
+		let err = match res {
+			Ok(()) => CacheError::Closed,
+			Err(err) => CacheError::Reset(err.code()),
+		};
+
+		self.statuses
+			.lock()
+			.unwrap()
+			.drain()
+			.for_each(|(_, status)| status.close(err.clone()));
+
+		self.probes
+			.lock()
+			.unwrap()
+			.drain()
+			.for_each(|(_, probe)| probe.close(err.clone()));
 	}
 
 	async fn run_inbound(mut self) -> Result<(), SessionError> {
@@ -69,31 +163,215 @@ impl Subscriber {
 		}
 	}
 
-	fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
+	pub(crate) fn recv_message(&mut self, msg: &Message) -> Result<(), SessionError> {
 		match msg {
-			Message::Announce(_) => Ok(()),       // don't care
-			Message::Unannounce(_) => Ok(()),     // also don't care
-			Message::SubscribeOk(_msg) => Ok(()), // don't care
+			Message::Announce(_) => Ok(()),   // don't care
+			Message::Unannounce(_) => Ok(()), // also don't care
+			Message::SubscribeOk(msg) => self.recv_subscribe_ok(msg),
 			Message::SubscribeReset(msg) => self.recv_subscribe_error(msg.id, CacheError::Reset(msg.code)),
-			Message::SubscribeFin(msg) => self.recv_subscribe_error(msg.id, CacheError::Closed),
+			Message::SubscribeFin(msg) => self.recv_subscribe_fin(msg),
 			Message::SubscribeError(msg) => self.recv_subscribe_error(msg.id, CacheError::Reset(msg.code)),
-			Message::GoAway(_msg) => unimplemented!("GOAWAY"),
+			Message::ProbeOk(msg) => self.recv_probe_ok(msg),
+			Message::GoAway(msg) => self.recv_go_away(msg),
 			_ => Err(SessionError::RoleViolation(msg.id())),
 		}
 	}
 
+	/// Send a GOAWAY, asking the publisher to reconnect to `url`, or the same URL if it's empty.
+	///
+	/// The session keeps running until the publisher disconnects, so it can switch without a gap.
+	pub async fn go_away(&self, url: &str) -> Result<(), SessionError> {
+		self.control.send(message::GoAway { url: url.to_string() }).await
+	}
+
+	/// Resolves once the publisher sends a GOAWAY, returning the URL to reconnect to, or an empty string for the same URL.
+	///
+	/// The session keeps running, so the application decides when to reconnect.
+	pub async fn going_away(&self) -> String {
+		self.control.going_away().await
+	}
+
+	/// Snapshot the QUIC metrics and per-subscription counters.
+	pub fn stats(&self) -> Stats {
+		Stats::new(&*self.transport, &self.control.stats)
+	}
+
+	fn recv_subscribe_ok(&mut self, msg: &message::SubscribeOk) -> Result<(), SessionError> {
+		// Only explicit subscriptions care about SUBSCRIBE_OK.
+		if let Some(status) = self.statuses.lock().unwrap().get(&msg.id) {
+			status.ok(msg.expires);
+		}
+
+		Ok(())
+	}
+
+	fn recv_subscribe_fin(&mut self, msg: &message::SubscribeFin) -> Result<(), SessionError> {
+		if let Some(status) = self.statuses.lock().unwrap().get(&msg.id) {
+			status.fin(msg.clone());
+		}
+
+		self.recv_subscribe_error(msg.id, CacheError::Closed)
+	}
+
 	fn recv_subscribe_error(&mut self, id: VarInt, err: CacheError) -> Result<(), SessionError> {
+		// A failed PROBE is reset like a subscription.
+		if let Some(probe) = self.probes.lock().unwrap().get(&id) {
+			probe.close(err);
+			return Ok(());
+		}
+
+		if let Some(status) = self.statuses.lock().unwrap().remove(&id) {
+			status.close(err.clone());
+		}
+
+		// Finish any group that was being received via datagrams.
+		self.groups.lock().unwrap().remove(&id);
+
 		let mut subscribes = self.subscribes.lock().unwrap();
 		let subscribe = subscribes.remove(&id).ok_or(CacheError::NotFound)?;
 		subscribe.close(err)?;
 
+		self.control.stats.unsubscribe(id);
+
 		Ok(())
 	}
 
-	async fn run_streams(self) -> Result<(), SessionError> {
+	fn recv_go_away(&mut self, msg: &message::GoAway) -> Result<(), SessionError> {
+		// Keep receiving until the application reconnects or the server closes the session.
+		log::info!("received GOAWAY: url={:?}", msg.url);
+		self.control.set_going_away(&msg.url);
+		Ok(())
+	}
+
+	fn recv_probe_ok(&mut self, msg: &message::ProbeOk) -> Result<(), SessionError> {
+		let probes = self.probes.lock().unwrap();
+		let probe = probes.get(&msg.id).ok_or(CacheError::NotFound)?;
+		probe.done(msg.sent.iter().map(|sent| sent.into_inner()).collect());
+
+		Ok(())
+	}
+
+	/// Subscribe to the named track, waiting until the publisher replies with SUBSCRIBE_OK.
+	///
+	/// Unlike [broadcast::Subscriber::get_track], this returns the error code if the subscription is rejected.
+	pub async fn subscribe(&self, name: &str, options: SubscribeOptions) -> Result<Subscription, SessionError> {
+		if !options.namespace.is_empty() {
+			self.control.ext.require_subscribe_split()?;
+		}
+
+		if options.switch.is_some() {
+			self.control.ext.require_switch_track_id()?;
+		}
+
+		let (publisher, subscriber) = track::new(name);
+		let status = Status::default();
+
+		let id = VarInt::from_u32(self.next.fetch_add(1, atomic::Ordering::SeqCst));
+		self.statuses.lock().unwrap().insert(id, status.clone());
+		self.subscribes.lock().unwrap().insert(id, publisher);
+		self.control.stats.subscribe(id, name);
+
+		let res = match self.subscribe_message(id, name, &options).await {
+			Ok(msg) => self.control.send(msg).await,
+			Err(err) => Err(err),
+		};
+
+		let res = match res {
+			Ok(()) => status.accepted().await.map_err(SessionError::from),
+			Err(err) => Err(err),
+		};
+
+		match res {
+			Ok(expires) => Ok(Subscription::new(id, expires, subscriber, status, self.clone())),
+			Err(err) => {
+				// The subscription is already gone if it was rejected.
+				self.remove(id);
+				Err(err)
+			}
+		}
+	}
+
+	pub(super) async fn unsubscribe(&self, id: VarInt) -> Result<(), SessionError> {
+		self.remove(id);
+		self.control.send(message::Unsubscribe { id }).await
+	}
+
+	// Forget about a subscription, closing the track.
+	fn remove(&self, id: VarInt) {
+		if let Some(status) = self.statuses.lock().unwrap().remove(&id) {
+			status.close(CacheError::Stop);
+		}
+
+		self.groups.lock().unwrap().remove(&id);
+
+		if let Some(track) = self.subscribes.lock().unwrap().remove(&id) {
+			track.close(CacheError::Stop).ok();
+			self.control.stats.unsubscribe(id);
+		}
+	}
+
+	/// Take the tracks requested from the source broadcast, so they aren't closed when the session is dropped.
+	///
+	/// This is used to resume the tracks with a new session after this one fails, see [broadcast::Publisher::requeue].
+	/// Explicit subscriptions created by [Subscriber::subscribe] are not included.
+	pub fn detach(&self) -> Vec<track::Publisher> {
+		let explicit: HashSet<VarInt> = self.statuses.lock().unwrap().keys().copied().collect();
+		let mut subscribes = self.subscribes.lock().unwrap();
+
+		let ids: Vec<VarInt> = subscribes.keys().filter(|id| !explicit.contains(id)).copied().collect();
+
+		ids.into_iter()
+			.filter_map(|id| {
+				self.control.stats.unsubscribe(id);
+				subscribes.remove(&id)
+			})
+			.collect()
+	}
+
+	/// Measure the available bandwidth by asking the publisher for `count` OBJECTs of `size` bytes.
+	///
+	/// The publisher waits `pacing` between each OBJECT and sends them with the given priority, where **smaller** values are sent first.
+	/// This returns once every OBJECT has been received, so wrap it in a timeout if the network is lossy.
+	/// Requires the probe extension.
+	pub async fn probe(
+		&self,
+		size: VarInt,
+		priority: u32,
+		count: VarInt,
+		pacing: time::Duration,
+	) -> Result<ProbeResult, SessionError> {
+		self.control.ext.require_probe()?;
+
+		let expected = size.into_inner().saturating_mul(count.into_inner());
+		let probe = Probe::new(expected);
+
+		let id = VarInt::from_u32(self.next.fetch_add(1, atomic::Ordering::SeqCst));
+		self.probes.lock().unwrap().insert(id, probe.clone());
+
+		let msg = message::Probe {
+			id,
+			size,
+			priority,
+			count,
+			pacing,
+		};
+
+		let res = match self.control.send(msg).await {
+			Ok(()) => probe.result().await.map_err(SessionError::from),
+			Err(err) => Err(err),
+		};
+
+		self.probes.lock().unwrap().remove(&id);
+
+		res
+	}
+
+	pub(crate) async fn run_streams(self) -> Result<(), SessionError> {
 		loop {
 			// Accept all incoming unidirectional streams.
-			let stream = self.webtransport.accept_uni().await?;
+			let stream = self.transport.accept_uni().await?;
+			self.control.stats.stream_accepted();
+
 			let this = self.clone();
 
 			tokio::spawn(async move {
@@ -104,36 +382,57 @@ impl Subscriber {
 		}
 	}
 
-	async fn run_stream(self, mut stream: RecvStream) -> Result<(), SessionError> {
+	async fn run_stream(self, mut stream: Box<dyn RecvStream>) -> Result<(), SessionError> {
 		// Decode the object on the data stream.
-		let mut object = message::Object::decode(&mut stream, &self.control.ext)
-			.await
-			.map_err(|e| SessionError::Unknown(e.to_string()))?;
+		let mut decoder = self.control.object_decoder();
+		let mut object = decoder.decode(&mut stream).await?;
 
 		log::trace!("first object: {:?}", object);
 
+		let probe = self.probes.lock().unwrap().get(&object.track).cloned();
+		if let Some(probe) = probe {
+			return Self::run_probe_stream(stream, probe).await;
+		}
+
+		// Continue a group that started as datagrams but fell back to a stream.
+		let group = {
+			let mut groups = self.groups.lock().unwrap();
+			match groups.entry(object.track) {
+				hash_map::Entry::Occupied(entry) if entry.get().sequence == object.group => Some(entry.remove()),
+				_ => None,
+			}
+		};
+
 		// A new scope is needed because the async compiler is dumb
-		let mut segment = {
-			let mut subscribes = self.subscribes.lock().unwrap();
-			let track = subscribes.get_mut(&object.track).ok_or(CacheError::NotFound)?;
-
-			track.create_segment(segment::Info {
-				sequence: object.group,
-				priority: object.priority,
-				expires: object.expires,
-			})?
+		let mut segment = match group {
+			Some(segment) => segment,
+			None => {
+				let mut subscribes = self.subscribes.lock().unwrap();
+				let track = subscribes.get_mut(&object.track).ok_or(CacheError::NotFound)?;
+				self.control.stats.arrival(object.track, track.arrival(object.group));
+
+				track.create_segment(segment::Info {
+					sequence: object.group,
+					priority: object.priority,
+					expires: object.expires,
+				})?
+			}
 		};
 
 		log::trace!("received segment: {:?}", segment);
 
+		// Every OBJECT on the stream belongs to this segment, so count them all against its track.
+		let track = object.track;
+
 		// Create the first fragment
-		let mut fragment = segment.push_fragment(object.sequence, object.size.map(usize::from))?;
+		let mut fragment = self.push_fragment(&mut segment, &object, object.size.map(usize::from))?;
+		self.control.stats.subscribed(track, 1, 0);
 		let mut remain = object.size.map(usize::from);
 
 		loop {
 			if let Some(0) = remain {
 				// Decode the next object from the stream.
-				let next = match message::Object::decode(&mut stream, &self.control.ext).await {
+				let next = match decoder.decode(&mut stream).await {
 					Ok(next) => next,
 
 					// No more objects
@@ -143,25 +442,26 @@ impl Subscriber {
 					Err(err) => return Err(err.into()),
 				};
 
-				log::trace!("next object: {:?}", object);
+				log::trace!("next object: {:?}", next);
 
 				// NOTE: This is a custom restriction; not part of the moq-transport draft.
 				// We require every OBJECT to contain the same priority since prioritization is done per-stream.
-				// We also require every OBJECT to contain the same group so we know when the group ends, and can detect gaps.
-				if next.priority != object.priority && next.group != object.group {
+				// We also require every OBJECT to contain the same track and group so we know when the group ends, and can detect gaps.
+				if next.track != object.track || next.group != object.group || next.priority != object.priority {
 					return Err(SessionError::StreamMapping);
 				}
 
 				object = next;
 
 				// Create a new object.
-				fragment = segment.push_fragment(object.sequence, object.size.map(usize::from))?;
+				fragment = self.push_fragment(&mut segment, &object, object.size.map(usize::from))?;
+				self.control.stats.subscribed(track, 1, 0);
 				remain = object.size.map(usize::from);
 
 				log::trace!("next fragment: {:?}", fragment);
 			}
 
-			match stream.read_chunk(remain.unwrap_or(usize::MAX), true).await? {
+			match stream.read_chunk(remain.unwrap_or(usize::MAX)).await? {
 				// Unbounded object has ended
 				None if remain.is_none() => break,
 
@@ -171,10 +471,11 @@ impl Subscriber {
 				// NOTE: This does not make a copy!
 				// Bytes are immutable and ref counted.
 				Some(data) => {
-					remain = remain.map(|r| r - data.bytes.len());
+					remain = remain.map(|r| r - data.len());
 
 					log::trace!("next chunk: {:?}", data);
-					fragment.chunk(data.bytes)?;
+					self.control.stats.subscribed(track, 0, data.len() as u64);
+					fragment.chunk(data)?;
 				}
 			}
 		}
@@ -182,33 +483,235 @@ impl Subscriber {
 		Ok(())
 	}
 
-	async fn run_source(mut self) -> Result<(), SessionError> {
+	// Count the payload of a PROBE OBJECT, discarding it.
+	async fn run_probe_stream(mut stream: Box<dyn RecvStream>, probe: Probe) -> Result<(), SessionError> {
+		while let Some(data) = stream.read_chunk(usize::MAX).await? {
+			probe.recv(data.len());
+		}
+
+		Ok(())
+	}
+
+	pub(crate) async fn run_datagrams(self) -> Result<(), SessionError> {
+		loop {
+			let datagram = self.transport.recv_datagram().await?;
+			self.control.stats.datagram_received();
+
+			if let Err(err) = self.recv_datagram(datagram).await {
+				log::warn!("failed to receive datagram: err={:#?}", err);
+			}
+		}
+	}
+
+	async fn recv_datagram(&self, mut datagram: Bytes) -> Result<(), SessionError> {
+		// The OBJECT header is followed by the payload, which runs until the end of the datagram.
+		let mut cursor = io::Cursor::new(&datagram[..]);
+		let object = message::Object::decode(&mut cursor, &self.control.ext).await?;
+		let payload = datagram.split_off(cursor.position() as usize);
+
+		log::trace!("received datagram: {:?}", object);
+
+		let mut groups = self.groups.lock().unwrap();
+
+		let segment = match groups.entry(object.track) {
+			hash_map::Entry::Occupied(entry) if entry.get().sequence == object.group => entry.into_mut(),
+
+			// Datagrams can be reordered, so drop any OBJECT for an older group.
+			hash_map::Entry::Occupied(entry) if entry.get().sequence > object.group => return Ok(()),
+
+			entry => {
+				let mut subscribes = self.subscribes.lock().unwrap();
+				let track = subscribes.get_mut(&object.track).ok_or(CacheError::NotFound)?;
+				let arrival = track.arrival(object.group);
+
+				let segment = match track.create_segment(segment::Info {
+					sequence: object.group,
+					priority: object.priority,
+					expires: object.expires,
+				}) {
+					Ok(segment) => segment,
+
+					// The group already fell back to a stream, so this OBJECT is treated as lost.
+					Err(CacheError::Duplicate) => return Ok(()),
+					Err(err) => return Err(err.into()),
+				};
+
+				self.control.stats.arrival(object.track, arrival);
+
+				// Replacing the previous group finishes it.
+				match entry {
+					hash_map::Entry::Occupied(mut entry) => {
+						entry.insert(segment);
+						entry.into_mut()
+					}
+					hash_map::Entry::Vacant(entry) => entry.insert(segment),
+				}
+			}
+		};
+
+		self.control.stats.subscribed(object.track, 1, payload.len() as u64);
+
+		let mut fragment = self.push_fragment(segment, &object, Some(payload.len()))?;
+		fragment.chunk(payload)?;
+
+		Ok(())
+	}
+
+	// Create the fragment for a received OBJECT, counting any gap before it.
+	fn push_fragment(
+		&self,
+		segment: &mut segment::Publisher,
+		object: &message::Object,
+		size: Option<usize>,
+	) -> Result<fragment::Publisher, SessionError> {
+		self.control
+			.stats
+			.arrival(object.track, segment.arrival(object.sequence));
+
+		let fragment = segment.push_fragment(object.sequence, size)?;
+
+		Ok(match self.max_fragment_size {
+			Some(limit) => fragment.with_limit(limit),
+			None => fragment,
+		})
+	}
+
+	pub(crate) async fn run_source(mut self) -> Result<(), SessionError> {
 		log::debug!("running source");
 		loop {
 			// NOTE: This returns Closed when the source is closed.
-			let track = self.source.next_track().await?;
-			let name = track.name.clone();
+			let request = self.source.next_request().await?;
+			let name = request.track.name.clone();
+
+			let mut options = self.track_options(&name);
+
+			// Forward a switch if we're subscribed to the track being replaced.
+			if let Some(from) = &request.switch {
+				options.switch = self.find(from);
+			}
 
 			let id = VarInt::from_u32(self.next.fetch_add(1, atomic::Ordering::SeqCst));
-			self.subscribes.lock().unwrap().insert(id, track);
+			self.subscribes.lock().unwrap().insert(id, request.track);
+			self.control.stats.subscribe(id, &name);
+
+			let msg = self.subscribe_message(id, &name, &options).await?;
+			self.control.send(msg).await?;
+		}
+	}
+
+	// Returns the ID of an active subscription for the named track.
+	fn find(&self, name: &str) -> Option<VarInt> {
+		let subscribes = self.subscribes.lock().unwrap();
+		subscribes
+			.iter()
+			.find(|(_, track)| track.name == name)
+			.map(|(id, _)| *id)
+	}
+
+	// The options configured for the named track, used for subscriptions created by get_track.
+	fn track_options(&self, name: &str) -> SubscribeOptions {
+		let mut options = SubscribeOptions::default();
 
-			let msg = message::Subscribe {
-				id,
-				namespace: self.control.ext.subscribe_split.then(|| "".to_string()),
-				name,
+		if self.datagrams.contains(name) {
+			options = options.with_datagram();
+		}
 
-				// TODO correctly support these
-				start_group: message::SubscribeLocation::Latest(VarInt::ZERO),
-				start_object: message::SubscribeLocation::Absolute(VarInt::ZERO),
-				end_group: message::SubscribeLocation::None,
-				end_object: message::SubscribeLocation::None,
+		if let Some(priority) = self.priorities.get(name) {
+			options = options.with_priority(*priority);
+		}
 
-				switch_track_id: Some(VarInt::ZERO),
+		if let Some(latency) = self.latencies.get(name) {
+			options = options.with_max_latency(*latency);
+		}
 
-				params: Default::default(),
-			};
+		options
+	}
 
-			self.control.send(msg).await?;
+	async fn subscribe_message(
+		&self,
+		id: VarInt,
+		name: &str,
+		options: &SubscribeOptions,
+	) -> Result<message::Subscribe, SessionError> {
+		let mut params = coding::Params::default();
+		if self.control.ext.datagram && options.datagram {
+			params.0.insert(message::SUBSCRIBE_DATAGRAM, Vec::new());
 		}
+
+		if let Some(priority) = options.priority {
+			params.0.insert(message::SUBSCRIBE_PRIORITY, vec![priority]);
+		}
+
+		if let Some(latency) = options.max_latency {
+			let millis = VarInt::try_from(latency.as_millis() as u64)?;
+			params.set(message::SUBSCRIBE_MAX_LATENCY, millis).await?;
+		}
+
+		Ok(message::Subscribe {
+			id,
+			namespace: self.control.ext.subscribe_split.then(|| options.namespace.clone()),
+			name: name.to_string(),
+
+			start_group: options.start_group.clone(),
+			start_object: options.start_object.clone(),
+			end_group: options.end_group.clone(),
+			end_object: options.end_object.clone(),
+
+			switch_track_id: self
+				.control
+				.ext
+				.switch_track_id
+				.then(|| options.switch.unwrap_or(VarInt::ZERO)),
+
+			params,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		setup,
+		transport::{loopback, LoopbackConfig, Session},
+	};
+
+	fn object(group: u32, sequence: u32, size: usize) -> message::Object {
+		message::Object {
+			track: VarInt::ZERO,
+			group: VarInt::from_u32(group),
+			sequence: VarInt::from_u32(sequence),
+			priority: 0,
+			expires: None,
+			size: Some(VarInt::try_from(size).unwrap()),
+			ntp_timestamp: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn stream_mapping() {
+		let (remote, local) = loopback(LoopbackConfig::default());
+		let ext = setup::Extensions::default();
+
+		let (send, recv) = local.open_bi().await.unwrap();
+		let control = Control::new(send, recv, setup::Version::KIXEL_01, ext.clone());
+
+		let (source, _broadcast) = broadcast::new("");
+		let subscriber = Subscriber::new(Arc::new(local), control, source);
+
+		let (track, _track) = track::new("video");
+		subscriber.subscribes.lock().unwrap().insert(VarInt::ZERO, track);
+
+		// Each stream must contain a single group, so the second OBJECT is invalid.
+		let mut stream = remote.open_uni().await.unwrap();
+		for group in 0..2 {
+			object(group, 0, 5).encode(&mut stream, &ext).await.unwrap();
+			stream.write_chunk(Bytes::from_static(b"hello")).await.unwrap();
+		}
+		drop(stream);
+
+		let stream = subscriber.transport.accept_uni().await.unwrap();
+		let res = subscriber.run_stream(stream).await;
+		assert!(matches!(res, Err(SessionError::StreamMapping)), "{:?}", res);
 	}
 }
@@ -0,0 +1,242 @@
+use std::time;
+
+use crate::{
+	cache::{segment, track, Arrival, CacheError, Watch},
+	message::{self, SubscribeLocation},
+	VarInt,
+};
+
+use super::{SessionError, Subscriber};
+
+/// The options for an explicit subscription, see [Subscriber::subscribe].
+#[derive(Clone, Debug)]
+pub struct SubscribeOptions {
+	pub(super) namespace: String,
+	pub(super) start_group: SubscribeLocation,
+	pub(super) start_object: SubscribeLocation,
+	pub(super) end_group: SubscribeLocation,
+	pub(super) end_object: SubscribeLocation,
+	pub(super) priority: Option<u8>,
+	pub(super) max_latency: Option<time::Duration>,
+	pub(super) datagram: bool,
+
+	// The subscription replaced by this one, set by Subscription::switch.
+	pub(super) switch: Option<VarInt>,
+}
+
+impl Default for SubscribeOptions {
+	fn default() -> Self {
+		Self {
+			namespace: String::new(),
+
+			// Start at the latest group, without an end.
+			start_group: SubscribeLocation::Latest(VarInt::ZERO),
+			start_object: SubscribeLocation::Absolute(VarInt::ZERO),
+			end_group: SubscribeLocation::None,
+			end_object: SubscribeLocation::None,
+
+			priority: None,
+			max_latency: None,
+			datagram: false,
+			switch: None,
+		}
+	}
+}
+
+impl SubscribeOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Subscribe to a track in the given namespace, instead of the broadcast at the session path.
+	///
+	/// Requires the subscribe_split extension.
+	pub fn with_namespace(mut self, namespace: &str) -> Self {
+		self.namespace = namespace.to_string();
+		self
+	}
+
+	/// Start the subscription at the given group and object.
+	pub fn with_start(mut self, group: SubscribeLocation, object: SubscribeLocation) -> Self {
+		self.start_group = group;
+		self.start_object = object;
+		self
+	}
+
+	/// End the subscription at the given group and object.
+	pub fn with_end(mut self, group: SubscribeLocation, object: SubscribeLocation) -> Self {
+		self.end_group = group;
+		self.end_object = object;
+		self
+	}
+
+	/// Ask for the track to be sent with the given priority, see [Subscriber::with_track_priority].
+	pub fn with_priority(mut self, priority: u8) -> Self {
+		self.priority = Some(priority);
+		self
+	}
+
+	/// Ask for old groups to be dropped, see [Subscriber::with_track_max_latency].
+	pub fn with_max_latency(mut self, latency: time::Duration) -> Self {
+		self.max_latency = Some(latency);
+		self
+	}
+
+	/// Ask for the track to be delivered as datagrams, see [Subscriber::with_datagrams].
+	pub fn with_datagram(mut self) -> Self {
+		self.datagram = true;
+		self
+	}
+}
+
+#[derive(Debug, Default)]
+struct State {
+	// Set when SUBSCRIBE_OK is received.
+	expires: Option<VarInt>,
+
+	// Set when SUBSCRIBE_FIN is received.
+	fin: Option<message::SubscribeFin>,
+
+	// Set when the subscription was rejected or reset.
+	closed: Option<CacheError>,
+}
+
+/// Tracks the responses for an explicit subscription.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Status {
+	state: Watch<State>,
+}
+
+impl Status {
+	pub fn ok(&self, expires: VarInt) {
+		self.state.lock_mut().expires = Some(expires);
+	}
+
+	pub fn fin(&self, msg: message::SubscribeFin) {
+		self.state.lock_mut().fin = Some(msg);
+	}
+
+	pub fn close(&self, err: CacheError) {
+		let mut state = self.state.lock_mut();
+		state.closed.get_or_insert(err);
+	}
+
+	/// Wait until SUBSCRIBE_OK, returning the expiration, or the error if the subscription was rejected.
+	pub async fn accepted(&self) -> Result<VarInt, CacheError> {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+
+				if let Some(expires) = state.expires {
+					return Ok(expires);
+				}
+
+				if let Some(err) = &state.closed {
+					return Err(err.clone());
+				}
+
+				state.changed()
+			};
+
+			notify.await;
+		}
+	}
+
+	/// Wait until SUBSCRIBE_FIN, or the error if the subscription was reset.
+	pub async fn finished(&self) -> Result<message::SubscribeFin, CacheError> {
+		loop {
+			let notify = {
+				let state = self.state.lock();
+
+				if let Some(fin) = &state.fin {
+					return Ok(fin.clone());
+				}
+
+				if let Some(err) = &state.closed {
+					return Err(err.clone());
+				}
+
+				state.changed()
+			};
+
+			notify.await;
+		}
+	}
+}
+
+/// An active subscription, returned by [Subscriber::subscribe] once the publisher replied with SUBSCRIBE_OK.
+///
+/// The subscription isn't cancelled when dropped; use [Subscription::unsubscribe] instead.
+#[derive(Debug)]
+pub struct Subscription {
+	id: VarInt,
+	expires: VarInt,
+	track: track::Subscriber,
+	status: Status,
+	subscriber: Subscriber,
+}
+
+impl Subscription {
+	pub(super) fn new(
+		id: VarInt,
+		expires: VarInt,
+		track: track::Subscriber,
+		status: Status,
+		subscriber: Subscriber,
+	) -> Self {
+		Self {
+			id,
+			expires,
+			track,
+			status,
+			subscriber,
+		}
+	}
+
+	/// The subscribe ID, used in the stats.
+	pub fn id(&self) -> VarInt {
+		self.id
+	}
+
+	/// The name of the track.
+	pub fn name(&self) -> &str {
+		&self.track.name
+	}
+
+	/// The expiration from SUBSCRIBE_OK in milliseconds, or zero if it never expires.
+	pub fn expires(&self) -> VarInt {
+		self.expires
+	}
+
+	/// Block until the next segment is received, or None once the subscription is finished.
+	pub async fn segment(&mut self) -> Result<Option<segment::Subscriber>, SessionError> {
+		Ok(self.track.segment().await?)
+	}
+
+	/// How the last segment arrived relative to the earlier ones, see [track::Subscriber::arrival].
+	pub fn arrival(&self) -> Arrival {
+		self.track.arrival()
+	}
+
+	/// Wait until the publisher sends SUBSCRIBE_FIN, containing the final group and object.
+	///
+	/// Returns the error code if the subscription was reset instead.
+	pub async fn finished(&self) -> Result<message::SubscribeFin, SessionError> {
+		Ok(self.status.finished().await?)
+	}
+
+	/// Switch to the named track at the next group boundary, such as another rendition of the same content.
+	///
+	/// The publisher starts the new track at the group after the last one sent on this track,
+	/// then finishes this subscription with SUBSCRIBE_FIN, see [Subscription::finished].
+	/// Requires the switch_track_id extension.
+	pub async fn switch(&self, name: &str, mut options: SubscribeOptions) -> Result<Subscription, SessionError> {
+		options.switch = Some(self.id);
+		self.subscriber.subscribe(name, options).await
+	}
+
+	/// Stop the subscription by sending UNSUBSCRIBE.
+	pub async fn unsubscribe(self) -> Result<(), SessionError> {
+		self.subscriber.unsubscribe(self.id).await
+	}
+}
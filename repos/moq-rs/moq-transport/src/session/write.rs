@@ -0,0 +1,137 @@
+use std::{future::Future, time};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::transport;
+
+use super::SessionError;
+
+/// Decides how a [Publisher](super::Publisher) writes OBJECTs to each stream.
+///
+/// By default every chunk is written as soon as it's available, without copying.
+#[derive(Clone, Debug, Default)]
+pub struct WriteStrategy {
+	// Buffer chunks until this many bytes are queued, or zero to disable coalescing.
+	coalesce_size: usize,
+
+	// The longest a chunk can be buffered before it's written anyway.
+	coalesce_delay: time::Duration,
+
+	// The maximum rate of each stream in bits per second.
+	bitrate: Option<u64>,
+}
+
+impl WriteStrategy {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Coalesce small chunks into a single write once `size` bytes are queued, or once the oldest chunk waited for `delay`.
+	pub fn with_coalesce(mut self, size: usize, delay: time::Duration) -> Self {
+		self.coalesce_size = size;
+		self.coalesce_delay = delay;
+		self
+	}
+
+	/// Pace the writes so each stream doesn't exceed the bitrate, or disable pacing if zero.
+	pub fn with_pacing(mut self, bitrate: u64) -> Self {
+		self.bitrate = (bitrate > 0).then_some(bitrate);
+		self
+	}
+}
+
+// Writes chunks to a stream according to a [WriteStrategy].
+pub(super) struct Writer<'a> {
+	stream: &'a mut Box<dyn transport::SendStream>,
+	strategy: &'a WriteStrategy,
+
+	// The chunks waiting to be coalesced.
+	pending: Vec<Bytes>,
+	pending_size: usize,
+
+	// When the oldest pending chunk was queued.
+	queued: Option<time::Instant>,
+
+	// Used to pace the stream.
+	started: time::Instant,
+	written: u64,
+}
+
+impl<'a> Writer<'a> {
+	pub fn new(stream: &'a mut Box<dyn transport::SendStream>, strategy: &'a WriteStrategy) -> Self {
+		Self {
+			stream,
+			strategy,
+			pending: Vec::new(),
+			pending_size: 0,
+			queued: None,
+			started: time::Instant::now(),
+			written: 0,
+		}
+	}
+
+	/// Write the chunk, or queue it until enough chunks are available to coalesce.
+	pub async fn write(&mut self, chunk: Bytes) -> Result<(), SessionError> {
+		if chunk.is_empty() {
+			return Ok(());
+		}
+
+		if self.strategy.coalesce_size == 0 {
+			return self.send(chunk).await;
+		}
+
+		let queued = *self.queued.get_or_insert_with(time::Instant::now);
+		self.pending_size += chunk.len();
+		self.pending.push(chunk);
+
+		if self.pending_size >= self.strategy.coalesce_size || queued.elapsed() >= self.strategy.coalesce_delay {
+			self.flush().await?;
+		}
+
+		Ok(())
+	}
+
+	/// Resolves once the queued chunks have waited long enough and should be flushed.
+	// NOTE: The future doesn't borrow the writer, since the stream isn't Sync.
+	pub fn expired(&self) -> impl Future<Output = ()> {
+		let deadline = self.queued.map(|queued| queued + self.strategy.coalesce_delay);
+
+		async move {
+			match deadline {
+				Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+				None => std::future::pending().await,
+			}
+		}
+	}
+
+	/// Write any queued chunks as a single chunk.
+	pub async fn flush(&mut self) -> Result<(), SessionError> {
+		let chunk = match self.pending.len() {
+			0 => return Ok(()),
+			1 => self.pending.pop().unwrap(),
+			_ => {
+				let mut buf = BytesMut::with_capacity(self.pending_size);
+				for chunk in self.pending.drain(..) {
+					buf.extend_from_slice(&chunk);
+				}
+				buf.freeze()
+			}
+		};
+
+		self.pending_size = 0;
+		self.queued = None;
+
+		self.send(chunk).await
+	}
+
+	async fn send(&mut self, chunk: Bytes) -> Result<(), SessionError> {
+		// Wait until the bytes already written would have been sent at the target bitrate.
+		if let Some(bitrate) = self.strategy.bitrate {
+			let due = time::Duration::from_secs_f64(self.written as f64 * 8.0 / bitrate as f64);
+			tokio::time::sleep_until((self.started + due).into()).await;
+		}
+
+		self.written += chunk.len() as u64;
+		self.stream.write_chunk(chunk).await
+	}
+}
@@ -1,4 +1,8 @@
-use super::{Extensions, Role, Versions};
+use std::io;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::{Extensions, Role, Version, Versions, MAX_SETUP_SIZE, PATH};
 use crate::{
 	coding::{Decode, DecodeError, Encode, EncodeError, Params},
 	VarInt,
@@ -9,7 +13,7 @@ use crate::coding::{AsyncRead, AsyncWrite};
 /// Sent by the client to setup the session.
 // NOTE: This is not a message type, but rather the control stream header.
 // Proposal: https://github.com/moq-wg/moq-transport/issues/138
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Client {
 	/// The list of supported versions in preferred order.
 	pub versions: Versions,
@@ -17,6 +21,9 @@ pub struct Client {
 	/// Indicate if the client is a publisher, a subscriber, or both.
 	pub role: Role,
 
+	/// The path of the URL, only sent when running directly over QUIC.
+	pub path: Option<String>,
+
 	/// A list of known/offered extensions.
 	pub extensions: Extensions,
 
@@ -32,40 +39,99 @@ impl Client {
 			return Err(DecodeError::InvalidMessage(typ));
 		}
 
-		let versions = Versions::decode(r).await?;
-		let mut params = Params::decode(r).await?;
+		// The fork encodes the version count followed by the first version.
+		// Newer drafts encode the message length followed by the version count.
+		let first = VarInt::decode(r).await?;
+
+		// The length includes at least the version and parameter counts, so zero is an empty list in the fork.
+		if first == VarInt::ZERO {
+			return Self::decode_params(Versions::from(Vec::new()), Params::decode(r).await?).await;
+		}
+
+		let second = VarInt::decode(r).await?;
+
+		// Each version takes at least a byte, so the count is always smaller than the length.
+		// Otherwise the second value is the first version of the fork's list.
+		let (versions, params) = if second >= first {
+			let mut versions = vec![Version(second)];
+			for _ in 1..first.into_inner() {
+				versions.push(Version::decode(r).await?);
+			}
+
+			(versions.into(), Params::decode(r).await?)
+		} else {
+			// We already read the version count, so read the remainder of the message.
+			let size = first
+				.into_inner()
+				.checked_sub(second.size() as u64)
+				.filter(|size| *size <= MAX_SETUP_SIZE)
+				.ok_or(DecodeError::InvalidLength)?;
+
+			let mut buf = vec![0; size as usize];
+			r.read_exact(&mut buf).await?;
+			let mut payload = io::Cursor::new(buf);
 
+			let mut versions = Vec::new();
+			for _ in 0..second.into_inner() {
+				versions.push(Version::decode(&mut payload).await?);
+			}
+
+			let params = Params::decode(&mut payload).await?;
+			if payload.position() != size {
+				return Err(DecodeError::InvalidLength);
+			}
+
+			(versions.into(), params)
+		};
+
+		Self::decode_params(versions, params).await
+	}
+
+	async fn decode_params(versions: Versions, mut params: Params) -> Result<Self, DecodeError> {
 		let role = params
 			.get::<Role>(VarInt::from_u32(0))
 			.await?
 			.ok_or(DecodeError::MissingParameter)?;
 
-		// Make sure the PATH parameter isn't used
-		// TODO: This assumes WebTransport support only
-		if params.has(VarInt::from_u32(1)) {
-			return Err(DecodeError::InvalidParameter);
-		}
+		// Only used when running directly over QUIC, since WebTransport uses the CONNECT path.
+		let path = params.get::<String>(PATH).await?;
 
 		let extensions = Extensions::load(&mut params).await?;
 
 		Ok(Self {
 			versions,
 			role,
+			path,
 			extensions,
 			params,
 		})
 	}
 
-	/// Encode a server setup message.
+	/// Encode a client setup message.
+	///
+	/// The framing is chosen by the first version, so the fork and [Version::is_ietf] versions can't be offered together.
 	pub async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
 		VarInt::from_u32(0x40).encode(w).await?;
-		self.versions.encode(w).await?;
 
 		let mut params = self.params.clone();
 		params.set(VarInt::from_u32(0), self.role).await?;
+		if let Some(path) = &self.path {
+			params.set(PATH, path.clone()).await?;
+		}
 		self.extensions.store(&mut params).await?;
 
-		params.encode(w).await?;
+		if self.versions.first().map(Version::is_ietf).unwrap_or(false) {
+			// Newer drafts prefix the message with its length.
+			let mut payload = Vec::new();
+			self.versions.encode(&mut payload).await?;
+			params.encode(&mut payload).await?;
+
+			VarInt::try_from(payload.len())?.encode(w).await?;
+			w.write_all(&payload).await?;
+		} else {
+			self.versions.encode(w).await?;
+			params.encode(w).await?;
+		}
 
 		Ok(())
 	}
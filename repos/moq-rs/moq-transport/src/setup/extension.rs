@@ -8,10 +8,9 @@ use paste::paste;
 /// This is a custom extension scheme to allow/require draft PRs.
 ///
 /// By convention, the extension number is the PR number + 0xe0000.
-
 macro_rules! extensions {
     {$($name:ident = $val:expr,)*} => {
-		#[derive(Clone, Default, Debug)]
+		#[derive(Clone, Default, Debug, PartialEq, Eq)]
 		pub struct Extensions {
 			$(
 				pub $name: bool,
@@ -85,4 +84,10 @@ extensions! {
 	ntp_timestamp = 0xe0116, // TODO - ZG -write up a PR
 
 	switch_track_id = 0xe0117, // TODO - ZG -write up a PR
+
+	// optional: PROBE/PROBE_OK messages for bandwidth estimation, replacing ".probe" track names.
+	probe = 0xe0118,
+
+	// optional: OBJECTs may be sent as datagrams when requested by SUBSCRIBE, see message::SUBSCRIBE_DATAGRAM.
+	datagram = 0xe0119,
 }
@@ -3,6 +3,8 @@
 //! After establishing the WebTransport session, the client creates a bidirectional QUIC stream.
 //! The client sends the [Client] message and the server responds with the [Server] message.
 //! Both sides negotate the [Version] and [Role].
+//!
+//! Newer drafts prefix both messages with a length, which is detected from the first values when decoding.
 
 mod client;
 mod extension;
@@ -15,3 +17,17 @@ pub use extension::*;
 pub use role::*;
 pub use server::*;
 pub use version::*;
+
+#[cfg(test)]
+mod tests;
+
+use crate::VarInt;
+
+/// The SETUP parameter containing the path, used when running directly over QUIC instead of WebTransport.
+pub const PATH: VarInt = VarInt::from_u32(1);
+
+/// The SETUP parameter used by newer drafts to limit the SUBSCRIBE ID, which defaults to zero.
+pub const MAX_SUBSCRIBE_ID: VarInt = VarInt::from_u32(2);
+
+// Newer drafts limit the SETUP length, so enforce it to avoid large allocations.
+const MAX_SETUP_SIZE: u64 = u16::MAX as u64;
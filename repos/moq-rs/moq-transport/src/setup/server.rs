@@ -1,4 +1,8 @@
-use super::{Extensions, Role, Version};
+use std::io;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::{Extensions, Role, Version, MAX_SETUP_SIZE};
 use crate::{
 	coding::{Decode, DecodeError, Encode, EncodeError, Params},
 	VarInt,
@@ -9,7 +13,7 @@ use crate::coding::{AsyncRead, AsyncWrite};
 /// Sent by the server in response to a client setup.
 // NOTE: This is not a message type, but rather the control stream header.
 // Proposal: https://github.com/moq-wg/moq-transport/issues/138
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Server {
 	/// The list of supported versions in preferred order.
 	pub version: Version,
@@ -33,8 +37,27 @@ impl Server {
 			return Err(DecodeError::InvalidMessage(typ));
 		}
 
-		let version = Version::decode(r).await?;
-		let mut params = Params::decode(r).await?;
+		// The fork encodes the version immediately, while newer drafts encode the message length first.
+		// Every version is larger than the maximum length, so a value within the limit can only be a length.
+		let first = VarInt::decode(r).await?;
+
+		let (version, mut params) = if first.into_inner() > MAX_SETUP_SIZE {
+			(Version(first), Params::decode(r).await?)
+		} else {
+			let size = first.into_inner();
+
+			let mut buf = vec![0; size as usize];
+			r.read_exact(&mut buf).await?;
+			let mut payload = io::Cursor::new(buf);
+
+			let version = Version::decode(&mut payload).await?;
+			let params = Params::decode(&mut payload).await?;
+			if payload.position() != size {
+				return Err(DecodeError::InvalidLength);
+			}
+
+			(version, params)
+		};
 
 		let role = params
 			.get::<Role>(VarInt::from_u32(0))
@@ -59,12 +82,23 @@ impl Server {
 	/// Encode the server setup.
 	pub async fn encode<W: AsyncWrite>(&self, w: &mut W) -> Result<(), EncodeError> {
 		VarInt::from_u32(0x41).encode(w).await?;
-		self.version.encode(w).await?;
 
 		let mut params = self.params.clone();
 		params.set(VarInt::from_u32(0), self.role).await?;
 		self.extensions.store(&mut params).await?;
-		params.encode(w).await?;
+
+		if self.version.is_ietf() {
+			// Newer drafts prefix the message with its length.
+			let mut payload = Vec::new();
+			self.version.encode(&mut payload).await?;
+			params.encode(&mut payload).await?;
+
+			VarInt::try_from(payload.len())?.encode(w).await?;
+			w.write_all(&payload).await?;
+		} else {
+			self.version.encode(w).await?;
+			params.encode(w).await?;
+		}
 
 		Ok(())
 	}
@@ -0,0 +1,112 @@
+use std::io;
+
+use proptest::prelude::*;
+
+use super::*;
+use crate::coding::Params;
+
+fn block_on<F: std::future::Future>(f: F) -> F::Output {
+	tokio::runtime::Builder::new_current_thread()
+		.build()
+		.unwrap()
+		.block_on(f)
+}
+
+// Unknown parameters only, since the role, path and extensions are parsed out of them.
+fn params() -> impl Strategy<Value = Params> {
+	prop::collection::hash_map(
+		(0x40..0x1000u32).prop_map(VarInt::from_u32),
+		prop::collection::vec(any::<u8>(), 0..16),
+		0..4,
+	)
+	.prop_map(Params)
+}
+
+fn extensions() -> impl Strategy<Value = Extensions> {
+	any::<[bool; 7]>().prop_map(
+		|[object_expires, subscriber_id, subscribe_split, ntp_timestamp, switch_track_id, probe, datagram]| {
+			Extensions {
+				object_expires,
+				subscriber_id,
+				subscribe_split,
+				ntp_timestamp,
+				switch_track_id,
+				probe,
+				datagram,
+			}
+		},
+	)
+}
+
+fn role() -> impl Strategy<Value = Role> {
+	prop_oneof![Just(Role::Publisher), Just(Role::Subscriber), Just(Role::Both)]
+}
+
+fn version() -> impl Strategy<Value = Version> {
+	prop_oneof![
+		Just(Version::DRAFT_00),
+		Just(Version::DRAFT_01),
+		Just(Version::KIXEL_00),
+		Just(Version::KIXEL_01),
+		Just(Version::DRAFT_07),
+		// Unknown versions must be carried through too.
+		(0x10000..=VarInt::MAX.into_inner()).prop_map(|v| Version::from(VarInt::try_from(v).unwrap())),
+	]
+}
+
+fn client() -> impl Strategy<Value = Client> {
+	(
+		prop::collection::vec(version(), 0..4),
+		role(),
+		prop::option::of(any::<String>()),
+		extensions(),
+		params(),
+	)
+		.prop_map(|(versions, role, path, extensions, params)| Client {
+			versions: versions.into(),
+			role,
+			path,
+			extensions,
+			params,
+		})
+}
+
+fn server() -> impl Strategy<Value = Server> {
+	(version(), role(), extensions(), params()).prop_map(|(version, role, extensions, params)| Server {
+		version,
+		role,
+		extensions,
+		params,
+	})
+}
+
+proptest! {
+	#[test]
+	fn client_round_trip(client in client()) {
+		let mut buf = Vec::new();
+		block_on(client.encode(&mut buf)).unwrap();
+
+		let mut cursor = io::Cursor::new(&buf);
+		let decoded = block_on(Client::decode(&mut cursor)).unwrap();
+		prop_assert_eq!(decoded, client);
+		prop_assert_eq!(cursor.position() as usize, buf.len());
+	}
+
+	#[test]
+	fn server_round_trip(server in server()) {
+		let mut buf = Vec::new();
+		block_on(server.encode(&mut buf)).unwrap();
+
+		let mut cursor = io::Cursor::new(&buf);
+		let decoded = block_on(Server::decode(&mut cursor)).unwrap();
+		prop_assert_eq!(decoded, server);
+		prop_assert_eq!(cursor.position() as usize, buf.len());
+	}
+
+	#[test]
+	fn decode_arbitrary(buf in prop::collection::vec(any::<u8>(), 0..256)) {
+		// Garbage may fail to decode, but it must never panic.
+		let _ = block_on(Client::decode(&mut io::Cursor::new(&buf)));
+		let _ = block_on(Server::decode(&mut io::Cursor::new(&buf)));
+	}
+}
@@ -71,6 +71,16 @@ impl Version {
 	/// - OBJECT `priority` is still a VarInt, but the max value is a u32 (implementation reasons)
 	/// - OBJECT messages within the same `group` MUST be on the same QUIC stream.
 	pub const KIXEL_01: Version = Version(VarInt::from_u32(0xbad01));
+
+	/// https://www.ietf.org/archive/id/draft-ietf-moq-transport-07.html
+	///
+	/// Uses the separate wire format in [crate::ietf], including length-prefixed SETUP messages.
+	pub const DRAFT_07: Version = Version(VarInt::from_u32(0xff000007));
+
+	/// Returns true if this version uses the [crate::ietf] wire format instead of the fork.
+	pub fn is_ietf(&self) -> bool {
+		*self == Self::DRAFT_07
+	}
 }
 
 impl From<VarInt> for Version {
@@ -0,0 +1,540 @@
+use std::{
+	future::{self, Future},
+	io,
+	pin::Pin,
+	sync::{Arc, Mutex},
+	task::{ready, Context, Poll},
+	time,
+};
+
+use bytes::Bytes;
+use tokio::{
+	io::{AsyncRead, AsyncWrite, ReadBuf},
+	sync::{mpsc, watch},
+	time::{Instant, Sleep},
+};
+
+use super::{RecvStream, SendStream, Session, Stats};
+use crate::session::SessionError;
+
+/// Options for a [loopback] pair, simulating a network.
+///
+/// There's no bandwidth limit, so stream priorities have no effect.
+#[derive(Clone, Debug, Default)]
+pub struct LoopbackConfig {
+	/// The one-way delay before data written on one end can be read on the other.
+	pub latency: time::Duration,
+
+	/// The probability that a chunk of data is lost, between 0 and 1.
+	///
+	/// Streams are reliable, so a lost chunk is instead delayed by an extra round trip, like a retransmission.
+	pub loss: f64,
+
+	/// Decides which chunks are lost, so a lossy run can be reproduced.
+	pub seed: u64,
+
+	/// The largest datagram that can be sent, or None to disable datagrams.
+	///
+	/// Datagrams are unreliable, so a lost datagram is never delivered.
+	pub max_datagram_size: Option<usize>,
+}
+
+/// Create a pair of connected in-memory sessions.
+///
+/// Dropping either session closes the other one.
+/// Timers use [tokio::time], so tests can pause time to run instantly and deterministically.
+pub fn loopback(config: LoopbackConfig) -> (Loopback, Loopback) {
+	let (closed, _) = watch::channel(None);
+
+	let network = Arc::new(Network {
+		rng: Mutex::new(config.seed),
+		config,
+		closed,
+	});
+
+	let a: Arc<Mutex<Stats>> = Default::default();
+	let b: Arc<Mutex<Stats>> = Default::default();
+
+	let a_link = Link {
+		network: network.clone(),
+		local: a.clone(),
+		remote: b.clone(),
+	};
+
+	let b_link = Link {
+		network,
+		local: b,
+		remote: a,
+	};
+
+	let (a_uni, b_uni_accept) = mpsc::unbounded_channel();
+	let (b_uni, a_uni_accept) = mpsc::unbounded_channel();
+	let (a_bi, b_bi_accept) = mpsc::unbounded_channel();
+	let (b_bi, a_bi_accept) = mpsc::unbounded_channel();
+	let (a_datagram, b_datagram_recv) = mpsc::unbounded_channel();
+	let (b_datagram, a_datagram_recv) = mpsc::unbounded_channel();
+
+	let a = Loopback {
+		link: a_link.clone(),
+		peer: b_link.clone(),
+		uni: a_uni,
+		uni_accept: tokio::sync::Mutex::new(a_uni_accept),
+		bi: a_bi,
+		bi_accept: tokio::sync::Mutex::new(a_bi_accept),
+		datagram: a_datagram,
+		datagram_recv: tokio::sync::Mutex::new(a_datagram_recv),
+	};
+
+	let b = Loopback {
+		link: b_link,
+		peer: a_link,
+		uni: b_uni,
+		uni_accept: tokio::sync::Mutex::new(b_uni_accept),
+		bi: b_bi,
+		bi_accept: tokio::sync::Mutex::new(b_bi_accept),
+		datagram: b_datagram,
+		datagram_recv: tokio::sync::Mutex::new(b_datagram_recv),
+	};
+
+	(a, b)
+}
+
+#[derive(Debug)]
+struct Network {
+	config: LoopbackConfig,
+
+	// The state of the random number generator used to decide loss.
+	rng: Mutex<u64>,
+
+	// Set to the error code once either side closes the session.
+	closed: watch::Sender<Option<u32>>,
+}
+
+impl Network {
+	fn closed(&self) -> Result<(), SessionError> {
+		match *self.closed.borrow() {
+			Some(code) => Err(SessionError::Closed(code)),
+			None => Ok(()),
+		}
+	}
+
+	// Decide if the next chunk is lost, using splitmix64 so it's deterministic.
+	fn lost(&self) -> bool {
+		if self.config.loss <= 0.0 {
+			return false;
+		}
+
+		let mut state = self.rng.lock().unwrap();
+		*state = state.wrapping_add(0x9e3779b97f4a7c15);
+
+		let mut z = *state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		z ^= z >> 31;
+
+		((z >> 11) as f64 / (1u64 << 53) as f64) < self.config.loss
+	}
+}
+
+// One direction of the network, with the stats for each end.
+#[derive(Clone, Debug)]
+struct Link {
+	network: Arc<Network>,
+	local: Arc<Mutex<Stats>>,
+	remote: Arc<Mutex<Stats>>,
+}
+
+impl Link {
+	// Record a chunk being sent, returning when it can be read by the remote.
+	fn transmit(&self, size: usize) -> Instant {
+		let size = size as u64;
+		let lost = self.network.lost();
+		let latency = self.network.config.latency;
+
+		{
+			let mut local = self.local.lock().unwrap();
+			local.packets_sent += 1;
+			local.bytes_sent += size;
+
+			if lost {
+				local.packets_lost += 1;
+				local.bytes_lost += size;
+
+				// Count the retransmission too.
+				local.packets_sent += 1;
+				local.bytes_sent += size;
+			}
+		}
+
+		self.remote.lock().unwrap().bytes_received += size;
+
+		match lost {
+			true => Instant::now() + latency * 3,
+			false => Instant::now() + latency,
+		}
+	}
+
+	// Record a datagram being sent, returning when it can be read by the remote or None if it was lost.
+	fn transmit_datagram(&self, size: usize) -> Option<Instant> {
+		let size = size as u64;
+		let lost = self.network.lost();
+
+		{
+			let mut local = self.local.lock().unwrap();
+			local.packets_sent += 1;
+			local.bytes_sent += size;
+
+			if lost {
+				local.packets_lost += 1;
+				local.bytes_lost += size;
+				return None;
+			}
+		}
+
+		self.remote.lock().unwrap().bytes_received += size;
+
+		Some(Instant::now() + self.network.config.latency)
+	}
+
+	fn stream(&self) -> (LoopbackSend, LoopbackRecv) {
+		let (tx, rx) = mpsc::unbounded_channel();
+		let stopped = Arc::new(Mutex::new(None));
+		let reset = Arc::new(Mutex::new(None));
+
+		let send = LoopbackSend {
+			link: self.clone(),
+			frames: tx,
+			last: Instant::now(),
+			priority: 0,
+			finished: false,
+			stopped: stopped.clone(),
+			reset: reset.clone(),
+		};
+
+		let recv = LoopbackRecv {
+			network: self.network.clone(),
+			frames: rx,
+			next: None,
+			sleep: None,
+			stopped,
+			reset,
+		};
+
+		(send, recv)
+	}
+}
+
+/// One end of an in-memory session, created with [loopback].
+#[derive(Debug)]
+pub struct Loopback {
+	// The direction we send on, and the direction the remote sends on.
+	link: Link,
+	peer: Link,
+
+	uni: mpsc::UnboundedSender<LoopbackRecv>,
+	uni_accept: tokio::sync::Mutex<mpsc::UnboundedReceiver<LoopbackRecv>>,
+
+	bi: mpsc::UnboundedSender<(LoopbackSend, LoopbackRecv)>,
+	bi_accept: tokio::sync::Mutex<mpsc::UnboundedReceiver<(LoopbackSend, LoopbackRecv)>>,
+
+	// Datagrams along with when they arrive.
+	datagram: mpsc::UnboundedSender<(Instant, Bytes)>,
+	datagram_recv: tokio::sync::Mutex<mpsc::UnboundedReceiver<(Instant, Bytes)>>,
+}
+
+impl Loopback {
+	// Wait for the next stream from the remote, or until the session is closed.
+	async fn accept<T>(&self, streams: &tokio::sync::Mutex<mpsc::UnboundedReceiver<T>>) -> Result<T, SessionError> {
+		let mut streams = streams.lock().await;
+		let mut closed = self.link.network.closed.subscribe();
+
+		tokio::select! {
+			stream = streams.recv() => stream.ok_or(SessionError::Closed(0)),
+			code = closed.wait_for(Option::is_some) => match code {
+				Ok(code) => Err(SessionError::Closed(code.unwrap_or_default())),
+				Err(_) => Err(SessionError::Closed(0)),
+			},
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Session for Loopback {
+	async fn open_uni(&self) -> Result<Box<dyn SendStream>, SessionError> {
+		self.link.network.closed()?;
+
+		let (send, recv) = self.link.stream();
+		self.uni.send(recv).map_err(|_| SessionError::Closed(0))?;
+
+		Ok(Box::new(send))
+	}
+
+	async fn open_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		self.link.network.closed()?;
+
+		let (send, remote_recv) = self.link.stream();
+		let (remote_send, recv) = self.peer.stream();
+		self.bi
+			.send((remote_send, remote_recv))
+			.map_err(|_| SessionError::Closed(0))?;
+
+		Ok((Box::new(send), Box::new(recv)))
+	}
+
+	async fn accept_uni(&self) -> Result<Box<dyn RecvStream>, SessionError> {
+		let recv = self.accept(&self.uni_accept).await?;
+		Ok(Box::new(recv))
+	}
+
+	async fn accept_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		let (send, recv) = self.accept(&self.bi_accept).await?;
+		Ok((Box::new(send), Box::new(recv)))
+	}
+
+	fn max_datagram_size(&self) -> Option<usize> {
+		self.link.network.config.max_datagram_size
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> Result<(), SessionError> {
+		self.link.network.closed()?;
+
+		let max = self.max_datagram_size().ok_or(SessionError::DatagramUnsupported)?;
+		if payload.len() > max {
+			return Err(SessionError::DatagramTooLarge(payload.len()));
+		}
+
+		if let Some(at) = self.link.transmit_datagram(payload.len()) {
+			self.datagram.send((at, payload)).map_err(|_| SessionError::Closed(0))?;
+		}
+
+		Ok(())
+	}
+
+	async fn recv_datagram(&self) -> Result<Bytes, SessionError> {
+		let (at, payload) = self.accept(&self.datagram_recv).await?;
+		tokio::time::sleep_until(at).await;
+
+		Ok(payload)
+	}
+
+	fn close(&self, code: u32, _reason: &[u8]) {
+		self.link.network.closed.send_if_modified(|closed| match closed {
+			Some(_) => false,
+			None => {
+				*closed = Some(code);
+				true
+			}
+		});
+	}
+
+	fn stats(&self) -> Stats {
+		let mut stats = self.link.local.lock().unwrap().clone();
+		stats.rtt = self.link.network.config.latency * 2;
+		stats
+	}
+}
+
+#[derive(Debug)]
+enum Frame {
+	Chunk(Bytes),
+	Fin,
+	Reset(u32),
+}
+
+/// The sending half of a [Loopback] stream.
+#[derive(Debug)]
+pub struct LoopbackSend {
+	link: Link,
+	frames: mpsc::UnboundedSender<(Instant, Frame)>,
+
+	// Chunks are delivered in order, even if an earlier one was delayed by loss.
+	last: Instant,
+
+	priority: i32,
+	finished: bool,
+
+	// Set by the receiver when it stops the stream.
+	stopped: Arc<Mutex<Option<u32>>>,
+
+	// Set when the stream is reset, with the time the reset arrives.
+	reset: Arc<Mutex<Option<(Instant, u32)>>>,
+}
+
+impl LoopbackSend {
+	fn send(&mut self, chunk: Bytes) -> Result<(), SessionError> {
+		self.link.network.closed()?;
+
+		if self.finished {
+			return Err(SessionError::StreamClosed(0));
+		}
+
+		if let Some(code) = *self.stopped.lock().unwrap() {
+			return Err(SessionError::StreamClosed(code));
+		}
+
+		self.last = self.last.max(self.link.transmit(chunk.len()));
+		self.frames
+			.send((self.last, Frame::Chunk(chunk)))
+			.map_err(|_| SessionError::StreamClosed(0))
+	}
+
+	fn finish(&mut self) {
+		if !self.finished {
+			self.finished = true;
+			self.frames.send((self.last, Frame::Fin)).ok();
+		}
+	}
+
+	/// The priority set by [SendStream::set_priority].
+	pub fn priority(&self) -> i32 {
+		self.priority
+	}
+}
+
+#[async_trait::async_trait]
+impl SendStream for LoopbackSend {
+	async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), SessionError> {
+		self.send(chunk)
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		self.priority = order;
+	}
+
+	fn reset(&mut self, code: u32) {
+		if !self.finished {
+			self.finished = true;
+
+			// Like QUIC, a reset overtakes any data still in flight and discards anything unread.
+			let at = Instant::now() + self.link.network.config.latency;
+			*self.reset.lock().unwrap() = Some((at, code));
+
+			// Wake up the receiver if it's waiting for the next frame.
+			self.frames.send((at, Frame::Reset(code))).ok();
+		}
+	}
+}
+
+impl AsyncWrite for LoopbackSend {
+	fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+		let res = self.send(Bytes::copy_from_slice(buf));
+		Poll::Ready(res.map(|_| buf.len()).map_err(io_error))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		self.finish();
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl Drop for LoopbackSend {
+	fn drop(&mut self) {
+		// Like QUIC, dropping the stream gracefully finishes it.
+		self.finish();
+	}
+}
+
+/// The receiving half of a [Loopback] stream.
+#[derive(Debug)]
+pub struct LoopbackRecv {
+	network: Arc<Network>,
+	frames: mpsc::UnboundedReceiver<(Instant, Frame)>,
+
+	// The next frame, which may not have arrived yet.
+	next: Option<(Instant, Frame)>,
+	sleep: Option<Pin<Box<Sleep>>>,
+
+	stopped: Arc<Mutex<Option<u32>>>,
+	reset: Arc<Mutex<Option<(Instant, u32)>>>,
+}
+
+impl LoopbackRecv {
+	fn poll_chunk(&mut self, cx: &mut Context<'_>, max: usize) -> Poll<Result<Option<Bytes>, SessionError>> {
+		loop {
+			self.network.closed()?;
+
+			let reset = *self.reset.lock().unwrap();
+			if let Some((at, code)) = reset {
+				if at <= Instant::now() {
+					return Poll::Ready(Err(SessionError::StreamClosed(code)));
+				}
+			}
+
+			if self.next.is_none() {
+				match ready!(self.frames.poll_recv(cx)) {
+					Some(frame) => self.next = Some(frame),
+					// The sender always finishes when dropped, so this only happens after a stop.
+					None => return Poll::Ready(Ok(None)),
+				}
+			}
+
+			let at = self.next.as_ref().unwrap().0;
+
+			// Wake up early if a reset arrives before the next frame.
+			let wake = reset.map_or(at, |(reset, _)| reset.min(at));
+			if wake > Instant::now() {
+				let sleep = self
+					.sleep
+					.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(wake)));
+				if sleep.deadline() != wake {
+					sleep.as_mut().reset(wake);
+				}
+
+				ready!(sleep.as_mut().poll(cx));
+
+				// Check for the reset again.
+				continue;
+			}
+
+			match self.next.take().unwrap().1 {
+				Frame::Chunk(mut chunk) => {
+					if max > 0 && chunk.len() > max {
+						let remain = chunk.split_off(max);
+						self.next = Some((at, Frame::Chunk(remain)));
+					}
+
+					if !chunk.is_empty() {
+						return Poll::Ready(Ok(Some(chunk)));
+					}
+				}
+				Frame::Fin => {
+					// Keep returning None on subsequent reads.
+					self.next = Some((at, Frame::Fin));
+					return Poll::Ready(Ok(None));
+				}
+				Frame::Reset(code) => return Poll::Ready(Err(SessionError::StreamClosed(code))),
+			}
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl RecvStream for LoopbackRecv {
+	async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, SessionError> {
+		future::poll_fn(|cx| self.poll_chunk(cx, max)).await
+	}
+
+	fn stop(&mut self, code: u32) {
+		*self.stopped.lock().unwrap() = Some(code);
+		self.frames.close();
+	}
+}
+
+impl AsyncRead for LoopbackRecv {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+		let chunk = ready!(self.poll_chunk(cx, buf.remaining())).map_err(io_error)?;
+		if let Some(chunk) = chunk {
+			buf.put_slice(&chunk);
+		}
+
+		Poll::Ready(Ok(()))
+	}
+}
+
+fn io_error(err: SessionError) -> io::Error {
+	io::Error::new(io::ErrorKind::ConnectionReset, err.to_string())
+}
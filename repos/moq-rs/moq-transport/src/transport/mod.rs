@@ -0,0 +1,168 @@
+//! The transport carrying a MoQ session, abstracted so the session layer isn't tied to QUIC.
+//!
+//! A [Session] can open and accept unidirectional and bidirectional streams, matching the WebTransport API.
+//! It can optionally send and receive unreliable datagrams, if [Session::max_datagram_size] returns a size.
+//! It's implemented for [webtransport_quinn::Session], which is what you want for browsers,
+//! and for [quinn::Connection] to run MoQ directly over QUIC, see the [quic] module.
+//!
+//! The [loopback] pair connects two sessions in memory, without TLS certificates or a UDP socket.
+//! It can optionally simulate latency and loss, which is useful for deterministic tests.
+mod loopback;
+mod webtransport;
+
+pub mod quic;
+
+pub use loopback::*;
+
+use std::{fmt, time};
+
+use bytes::Bytes;
+use url::Url;
+
+use crate::session::SessionError;
+
+/// The ALPN to offer when connecting to the URL: [quic::ALPN] for `moqt://`, otherwise WebTransport.
+pub fn alpn(url: &Url) -> &'static [u8] {
+	match url.scheme() {
+		quic::SCHEME => quic::ALPN,
+		_ => webtransport_quinn::ALPN,
+	}
+}
+
+/// An error when connecting to a URL, see [connect].
+#[derive(thiserror::Error, Debug)]
+pub enum ConnectError {
+	#[error("quic error: {0}")]
+	Quic(#[from] quic::ConnectError),
+
+	#[error("webtransport error: {0}")]
+	WebTransport(#[from] webtransport_quinn::ClientError),
+}
+
+/// Connect to a `moqt://` URL directly over QUIC, or any other URL with WebTransport.
+///
+/// The client config must offer the [alpn] for the URL.
+/// Use [Client::with_url](crate::session::Client::with_url) so the path of a `moqt://` URL is sent during the handshake.
+pub async fn connect(
+	endpoint: &quinn::Endpoint,
+	config: quinn::ClientConfig,
+	url: &Url,
+) -> Result<Box<dyn Session>, ConnectError> {
+	let conn = quic::dial(endpoint, config, url).await?;
+
+	Ok(match url.scheme() {
+		quic::SCHEME => Box::new(conn),
+		_ => Box::new(webtransport_quinn::connect_with(conn, url).await?),
+	})
+}
+
+/// A connection capable of carrying a MoQ session.
+///
+/// Every method takes `&self` so the session can be shared between tasks.
+#[async_trait::async_trait]
+pub trait Session: Send + Sync + fmt::Debug + 'static {
+	/// Open a new unidirectional stream.
+	async fn open_uni(&self) -> Result<Box<dyn SendStream>, SessionError>;
+
+	/// Open a new bidirectional stream.
+	async fn open_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError>;
+
+	/// Accept the next unidirectional stream opened by the remote.
+	async fn accept_uni(&self) -> Result<Box<dyn RecvStream>, SessionError>;
+
+	/// Accept the next bidirectional stream opened by the remote.
+	async fn accept_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError>;
+
+	/// The largest datagram that can currently be sent, or None if datagrams are unsupported.
+	///
+	/// This depends on the path MTU, so it may change during the session.
+	fn max_datagram_size(&self) -> Option<usize>;
+
+	/// Send an unreliable datagram, which must be no larger than [Session::max_datagram_size].
+	fn send_datagram(&self, payload: Bytes) -> Result<(), SessionError>;
+
+	/// Receive the next datagram sent by the remote.
+	async fn recv_datagram(&self) -> Result<Bytes, SessionError>;
+
+	/// Immediately close the session with an error code and reason.
+	fn close(&self, code: u32, reason: &[u8]);
+
+	/// Snapshot the metrics of the underlying connection.
+	fn stats(&self) -> Stats;
+}
+
+// Allow choosing the transport at runtime, ex. based on the URL scheme.
+#[async_trait::async_trait]
+impl Session for Box<dyn Session> {
+	async fn open_uni(&self) -> Result<Box<dyn SendStream>, SessionError> {
+		(**self).open_uni().await
+	}
+
+	async fn open_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		(**self).open_bi().await
+	}
+
+	async fn accept_uni(&self) -> Result<Box<dyn RecvStream>, SessionError> {
+		(**self).accept_uni().await
+	}
+
+	async fn accept_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		(**self).accept_bi().await
+	}
+
+	fn max_datagram_size(&self) -> Option<usize> {
+		(**self).max_datagram_size()
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> Result<(), SessionError> {
+		(**self).send_datagram(payload)
+	}
+
+	async fn recv_datagram(&self) -> Result<Bytes, SessionError> {
+		(**self).recv_datagram().await
+	}
+
+	fn close(&self, code: u32, reason: &[u8]) {
+		(**self).close(code, reason)
+	}
+
+	fn stats(&self) -> Stats {
+		(**self).stats()
+	}
+}
+
+/// The sending half of a stream, which is finished when dropped.
+#[async_trait::async_trait]
+pub trait SendStream: tokio::io::AsyncWrite + Send + Unpin + fmt::Debug {
+	/// Write the entire chunk to the stream.
+	async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), SessionError>;
+
+	/// Set the priority relative to other streams on the same session, where **larger** values are sent first.
+	fn set_priority(&mut self, order: i32);
+
+	/// Abruptly terminate the stream with an error code.
+	fn reset(&mut self, code: u32);
+}
+
+/// The receiving half of a stream.
+#[async_trait::async_trait]
+pub trait RecvStream: tokio::io::AsyncRead + Send + Unpin + fmt::Debug {
+	/// Read the next chunk of at most `max` bytes, or None when the stream is finished.
+	async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, SessionError>;
+
+	/// Ask the remote to stop sending with an error code.
+	fn stop(&mut self, code: u32);
+}
+
+/// Metrics for the connection underlying a [Session].
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+	pub rtt: time::Duration,
+	pub cwnd: u64,
+	pub congestion_events: u64,
+	pub bytes_sent: u64,
+	pub bytes_received: u64,
+	pub bytes_lost: u64,
+	pub packets_sent: u64,
+	pub packets_lost: u64,
+}
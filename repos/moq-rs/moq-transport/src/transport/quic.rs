@@ -0,0 +1,183 @@
+//! MoQ directly over QUIC, without WebTransport.
+//!
+//! This avoids the HTTP/3 CONNECT overhead for clients that aren't browsers, such as relay-to-relay links.
+//! The connection uses its own [ALPN] and `moqt://` URLs, and the path is sent in the SETUP message instead.
+use bytes::Bytes;
+use tokio::net;
+use url::Url;
+
+use super::{RecvStream, SendStream, Session, Stats};
+use crate::session::SessionError;
+
+/// The ALPN used to negotiate MoQ directly over QUIC.
+pub const ALPN: &[u8] = b"moq-00";
+
+/// The URL scheme used to connect directly over QUIC.
+pub const SCHEME: &str = "moqt";
+
+/// An error when connecting to a `moqt://` URL.
+#[derive(thiserror::Error, Debug)]
+pub enum ConnectError {
+	#[error("invalid url: {0}")]
+	InvalidUrl(Url),
+
+	#[error("failed to resolve host: {0}")]
+	Resolve(#[from] std::io::Error),
+
+	#[error("failed to connect: {0}")]
+	Connect(#[from] quinn::ConnectError),
+
+	#[error("connection error: {0}")]
+	Connection(#[from] quinn::ConnectionError),
+}
+
+/// Connect to a `moqt://` URL, using a client config that offers [ALPN].
+///
+/// The path and query of the URL should be passed to [Client::with_path](crate::session::Client::with_path).
+pub async fn connect(
+	endpoint: &quinn::Endpoint,
+	config: quinn::ClientConfig,
+	url: &Url,
+) -> Result<quinn::Connection, ConnectError> {
+	if url.scheme() != SCHEME {
+		return Err(ConnectError::InvalidUrl(url.clone()));
+	}
+
+	dial(endpoint, config, url).await
+}
+
+// Resolve the host of the URL and establish a QUIC connection, regardless of the scheme.
+pub(super) async fn dial(
+	endpoint: &quinn::Endpoint,
+	config: quinn::ClientConfig,
+	url: &Url,
+) -> Result<quinn::Connection, ConnectError> {
+	let host = url.host_str().ok_or_else(|| ConnectError::InvalidUrl(url.clone()))?;
+	let port = url.port().unwrap_or(443);
+
+	let addr = net::lookup_host((host, port))
+		.await?
+		.next()
+		.ok_or_else(|| ConnectError::InvalidUrl(url.clone()))?;
+
+	let conn = endpoint.connect_with(config, addr, host)?.await?;
+	Ok(conn)
+}
+
+#[async_trait::async_trait]
+impl Session for quinn::Connection {
+	async fn open_uni(&self) -> Result<Box<dyn SendStream>, SessionError> {
+		let send = quinn::Connection::open_uni(self)
+			.await
+			.map_err(webtransport_quinn::SessionError::from)?;
+
+		Ok(Box::new(send))
+	}
+
+	async fn open_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		let (send, recv) = quinn::Connection::open_bi(self)
+			.await
+			.map_err(webtransport_quinn::SessionError::from)?;
+
+		Ok((Box::new(send), Box::new(recv)))
+	}
+
+	async fn accept_uni(&self) -> Result<Box<dyn RecvStream>, SessionError> {
+		let recv = quinn::Connection::accept_uni(self)
+			.await
+			.map_err(webtransport_quinn::SessionError::from)?;
+
+		Ok(Box::new(recv))
+	}
+
+	async fn accept_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		let (send, recv) = quinn::Connection::accept_bi(self)
+			.await
+			.map_err(webtransport_quinn::SessionError::from)?;
+
+		Ok((Box::new(send), Box::new(recv)))
+	}
+
+	fn max_datagram_size(&self) -> Option<usize> {
+		quinn::Connection::max_datagram_size(self)
+	}
+
+	fn send_datagram(&self, payload: Bytes) -> Result<(), SessionError> {
+		let size = payload.len();
+
+		quinn::Connection::send_datagram(self, payload).map_err(|err| match err {
+			quinn::SendDatagramError::TooLarge => SessionError::DatagramTooLarge(size),
+			quinn::SendDatagramError::ConnectionLost(err) => webtransport_quinn::SessionError::from(err).into(),
+			quinn::SendDatagramError::UnsupportedByPeer | quinn::SendDatagramError::Disabled => {
+				SessionError::DatagramUnsupported
+			}
+		})
+	}
+
+	async fn recv_datagram(&self) -> Result<Bytes, SessionError> {
+		let payload = quinn::Connection::read_datagram(self)
+			.await
+			.map_err(webtransport_quinn::SessionError::from)?;
+
+		Ok(payload)
+	}
+
+	fn close(&self, code: u32, reason: &[u8]) {
+		quinn::Connection::close(self, code.into(), reason)
+	}
+
+	fn stats(&self) -> Stats {
+		stats(self)
+	}
+}
+
+// Shared with WebTransport, which runs over the same quinn connection.
+pub(super) fn stats(conn: &quinn::Connection) -> Stats {
+	let stats = conn.stats();
+
+	Stats {
+		rtt: stats.path.rtt,
+		cwnd: stats.path.cwnd,
+		congestion_events: stats.path.congestion_events,
+		bytes_sent: stats.udp_tx.bytes,
+		bytes_received: stats.udp_rx.bytes,
+		bytes_lost: stats.path.lost_bytes,
+		packets_sent: stats.path.sent_packets,
+		packets_lost: stats.path.lost_packets,
+	}
+}
+
+#[async_trait::async_trait]
+impl SendStream for quinn::SendStream {
+	async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), SessionError> {
+		quinn::SendStream::write_chunk(self, chunk)
+			.await
+			.map_err(webtransport_quinn::WriteError::from)?;
+
+		Ok(())
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		// Fails only if the stream was already closed, in which case the priority doesn't matter.
+		quinn::SendStream::set_priority(self, order).ok();
+	}
+
+	fn reset(&mut self, code: u32) {
+		quinn::SendStream::reset(self, code.into()).ok();
+	}
+}
+
+#[async_trait::async_trait]
+impl RecvStream for quinn::RecvStream {
+	async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, SessionError> {
+		let chunk = quinn::RecvStream::read_chunk(self, max, true)
+			.await
+			.map_err(webtransport_quinn::ReadError::from)?;
+
+		Ok(chunk.map(|chunk| chunk.bytes))
+	}
+
+	fn stop(&mut self, code: u32) {
+		quinn::RecvStream::stop(self, code.into()).ok();
+	}
+}
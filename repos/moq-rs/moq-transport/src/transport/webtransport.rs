@@ -0,0 +1,78 @@
+use bytes::Bytes;
+
+use super::{RecvStream, SendStream, Session, Stats};
+use crate::session::SessionError;
+
+#[async_trait::async_trait]
+impl Session for webtransport_quinn::Session {
+	async fn open_uni(&self) -> Result<Box<dyn SendStream>, SessionError> {
+		let send = webtransport_quinn::Session::open_uni(self).await?;
+		Ok(Box::new(send))
+	}
+
+	async fn open_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		let (send, recv) = webtransport_quinn::Session::open_bi(self).await?;
+		Ok((Box::new(send), Box::new(recv)))
+	}
+
+	async fn accept_uni(&self) -> Result<Box<dyn RecvStream>, SessionError> {
+		let recv = webtransport_quinn::Session::accept_uni(self).await?;
+		Ok(Box::new(recv))
+	}
+
+	async fn accept_bi(&self) -> Result<(Box<dyn SendStream>, Box<dyn RecvStream>), SessionError> {
+		let (send, recv) = webtransport_quinn::Session::accept_bi(self).await?;
+		Ok((Box::new(send), Box::new(recv)))
+	}
+
+	// TODO webtransport-quinn doesn't support datagrams yet, so everything is sent over streams.
+	fn max_datagram_size(&self) -> Option<usize> {
+		None
+	}
+
+	fn send_datagram(&self, _payload: Bytes) -> Result<(), SessionError> {
+		Err(SessionError::DatagramUnsupported)
+	}
+
+	async fn recv_datagram(&self) -> Result<Bytes, SessionError> {
+		// Block forever, just like a peer that never sends a datagram.
+		std::future::pending().await
+	}
+
+	fn close(&self, code: u32, reason: &[u8]) {
+		webtransport_quinn::Session::close(self, code, reason)
+	}
+
+	fn stats(&self) -> Stats {
+		super::quic::stats(self)
+	}
+}
+
+#[async_trait::async_trait]
+impl SendStream for webtransport_quinn::SendStream {
+	async fn write_chunk(&mut self, chunk: Bytes) -> Result<(), SessionError> {
+		webtransport_quinn::SendStream::write_chunk(self, chunk).await?;
+		Ok(())
+	}
+
+	fn set_priority(&mut self, order: i32) {
+		// Fails only if the stream was already closed, in which case the priority doesn't matter.
+		webtransport_quinn::SendStream::set_priority(self, order).ok();
+	}
+
+	fn reset(&mut self, code: u32) {
+		webtransport_quinn::SendStream::reset(self, code).ok();
+	}
+}
+
+#[async_trait::async_trait]
+impl RecvStream for webtransport_quinn::RecvStream {
+	async fn read_chunk(&mut self, max: usize) -> Result<Option<Bytes>, SessionError> {
+		let chunk = webtransport_quinn::RecvStream::read_chunk(self, max, true).await?;
+		Ok(chunk.map(|chunk| chunk.bytes))
+	}
+
+	fn stop(&mut self, code: u32) {
+		webtransport_quinn::RecvStream::stop(self, code).ok();
+	}
+}
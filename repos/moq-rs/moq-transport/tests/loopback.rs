@@ -0,0 +1,683 @@
+use std::{
+	sync::{Arc, Mutex},
+	time,
+};
+
+use bytes::Bytes;
+use moq_transport::{
+	cache::{broadcast, segment, track},
+	session::{
+		AbrContext, AbrPolicy, Client, PriorityContext, PriorityPolicy, Publisher, Server, SessionError,
+		SubscribeOptions, Subscriber, WriteStrategy,
+	},
+	transport::{loopback, LoopbackConfig},
+	MoqError, VarInt,
+};
+
+// Connect a client publisher to a server subscriber, like moq-pub to moq-relay.
+async fn publish(
+	config: LoopbackConfig,
+	source: broadcast::Subscriber,
+	sink: broadcast::Publisher,
+) -> Result<(), SessionError> {
+	let (client, server) = loopback(config);
+	let connect = Client::default();
+	let (publisher, subscriber) = tokio::try_join!(connect.publisher(client, source), async {
+		Server::accept(server).await?.subscriber(sink).await
+	})?;
+
+	tokio::spawn(publisher.run());
+	tokio::spawn(subscriber.run());
+
+	Ok(())
+}
+
+// Set up a session between a client subscriber and a server publisher, without running it.
+async fn connect(
+	config: LoopbackConfig,
+	source: broadcast::Subscriber,
+	sink: broadcast::Publisher,
+) -> Result<(Subscriber, Publisher), SessionError> {
+	let (session, server) = loopback(config);
+	let client = Client::default();
+	let (subscriber, publisher) = tokio::try_join!(client.subscriber(session, sink), async {
+		Server::accept(server).await?.publisher(source).await
+	})?;
+
+	Ok((subscriber, publisher))
+}
+
+// Connect a client subscriber to a server publisher, like a viewer to moq-relay.
+async fn subscribe(
+	config: LoopbackConfig,
+	source: broadcast::Subscriber,
+	sink: broadcast::Publisher,
+) -> Result<(), SessionError> {
+	let (subscriber, publisher) = connect(config, source, sink).await?;
+
+	tokio::spawn(subscriber.run());
+	tokio::spawn(publisher.run());
+
+	Ok(())
+}
+
+fn write_segment(track: &mut track::Publisher, sequence: u32, payload: &[&'static [u8]]) {
+	let mut segment = track
+		.create_segment(segment::Info {
+			sequence: VarInt::from_u32(sequence),
+			priority: 0,
+			expires: Some(time::Duration::from_secs(10)),
+		})
+		.unwrap();
+
+	for (i, data) in payload.iter().enumerate() {
+		let mut fragment = segment.fragment(VarInt::from_u32(i as u32), data.len()).unwrap();
+		fragment.chunk(Bytes::from_static(data)).unwrap();
+	}
+}
+
+async fn read_segment(track: &mut track::Subscriber) -> (VarInt, Vec<Bytes>) {
+	let mut segment = track.segment().await.unwrap().expect("track ended early");
+	let mut payload = Vec::new();
+
+	while let Some(mut fragment) = segment.fragment().await.unwrap() {
+		let mut data = Vec::new();
+		while let Some(chunk) = fragment.chunk().await.unwrap() {
+			data.extend_from_slice(&chunk);
+		}
+
+		payload.push(Bytes::from(data));
+	}
+
+	(segment.sequence, payload)
+}
+
+// Publish a track through a relay and read it back from a viewer.
+async fn relay(config: LoopbackConfig) {
+	let (mut origin, source) = broadcast::new("");
+	let (relay_publisher, relay_subscriber) = broadcast::new("");
+	let (viewer_publisher, viewer_subscriber) = broadcast::new("");
+
+	let mut track = origin.create_track("video").unwrap();
+	write_segment(&mut track, 0, &[b"hello", b"world"]);
+
+	publish(config.clone(), source, relay_publisher).await.unwrap();
+	subscribe(config, relay_subscriber, viewer_publisher).await.unwrap();
+
+	let mut viewer = viewer_subscriber.get_track("video").unwrap();
+
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(0));
+	assert_eq!(payload, [&b"hello"[..], &b"world"[..]]);
+
+	// Segments written after the subscription also make it through.
+	write_segment(&mut track, 1, &[b"again"]);
+
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(1));
+	assert_eq!(payload, [&b"again"[..]]);
+}
+
+#[tokio::test]
+async fn relay_loopback() {
+	relay(LoopbackConfig::default()).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn relay_lossy() {
+	relay(LoopbackConfig {
+		latency: time::Duration::from_millis(50),
+		loss: 0.2,
+		seed: 1,
+		..Default::default()
+	})
+	.await;
+}
+
+// Connect a client subscriber that asks for the track to be delivered as datagrams.
+async fn subscribe_datagrams(
+	config: LoopbackConfig,
+	source: broadcast::Subscriber,
+	sink: broadcast::Publisher,
+	track: &str,
+) -> Result<Subscriber, SessionError> {
+	let (subscriber, publisher) = connect(config, source, sink).await?;
+	let subscriber = subscriber.with_datagrams([track]);
+
+	tokio::spawn(subscriber.clone().run());
+	tokio::spawn(publisher.run());
+
+	Ok(subscriber)
+}
+
+async fn read_fragment(segment: &mut segment::Subscriber) -> Option<Bytes> {
+	let mut fragment = segment.fragment().await.unwrap()?;
+	let mut data = Vec::new();
+	while let Some(chunk) = fragment.chunk().await.unwrap() {
+		data.extend_from_slice(&chunk);
+	}
+
+	Some(Bytes::from(data))
+}
+
+#[tokio::test]
+async fn datagram_delivery() {
+	let config = LoopbackConfig {
+		max_datagram_size: Some(1200),
+		..Default::default()
+	};
+
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, viewer_subscriber) = broadcast::new("");
+
+	let mut track = origin.create_track("audio").unwrap();
+	write_segment(&mut track, 0, &[b"hello", b"world"]);
+
+	let session = subscribe_datagrams(config, source, viewer_publisher, "audio")
+		.await
+		.unwrap();
+
+	let mut viewer = viewer_subscriber.get_track("audio").unwrap();
+
+	let mut segment = viewer.segment().await.unwrap().unwrap();
+	assert_eq!(segment.sequence, VarInt::from_u32(0));
+	assert_eq!(read_fragment(&mut segment).await.unwrap(), "hello");
+	assert_eq!(read_fragment(&mut segment).await.unwrap(), "world");
+
+	// A datagram group is only finished once the next group starts.
+	write_segment(&mut track, 1, &[b"again"]);
+	assert!(read_fragment(&mut segment).await.is_none());
+
+	let mut segment = viewer.segment().await.unwrap().unwrap();
+	assert_eq!(segment.sequence, VarInt::from_u32(1));
+	assert_eq!(read_fragment(&mut segment).await.unwrap(), "again");
+
+	let stats = session.stats();
+	assert_eq!(stats.datagrams_received, 3);
+	assert_eq!(stats.streams_accepted, 0);
+}
+
+#[tokio::test]
+async fn datagram_fallback() {
+	let config = LoopbackConfig {
+		max_datagram_size: Some(64),
+		..Default::default()
+	};
+
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, viewer_subscriber) = broadcast::new("");
+
+	// The OBJECT doesn't fit in a datagram, so it's sent over a stream instead.
+	let mut track = origin.create_track("audio").unwrap();
+	write_segment(&mut track, 0, &[&[1; 128]]);
+
+	let session = subscribe_datagrams(config, source, viewer_publisher, "audio")
+		.await
+		.unwrap();
+
+	let mut viewer = viewer_subscriber.get_track("audio").unwrap();
+
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(0));
+	assert_eq!(payload, [&[1; 128][..]]);
+
+	let stats = session.stats();
+	assert_eq!(stats.datagrams_received, 0);
+	assert_eq!(stats.streams_accepted, 1);
+}
+
+// Records the segments it was asked to prioritize.
+#[derive(Debug, Default)]
+struct RecordPriority {
+	segments: Mutex<Vec<(String, VarInt, Option<u8>)>>,
+}
+
+impl PriorityPolicy for RecordPriority {
+	fn priority(&self, segment: &PriorityContext) -> i32 {
+		let mut segments = self.segments.lock().unwrap();
+		segments.push((segment.track.to_string(), segment.group, segment.subscriber_priority));
+		0
+	}
+}
+
+#[tokio::test]
+async fn priority_policy() {
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, viewer_subscriber) = broadcast::new("");
+
+	let mut track = origin.create_track("audio").unwrap();
+	write_segment(&mut track, 0, &[b"hello"]);
+
+	let policy = Arc::new(RecordPriority::default());
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, viewer_publisher)
+		.await
+		.unwrap();
+
+	tokio::spawn(subscriber.with_track_priority("audio", 3).run());
+	tokio::spawn(publisher.with_priority(policy.clone()).run());
+
+	let mut viewer = viewer_subscriber.get_track("audio").unwrap();
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(0));
+	assert_eq!(payload, [&b"hello"[..]]);
+
+	let segments = policy.segments.lock().unwrap();
+	assert_eq!(*segments, [("audio".to_string(), VarInt::ZERO, Some(3))]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn max_latency() {
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, viewer_subscriber) = broadcast::new("");
+
+	let mut track = origin.create_track("video").unwrap();
+	write_segment(&mut track, 0, &[b"stale"]);
+
+	// The first segment is older than the max latency by the time it's requested.
+	tokio::time::advance(time::Duration::from_millis(100)).await;
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, viewer_publisher)
+		.await
+		.unwrap();
+
+	let stats = publisher.clone();
+	let latency = time::Duration::from_millis(50);
+	tokio::spawn(subscriber.with_track_max_latency("video", latency).run());
+	tokio::spawn(publisher.run());
+
+	let mut viewer = viewer_subscriber.get_track("video").unwrap();
+	write_segment(&mut track, 1, &[b"fresh"]);
+
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(1));
+	assert_eq!(payload, [&b"fresh"[..]]);
+
+	let dropped: u64 = stats.stats().served.values().map(|sub| sub.dropped).sum();
+	assert_eq!(dropped, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn write_strategy() {
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, viewer_subscriber) = broadcast::new("");
+
+	let mut track = origin.create_track("video").unwrap();
+	write_segment(&mut track, 0, &[&[0; 4000], &[1; 4000]]);
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, viewer_publisher)
+		.await
+		.unwrap();
+
+	// Coalesce each OBJECT header with its payload, and pace at 800kbps.
+	let strategy = WriteStrategy::new()
+		.with_coalesce(1000, time::Duration::from_millis(10))
+		.with_pacing(800_000);
+
+	let start = tokio::time::Instant::now();
+	tokio::spawn(subscriber.run());
+	tokio::spawn(publisher.with_write_strategy(strategy).run());
+
+	let mut viewer = viewer_subscriber.get_track("video").unwrap();
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(0));
+	assert_eq!(payload, [&[0; 4000][..], &[1; 4000][..]]);
+
+	// The second OBJECT waits for the first 4000 bytes to be paced out, but not much longer.
+	assert!(start.elapsed() >= time::Duration::from_millis(40));
+	assert!(start.elapsed() < time::Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn subscription() {
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, _viewer_subscriber) = broadcast::new("");
+
+	let mut track = origin.create_track("video").unwrap();
+	write_segment(&mut track, 0, &[b"hello", b"world"]);
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, viewer_publisher)
+		.await
+		.unwrap();
+
+	tokio::spawn(subscriber.clone().run());
+	tokio::spawn(publisher.run());
+
+	// Without a router, any other namespace is rejected as not found.
+	let missing = SubscribeOptions::new().with_namespace("missing");
+	let err = subscriber.subscribe("video", missing).await.unwrap_err();
+	assert_eq!(err.code(), 404);
+
+	let mut subscription = subscriber.subscribe("video", SubscribeOptions::new()).await.unwrap();
+	assert_eq!(subscription.name(), "video");
+
+	let mut segment = subscription.segment().await.unwrap().unwrap();
+	assert_eq!(segment.sequence, VarInt::ZERO);
+	let payload = read_fragment(&mut segment).await.unwrap();
+	assert_eq!(payload, &b"hello"[..]);
+
+	// Ending the track finishes the subscription.
+	drop(track);
+
+	let fin = subscription.finished().await.unwrap();
+	assert_eq!(fin.final_group, VarInt::ZERO);
+	assert_eq!(fin.final_object, VarInt::from_u32(1));
+	assert!(subscription.segment().await.unwrap().is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn subscription_closed() {
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, _viewer_subscriber) = broadcast::new("");
+
+	let _track = origin.create_track("video").unwrap();
+
+	// The SUBSCRIBE_OK takes a round trip, so the session ends first.
+	let config = LoopbackConfig {
+		latency: time::Duration::from_secs(1),
+		..Default::default()
+	};
+
+	let (subscriber, publisher) = connect(config, source, viewer_publisher).await.unwrap();
+
+	tokio::spawn(subscriber.clone().run());
+	tokio::spawn(publisher.run());
+
+	let subscribe = tokio::spawn(async move { subscriber.subscribe("video", SubscribeOptions::new()).await });
+	tokio::time::sleep(time::Duration::from_millis(500)).await;
+	drop(origin);
+
+	let res = tokio::time::timeout(time::Duration::from_secs(10), subscribe)
+		.await
+		.unwrap();
+	assert!(res.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn switch_track() {
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, _viewer_subscriber) = broadcast::new("");
+
+	let mut low = origin.create_track("low").unwrap();
+	let mut high = origin.create_track("high").unwrap();
+	write_segment(&mut low, 0, &[b"low0"]);
+	write_segment(&mut high, 0, &[b"high0"]);
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, viewer_publisher)
+		.await
+		.unwrap();
+
+	tokio::spawn(subscriber.clone().run());
+	tokio::spawn(publisher.run());
+
+	let mut before = subscriber.subscribe("low", SubscribeOptions::new()).await.unwrap();
+	let mut segment = before.segment().await.unwrap().unwrap();
+	assert_eq!(read_fragment(&mut segment).await.unwrap(), &b"low0"[..]);
+
+	// The new track starts at the next group, skipping the group already sent on the old track.
+	let mut after = before.switch("high", SubscribeOptions::new()).await.unwrap();
+	write_segment(&mut high, 1, &[b"high1"]);
+
+	let mut segment = after.segment().await.unwrap().unwrap();
+	assert_eq!(segment.sequence, VarInt::from_u32(1));
+	assert_eq!(read_fragment(&mut segment).await.unwrap(), &b"high1"[..]);
+
+	// The old track is finished once it reaches the same group.
+	write_segment(&mut low, 1, &[b"low1"]);
+
+	let fin = before.finished().await.unwrap();
+	assert_eq!(fin.final_group, VarInt::ZERO);
+	assert!(before.segment().await.unwrap().is_none());
+}
+
+// Switch every subscription to the "high" track from the second group onwards.
+#[derive(Debug)]
+struct SwitchHigh;
+
+impl AbrPolicy for SwitchHigh {
+	fn select(&self, ctx: &AbrContext) -> Option<String> {
+		assert_eq!(ctx.requested, "low");
+		(ctx.group >= VarInt::from_u32(1)).then(|| "high".to_string())
+	}
+}
+
+#[tokio::test]
+async fn abr_policy() {
+	let (mut origin, source) = broadcast::new("");
+	let (viewer_publisher, viewer_subscriber) = broadcast::new("");
+
+	let mut low = origin.create_track("low").unwrap();
+	let mut high = origin.create_track("high").unwrap();
+	write_segment(&mut low, 0, &[b"low0"]);
+	write_segment(&mut high, 0, &[b"high0"]);
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, viewer_publisher)
+		.await
+		.unwrap();
+
+	tokio::spawn(subscriber.run());
+	tokio::spawn(publisher.with_abr(Arc::new(SwitchHigh)).run());
+
+	let mut viewer = viewer_subscriber.get_track("low").unwrap();
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::ZERO);
+	assert_eq!(payload, [&b"low0"[..]]);
+
+	// The subscription keeps its track name, but is served from the other rendition starting at the new group.
+	write_segment(&mut low, 1, &[b"low1"]);
+	write_segment(&mut high, 1, &[b"high1"]);
+
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(1));
+	assert_eq!(payload, [&b"high1"[..]]);
+}
+
+// Connect a server publisher to a client subscriber, like moq-relay fetching from another origin.
+async fn fetch(source: broadcast::Subscriber, sink: broadcast::Publisher) -> Subscriber {
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, sink).await.unwrap();
+
+	tokio::spawn(publisher.run());
+	subscriber
+}
+
+#[tokio::test]
+async fn resume_tracks() {
+	let (mut origin, source) = broadcast::new("");
+	let (mut relay, cached) = broadcast::new("");
+
+	let mut track = origin.create_track("video").unwrap();
+	write_segment(&mut track, 0, &[b"first"]);
+
+	let upstream = fetch(source.clone(), relay.clone()).await;
+	let session = tokio::spawn(upstream.clone().run());
+
+	let mut viewer = cached.get_track("video").unwrap();
+	assert_eq!(read_segment(&mut viewer).await.1, [&b"first"[..]]);
+
+	// The upstream session fails, but the viewer keeps the same track when it's resumed with a new session.
+	session.abort();
+	for track in upstream.detach() {
+		relay.requeue(track).unwrap();
+	}
+
+	let upstream = fetch(source, relay).await;
+	tokio::spawn(upstream.run());
+
+	write_segment(&mut track, 1, &[b"second"]);
+
+	let (sequence, payload) = read_segment(&mut viewer).await;
+	assert_eq!(sequence, VarInt::from_u32(1));
+	assert_eq!(payload, [&b"second"[..]]);
+}
+
+#[tokio::test]
+async fn probe() {
+	let (_origin, source) = broadcast::new("");
+	let (sink, _cached) = broadcast::new("");
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, sink).await.unwrap();
+	tokio::spawn(subscriber.clone().run());
+	tokio::spawn(publisher.run());
+
+	let size = VarInt::from_u32(1000);
+	let count = VarInt::from_u32(4);
+	let result = subscriber.probe(size, 0, count, time::Duration::ZERO).await.unwrap();
+
+	assert_eq!(result.bytes, 4000);
+	assert_eq!(result.sent.len(), 4);
+
+	// Too many OBJECTs to list in the PROBE_OK, even when they're empty.
+	let count = VarInt::from_u32(1_000_000);
+	let err = subscriber.probe(VarInt::ZERO, 0, count, time::Duration::ZERO).await;
+	assert_eq!(err.unwrap_err().code(), 400);
+}
+
+#[tokio::test]
+async fn probe_closed() {
+	let (origin, source) = broadcast::new("");
+	let (sink, _cached) = broadcast::new("");
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, sink).await.unwrap();
+	tokio::spawn(subscriber.clone().run());
+	tokio::spawn(publisher.run());
+
+	// The publisher waits a minute between OBJECTs, so the probe is still outstanding when the session ends.
+	let pacing = time::Duration::from_secs(60);
+	let probe = tokio::spawn(async move {
+		subscriber
+			.probe(VarInt::from_u32(1000), 0, VarInt::from_u32(2), pacing)
+			.await
+	});
+
+	tokio::time::sleep(time::Duration::from_millis(50)).await;
+	drop(origin);
+
+	let res = tokio::time::timeout(time::Duration::from_secs(5), probe)
+		.await
+		.unwrap()
+		.unwrap();
+	assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn go_away() {
+	let (mut origin, source) = broadcast::new("");
+	let (sink, cached) = broadcast::new("");
+
+	let mut track = origin.create_track("video").unwrap();
+	write_segment(&mut track, 0, &[b"first"]);
+
+	let (subscriber, publisher) = connect(LoopbackConfig::default(), source, sink).await.unwrap();
+
+	let going_away = subscriber.clone();
+	let session = tokio::spawn(subscriber.run());
+	tokio::spawn(publisher.clone().run());
+
+	let mut viewer = cached.get_track("video").unwrap();
+	assert_eq!(read_segment(&mut viewer).await.1, [&b"first"[..]]);
+
+	// The client is told where to reconnect, but keeps receiving until the server closes the session.
+	publisher.go_away("https://other.example/demo").await.unwrap();
+	assert_eq!(going_away.going_away().await, "https://other.example/demo");
+
+	write_segment(&mut track, 1, &[b"second"]);
+
+	assert_eq!(read_segment(&mut viewer).await.1, [&b"second"[..]]);
+	assert!(!session.is_finished());
+}
+
+#[tokio::test(start_paused = true)]
+async fn loopback_datagram_loss() {
+	use moq_transport::transport::Session;
+
+	let config = LoopbackConfig {
+		loss: 0.5,
+		seed: 1,
+		max_datagram_size: Some(4),
+		..Default::default()
+	};
+
+	let (a, b) = loopback(config);
+
+	assert!(matches!(
+		a.send_datagram(Bytes::from_static(b"hello")),
+		Err(SessionError::DatagramTooLarge(5))
+	));
+
+	for _ in 0..32 {
+		a.send_datagram(Bytes::from_static(b"ping")).unwrap();
+	}
+
+	// Lost datagrams are never delivered, unlike stream data.
+	let stats = a.stats();
+	assert_eq!(stats.packets_sent, 32);
+	assert!(stats.packets_lost > 0);
+	assert_eq!(b.stats().bytes_received, (32 - stats.packets_lost) * 4);
+
+	assert_eq!(b.recv_datagram().await.unwrap(), "ping");
+}
+
+#[tokio::test(start_paused = true)]
+async fn loopback_latency() {
+	use moq_transport::transport::Session;
+
+	let config = LoopbackConfig {
+		latency: time::Duration::from_millis(100),
+		..Default::default()
+	};
+
+	let (a, b) = loopback(config);
+
+	let mut send = a.open_uni().await.unwrap();
+	let start = tokio::time::Instant::now();
+	send.write_chunk(Bytes::from_static(b"ping")).await.unwrap();
+	drop(send);
+
+	let mut recv = b.accept_uni().await.unwrap();
+	assert_eq!(recv.read_chunk(usize::MAX).await.unwrap().unwrap(), "ping");
+	assert_eq!(start.elapsed(), time::Duration::from_millis(100));
+	assert!(recv.read_chunk(usize::MAX).await.unwrap().is_none());
+
+	assert_eq!(a.stats().bytes_sent, 4);
+	assert_eq!(b.stats().bytes_received, 4);
+	assert_eq!(a.stats().rtt, time::Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn loopback_close() {
+	use moq_transport::transport::Session;
+
+	let (a, b) = loopback(LoopbackConfig::default());
+	a.close(42, b"bye");
+
+	assert!(matches!(b.accept_uni().await, Err(SessionError::Closed(42))));
+	assert!(matches!(b.open_bi().await, Err(SessionError::Closed(42))));
+}
+
+#[tokio::test(start_paused = true)]
+async fn loopback_reset() {
+	use moq_transport::transport::Session;
+
+	let config = LoopbackConfig {
+		latency: time::Duration::from_millis(100),
+		..Default::default()
+	};
+
+	let (a, b) = loopback(config);
+
+	let mut send = a.open_uni().await.unwrap();
+	send.write_chunk(Bytes::from_static(b"first")).await.unwrap();
+
+	let mut recv = b.accept_uni().await.unwrap();
+	tokio::time::sleep(time::Duration::from_millis(150)).await;
+
+	send.write_chunk(Bytes::from_static(b"second")).await.unwrap();
+	send.reset(7);
+
+	// Even the chunk that already arrived is discarded once the reset arrives.
+	tokio::time::sleep(time::Duration::from_millis(100)).await;
+	assert!(matches!(
+		recv.read_chunk(usize::MAX).await,
+		Err(SessionError::StreamClosed(7))
+	));
+}
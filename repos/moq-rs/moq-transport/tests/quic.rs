@@ -0,0 +1,114 @@
+use std::{fs, io, sync::Arc, time};
+
+use bytes::Bytes;
+use moq_transport::{
+	cache::{broadcast, segment},
+	session::{Client, Server},
+	transport::{self, quic},
+	VarInt,
+};
+use url::Url;
+
+// A self-signed certificate for localhost, generated with:
+// openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:prime256v1 -nodes -days 36500 -subj "/CN=localhost"
+const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cert/localhost.crt");
+const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cert/localhost.key");
+
+fn server() -> quinn::Endpoint {
+	let certs = rustls_pemfile::certs(&mut io::BufReader::new(fs::File::open(CERT).unwrap())).unwrap();
+	let certs = certs.into_iter().map(rustls::Certificate).collect();
+
+	let keys = rustls_pemfile::pkcs8_private_keys(&mut io::BufReader::new(fs::File::open(KEY).unwrap())).unwrap();
+	let key = rustls::PrivateKey(keys[0].clone());
+
+	let mut tls = rustls::ServerConfig::builder()
+		.with_safe_defaults()
+		.with_no_client_auth()
+		.with_single_cert(certs, key)
+		.unwrap();
+	tls.alpn_protocols = vec![quic::ALPN.to_vec(), webtransport_quinn::ALPN.to_vec()];
+
+	let config = quinn::ServerConfig::with_crypto(Arc::new(tls));
+	quinn::Endpoint::server(config, "127.0.0.1:0".parse().unwrap()).unwrap()
+}
+
+fn client_config(url: &Url) -> quinn::ClientConfig {
+	let mut tls = rustls::ClientConfig::builder()
+		.with_safe_defaults()
+		.with_custom_certificate_verifier(Arc::new(NoCertificateVerification))
+		.with_no_client_auth();
+	tls.alpn_protocols = vec![transport::alpn(url).to_vec()];
+
+	quinn::ClientConfig::new(Arc::new(tls))
+}
+
+struct NoCertificateVerification;
+
+impl rustls::client::ServerCertVerifier for NoCertificateVerification {
+	fn verify_server_cert(
+		&self,
+		_end_entity: &rustls::Certificate,
+		_intermediates: &[rustls::Certificate],
+		_server_name: &rustls::ServerName,
+		_scts: &mut dyn Iterator<Item = &[u8]>,
+		_ocsp_response: &[u8],
+		_now: time::SystemTime,
+	) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+		Ok(rustls::client::ServerCertVerified::assertion())
+	}
+}
+
+#[tokio::test]
+async fn moqt_url() {
+	let (mut origin, source) = broadcast::new("");
+	let (sink, cached) = broadcast::new("");
+
+	let mut track = origin.create_track("clock").unwrap();
+	let mut segment = track
+		.create_segment(segment::Info {
+			sequence: VarInt::ZERO,
+			priority: 0,
+			expires: None,
+		})
+		.unwrap();
+	segment
+		.fragment(VarInt::ZERO, 5)
+		.unwrap()
+		.chunk(Bytes::from_static(b"hello"))
+		.unwrap();
+
+	let server = server();
+	let addr = server.local_addr().unwrap();
+	let url = Url::parse(&format!("moqt://127.0.0.1:{}/demo?jwt=token", addr.port())).unwrap();
+
+	let accept = tokio::spawn(async move {
+		let conn = server.accept().await.unwrap().await.unwrap();
+		let handshake = conn.handshake_data().unwrap();
+		let handshake = handshake.downcast::<quinn::crypto::rustls::HandshakeData>().unwrap();
+		assert_eq!(handshake.protocol.as_deref(), Some(quic::ALPN));
+
+		// The path and query are sent during the handshake instead of a CONNECT request.
+		let request = Server::accept(conn).await.unwrap();
+		assert_eq!(request.path(), Some("/demo?jwt=token"));
+
+		let publisher = request.publisher(source).await.unwrap();
+		publisher.run().await
+	});
+
+	let endpoint = quinn::Endpoint::client("127.0.0.1:0".parse().unwrap()).unwrap();
+	let session = transport::connect(&endpoint, client_config(&url), &url).await.unwrap();
+
+	let subscriber = Client::default()
+		.with_url(&url)
+		.subscriber(session, sink)
+		.await
+		.unwrap();
+	tokio::spawn(subscriber.run());
+
+	let mut track = cached.get_track("clock").unwrap();
+	let mut segment = track.segment().await.unwrap().unwrap();
+	let mut fragment = segment.fragment().await.unwrap().unwrap();
+	assert_eq!(fragment.chunk().await.unwrap(), Some(Bytes::from_static(b"hello")));
+
+	assert!(!accept.is_finished());
+}